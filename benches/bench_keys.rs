@@ -12,5 +12,30 @@ fn bench_key_encoding(c: &mut Criterion) {
     });
 }
 
-criterion_group!(keys_benches, bench_key_encoding);
+fn bench_key_decoding(c: &mut Criterion) {
+    let keys: Vec<_> = (0..10000u64)
+        .map(|i| (i, "some-user-name").to_key())
+        .collect();
+
+    let mut group = c.benchmark_group("key_decoding");
+    group.bench_function("owned", |b| {
+        b.iter(|| {
+            for key in &keys {
+                let decoded: (u64, String) = key.clone().try_into().unwrap();
+                black_box(decoded);
+            }
+        });
+    });
+    group.bench_function("borrowed", |b| {
+        b.iter(|| {
+            for key in &keys {
+                let decoded: (u64, &str) = key.decode_borrowed().unwrap();
+                black_box(decoded);
+            }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(keys_benches, bench_key_encoding, bench_key_decoding);
 criterion_main!(keys_benches);
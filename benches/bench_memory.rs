@@ -1,5 +1,5 @@
 use criterion::{Criterion, black_box, criterion_group, criterion_main};
-use stupid_simple_kv::{IntoKey, Kv, MemoryBackend};
+use stupid_simple_kv::{IntoKey, Kv, KvBackend, MemoryBackend};
 
 fn bench_memory_set(c: &mut Criterion) {
     let keys: Vec<_> = (0..1000u64).map(|i| ("num", i).to_key()).collect();
@@ -34,5 +34,56 @@ fn bench_memory_get(c: &mut Criterion) {
     });
 }
 
-criterion_group!(memory_benches, bench_memory_set, bench_memory_get);
+fn bench_memory_delete(c: &mut Criterion) {
+    let backend = Box::new(MemoryBackend::new());
+    let mut kv = Kv::new(backend);
+    let keys: Vec<_> = (0..1000u64).map(|i| ("num", i).to_key()).collect();
+
+    c.bench_function("memory_delete", |b| {
+        b.iter(|| {
+            for (i, k) in keys.iter().enumerate() {
+                kv.set(k, (i as i64).into()).unwrap();
+            }
+            for k in &keys {
+                black_box(kv.delete(k).unwrap());
+            }
+        });
+    });
+}
+
+fn bench_memory_scan(c: &mut Criterion) {
+    let mut backend = MemoryBackend::new();
+    for i in 0..10_000u64 {
+        backend
+            .set(("num", i).to_key(), Some(vec![i as u8; 64]))
+            .unwrap();
+    }
+
+    let mut group = c.benchmark_group("memory_scan");
+    group.bench_function("get_range", |b| {
+        b.iter(|| {
+            let mut sum = 0u64;
+            for (_, value) in backend.get_range(None, None).unwrap() {
+                sum += value[0] as u64;
+            }
+            black_box(sum)
+        });
+    });
+    group.bench_function("for_each_in_range", |b| {
+        b.iter(|| {
+            let mut sum = 0u64;
+            backend.for_each_in_range(None, None, |_, value| sum += value[0] as u64);
+            black_box(sum)
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(
+    memory_benches,
+    bench_memory_set,
+    bench_memory_get,
+    bench_memory_delete,
+    bench_memory_scan
+);
 criterion_main!(memory_benches);
@@ -21,6 +21,60 @@ fn bench_sqlite_set_get(c: &mut Criterion) {
 }
 
 #[cfg(feature = "sqlite")]
-criterion_group!(sqlite_benches, bench_sqlite_set_get);
+fn bench_sqlite_bulk_load(c: &mut Criterion) {
+    let mut group = c.benchmark_group("sqlite_bulk_load_10k");
+    group.sample_size(10);
+    group.bench_function("set_loop", |b| {
+        b.iter(|| {
+            let backend = Box::new(SqliteBackend::in_memory().unwrap());
+            let mut kv = Kv::new(backend);
+            for i in 0..10_000i64 {
+                black_box(kv.set(("bulk", i), i.into())).unwrap();
+            }
+        });
+    });
+    group.bench_function("set_batch", |b| {
+        b.iter(|| {
+            let backend = Box::new(SqliteBackend::in_memory().unwrap());
+            let mut kv = Kv::new(backend);
+            black_box(kv.set_batch((0..10_000i64).map(|i| (("bulk", i), i.into())))).unwrap();
+        });
+    });
+    group.finish();
+}
+
+#[cfg(feature = "sqlite")]
+fn bench_sqlite_delete(c: &mut Criterion) {
+    let backend = Box::new(SqliteBackend::in_memory().unwrap());
+    let mut kv = Kv::new(backend);
+    let keys: Vec<_> = (0..1000i64).map(|i| ("sqlite-key", i).to_key()).collect();
+
+    let mut group = c.benchmark_group("sqlite_delete_1k");
+    group.bench_function("delete", |b| {
+        b.iter(|| {
+            kv.set_batch(keys.iter().map(|k| (k, 0i64.into()))).unwrap();
+            for k in &keys {
+                black_box(kv.delete(k).unwrap());
+            }
+        });
+    });
+    group.bench_function("remove", |b| {
+        b.iter(|| {
+            kv.set_batch(keys.iter().map(|k| (k, 0i64.into()))).unwrap();
+            for k in &keys {
+                black_box(kv.remove(k).unwrap());
+            }
+        });
+    });
+    group.finish();
+}
+
+#[cfg(feature = "sqlite")]
+criterion_group!(
+    sqlite_benches,
+    bench_sqlite_set_get,
+    bench_sqlite_bulk_load,
+    bench_sqlite_delete
+);
 #[cfg(feature = "sqlite")]
 criterion_main!(sqlite_benches);
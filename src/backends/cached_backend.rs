@@ -0,0 +1,307 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+use crate::backends::KvRangeIter;
+use crate::{KvBackend, KvKey, KvResult};
+
+/// A tiny least-recently-used map of raw value blobs.
+#[derive(Debug, Default)]
+struct Lru {
+    capacity: usize,
+    tick: u64,
+    entries: BTreeMap<KvKey, (Vec<u8>, u64)>,
+    order: BTreeMap<u64, KvKey>,
+}
+
+impl Lru {
+    fn with_capacity(capacity: usize) -> Self {
+        Self {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn get(&mut self, key: &KvKey) -> Option<Vec<u8>> {
+        self.tick += 1;
+        let tick = self.tick;
+        let (value, last_used) = self.entries.get_mut(key)?;
+        self.order.remove(last_used);
+        self.order.insert(tick, key.clone());
+        *last_used = tick;
+        Some(value.clone())
+    }
+
+    fn insert(&mut self, key: KvKey, value: Vec<u8>) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.remove(&key);
+        while self.entries.len() >= self.capacity {
+            let Some((_, oldest)) = self.order.pop_first() else {
+                break;
+            };
+            self.entries.remove(&oldest);
+        }
+        self.tick += 1;
+        self.order.insert(self.tick, key.clone());
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    fn remove(&mut self, key: &KvKey) {
+        if let Some((_, last_used)) = self.entries.remove(key) {
+            self.order.remove(&last_used);
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.order.clear();
+    }
+}
+
+/// A backend wrapper that keeps an in-memory LRU cache of values in front of another backend.
+///
+/// Point lookups (such as [`crate::Kv::get`]) are served from the cache when possible.
+/// Range scans always go to the inner backend. Writes and clears invalidate the cache.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{CachedBackend, Kv, MemoryBackend};
+/// let backend = CachedBackend::with_capacity(MemoryBackend::new(), 1024);
+/// let mut kv = Kv::new(Box::new(backend));
+/// kv.set(&("hot", 1u64), "value".into()).unwrap();
+/// assert_eq!(kv.get(&("hot", 1u64)).unwrap(), Some("value".into()));
+/// ```
+#[derive(Debug)]
+pub struct CachedBackend<B: KvBackend> {
+    inner: B,
+    cache: Mutex<Lru>,
+}
+
+impl<B: KvBackend> CachedBackend<B> {
+    /// Wrap `inner`, caching up to `capacity` values.
+    pub fn with_capacity(inner: B, capacity: usize) -> Self {
+        Self {
+            inner,
+            cache: Mutex::new(Lru::with_capacity(capacity)),
+        }
+    }
+
+    /// Unwrap the cache, returning the inner backend.
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: KvBackend> KvBackend for CachedBackend<B> {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.inner.get_range(start, end)
+    }
+
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        self.inner.get_range_iter(start, end)
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.inner.get_range_page(start, end, offset, limit)
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.inner.first(start, end)
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.inner.last(start, end)
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        if let Some(hit) = self.cache.lock().unwrap().get(key) {
+            return Ok(Some(hit));
+        }
+        let value = self.inner.get(key)?;
+        if let Some(v) = &value {
+            self.cache.lock().unwrap().insert(key.clone(), v.clone());
+        }
+        Ok(value)
+    }
+
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        if self.cache.lock().unwrap().get(key).is_some() {
+            return Ok(true);
+        }
+        self.inner.contains_key(key)
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        self.inner.len()
+    }
+
+    fn is_empty(&self) -> KvResult<bool> {
+        self.inner.is_empty()
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        self.cache.lock().unwrap().remove(&key);
+        self.inner.set(key, value)
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        self.cache.lock().unwrap().remove(key);
+        self.inner.delete(key)
+    }
+
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        self.cache.lock().unwrap().remove(key);
+        self.inner.take(key)
+    }
+
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        {
+            let mut cache = self.cache.lock().unwrap();
+            for (key, _) in &items {
+                cache.remove(key);
+            }
+        }
+        self.inner.set_batch(items)
+    }
+
+    fn clear(&mut self) -> KvResult<()> {
+        self.cache.lock().unwrap().clear();
+        self.inner.clear()
+    }
+
+    fn flush(&mut self) -> KvResult<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args)]
+mod tests {
+    use std::sync::Arc;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{IntoKey, Kv, KvValue, MemoryBackend};
+
+    /// Counts point lookups that reach the wrapped backend.
+    struct CountingBackend {
+        inner: MemoryBackend,
+        gets: Arc<AtomicUsize>,
+    }
+
+    impl KvBackend for CountingBackend {
+        fn get_range(
+            &self,
+            start: Option<KvKey>,
+            end: Option<KvKey>,
+        ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+            self.inner.get_range(start, end)
+        }
+
+        fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+            self.gets.fetch_add(1, Ordering::SeqCst);
+            self.inner.get(key)
+        }
+
+        fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+            self.inner.set(key, value)
+        }
+
+        fn clear(&mut self) -> KvResult<()> {
+            self.inner.clear()
+        }
+    }
+
+    fn counting_kv(capacity: usize) -> (Kv, Arc<AtomicUsize>) {
+        let gets = Arc::new(AtomicUsize::new(0));
+        let inner = CountingBackend {
+            inner: MemoryBackend::new(),
+            gets: gets.clone(),
+        };
+        let kv = Kv::new(Box::new(CachedBackend::with_capacity(inner, capacity)));
+        (kv, gets)
+    }
+
+    #[test]
+    fn second_get_is_served_from_cache() -> KvResult<()> {
+        let (mut kv, gets) = counting_kv(8);
+        kv.set(&(1u64, "a"), KvValue::I64(1))?;
+
+        assert_eq!(kv.get(&(1u64, "a"))?, Some(KvValue::I64(1)));
+        assert_eq!(gets.load(Ordering::SeqCst), 1);
+        assert_eq!(kv.get(&(1u64, "a"))?, Some(KvValue::I64(1)));
+        assert_eq!(gets.load(Ordering::SeqCst), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn set_and_clear_invalidate() -> KvResult<()> {
+        let (mut kv, gets) = counting_kv(8);
+        let key = (7u64,).to_key();
+        kv.set(&key, KvValue::I64(1))?;
+        kv.get(&key)?;
+
+        kv.set(&key, KvValue::I64(2))?;
+        assert_eq!(kv.get(&key)?, Some(KvValue::I64(2)));
+        assert_eq!(gets.load(Ordering::SeqCst), 2);
+
+        kv.delete(&key)?;
+        assert_eq!(kv.get(&key)?, None);
+
+        kv.set(&key, KvValue::I64(3))?;
+        kv.get(&key)?;
+        kv.backend.write().clear()?;
+        assert_eq!(kv.get(&key)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn evicts_least_recently_used() -> KvResult<()> {
+        let (mut kv, gets) = counting_kv(2);
+        for i in 0..3u64 {
+            kv.set(&(i,), KvValue::I64(i as i64))?;
+        }
+        kv.get(&(0u64,))?;
+        kv.get(&(1u64,))?;
+        kv.get(&(0u64,))?; // (0,) is now most recently used
+        kv.get(&(2u64,))?; // evicts (1,)
+        let before = gets.load(Ordering::SeqCst);
+
+        kv.get(&(0u64,))?;
+        assert_eq!(gets.load(Ordering::SeqCst), before);
+        kv.get(&(1u64,))?;
+        assert_eq!(gets.load(Ordering::SeqCst), before + 1);
+        Ok(())
+    }
+
+    #[test]
+    fn range_scans_bypass_cache() -> KvResult<()> {
+        let (mut kv, _) = counting_kv(8);
+        for i in 0..5i64 {
+            kv.set(&(1u64, i), KvValue::I64(i))?;
+        }
+        kv.get(&(1u64, 2i64))?;
+        let items = kv.list().prefix(&(1u64,)).entries()?;
+        assert_eq!(items.len(), 5);
+        Ok(())
+    }
+}
@@ -1,8 +1,23 @@
-use std::collections::BTreeMap;
-use std::sync::{Arc, Mutex};
+use std::collections::{BTreeMap, btree_map};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 
+use crate::backends::{KvRangeIter, PagedRange};
 use crate::{KvBackend, KvKey, KvResult};
 
+/// An in-memory backend backed by a sorted map.
+///
+/// Every read takes the map's lock once, so `get_range` sees a single point in time even
+/// while other clones are writing. `get_range_iter` (and iterating through [`crate::Kv`])
+/// fetches a page at a time instead, so a long scan may observe concurrent writes; use
+/// [`MemoryBackend::snapshot_range`] when it must not.
+///
+/// A panic while the map is locked (say, in a [`MemoryBackend::for_each_in_range`]
+/// callback) doesn't break the backend: each operation changes the map in a single step,
+/// so later calls carry on with it instead of failing on the poisoned lock.
+///
+/// Note that `Clone` does *not* copy the data: clones share the same underlying map, so a
+/// write through one is visible through all of them. Use [`MemoryBackend::snapshot`] for an
+/// independent copy.
 #[derive(Debug, Default, Clone)]
 pub struct MemoryBackend {
     // Shared and thread-safe
@@ -15,6 +30,149 @@ impl MemoryBackend {
             map: Arc::new(Mutex::new(BTreeMap::new())),
         }
     }
+
+    /// Build a backend from `(key, value)` pairs in ascending key order.
+    ///
+    /// This is the way to bulk-load: the underlying `BTreeMap` allocates node by node and
+    /// can't reserve capacity up front, so there is no `with_capacity`-style constructor.
+    ///
+    /// Sorted input lets the map be built bottom-up in linear time rather than by repeated
+    /// inserts. Unsorted input still produces a correct map, just more slowly. If a key
+    /// appears more than once, the last value wins.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{IntoKey, Kv, MemoryBackend};
+    /// let rows = (0..3u64).map(|i| ((i,).to_key(), vec![i as u8]));
+    /// let backend = MemoryBackend::from_sorted(rows);
+    /// assert_eq!(Kv::new(Box::new(backend)).len().unwrap(), 3);
+    /// ```
+    pub fn from_sorted(entries: impl IntoIterator<Item = (KvKey, Vec<u8>)>) -> Self {
+        Self {
+            map: Arc::new(Mutex::new(entries.into_iter().collect())),
+        }
+    }
+
+    /// Deep-copy the current contents into a new, independent backend.
+    ///
+    /// Later writes to either backend are not visible in the other, which makes this handy
+    /// for taking a restore point before a risky batch of changes.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let backend = MemoryBackend::new();
+    /// let mut kv = Kv::new(Box::new(backend.clone()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    ///
+    /// let restore_point = backend.snapshot();
+    /// kv.set(&("a",), 2i64.into()).unwrap();
+    ///
+    /// let restored = Kv::new(Box::new(restore_point));
+    /// assert_eq!(restored.get(&("a",)).unwrap(), Some(1i64.into()));
+    /// ```
+    pub fn snapshot(&self) -> MemoryBackend {
+        let map = self.lock().clone();
+        Self {
+            map: Arc::new(Mutex::new(map)),
+        }
+    }
+
+    /// Deep-copy the entries in `[start, end)` into a new, independent backend, atomically.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{IntoKey, Kv, MemoryBackend};
+    /// let backend = MemoryBackend::new();
+    /// let mut kv = Kv::new(Box::new(backend.clone()));
+    /// kv.set(&(1u64, "a"), 1i64.into()).unwrap();
+    /// kv.set(&(2u64, "a"), 2i64.into()).unwrap();
+    ///
+    /// let frozen = backend.snapshot_range(Some((1u64,).to_key()), (1u64,).to_key().successor());
+    /// kv.set(&(1u64, "b"), 3i64.into()).unwrap();
+    /// assert_eq!(Kv::new(Box::new(frozen)).entries().unwrap().len(), 1);
+    /// ```
+    pub fn snapshot_range(&self, start: Option<KvKey>, end: Option<KvKey>) -> MemoryBackend {
+        Self::from_sorted(self.collect_range(start, end, 0, None))
+    }
+
+    /// Call `f` on each entry in `[start, end)`, in key order, without copying anything.
+    ///
+    /// [`KvBackend::get_range`] clones every key and value it returns; when the caller only
+    /// needs to look at the bytes (to decode them, say), this avoids both allocations.
+    /// The whole scan runs under the map's lock, so it sees a single point in time, and `f`
+    /// must not use this backend (or a clone of it) or it will deadlock.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{IntoKey, KvBackend, MemoryBackend};
+    /// let mut backend = MemoryBackend::new();
+    /// for i in 0..4u64 {
+    ///     backend.set((i,).to_key(), Some(vec![i as u8; 8])).unwrap();
+    /// }
+    /// let mut total = 0;
+    /// backend.for_each_in_range(Some((1u64,).to_key()), None, |_key, value| {
+    ///     total += value.len();
+    /// });
+    /// assert_eq!(total, 24);
+    /// ```
+    pub fn for_each_in_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        mut f: impl FnMut(&KvKey, &[u8]),
+    ) {
+        self.with_range(start, end, |range| {
+            for (key, value) in range {
+                f(key, value);
+            }
+        })
+    }
+
+    /// Lock the map, recovering it if a panic poisoned the lock.
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<KvKey, Vec<u8>>> {
+        self.map.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn with_range<T>(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        f: impl FnOnce(btree_map::Range<'_, KvKey, Vec<u8>>) -> T,
+    ) -> T {
+        let map = self.lock();
+        f(map_range(&map, start, end))
+    }
+
+    fn collect_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> Vec<(KvKey, Vec<u8>)> {
+        self.with_range(start, end, |range| {
+            range
+                .skip(offset)
+                .take(limit.unwrap_or(usize::MAX))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+    }
+}
+
+/// The entries of `map` in `[start, end)`, with `None` leaving that side open.
+pub(super) fn map_range(
+    map: &BTreeMap<KvKey, Vec<u8>>,
+    start: Option<KvKey>,
+    end: Option<KvKey>,
+) -> btree_map::Range<'_, KvKey, Vec<u8>> {
+    match (start, end) {
+        (Some(start_key), Some(end_key)) => {
+            // `BTreeMap::range` panics on a reversed range; it should just be empty.
+            let end_key = end_key.max(start_key.clone());
+            map.range(start_key..end_key)
+        }
+        (Some(start_key), None) => map.range(start_key..),
+        (None, Some(end_key)) => map.range(..end_key),
+        (None, None) => map.range::<KvKey, _>(..),
+    }
 }
 
 impl KvBackend for MemoryBackend {
@@ -23,20 +181,58 @@ impl KvBackend for MemoryBackend {
         start: Option<KvKey>,
         end: Option<KvKey>,
     ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
-        let map = self.map.lock().unwrap();
+        Ok(self.collect_range(start, end, 0, None))
+    }
+
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(PagedRange::new(
+            start,
+            end,
+            |start, end, limit| Ok(self.collect_range(start, end, 0, Some(limit))),
+        )))
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        Ok(self.collect_range(start, end, offset, limit))
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.with_range(start, end, |mut range| {
+            range.next().map(|(k, v)| (k.clone(), v.clone()))
+        }))
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.with_range(start, end, |mut range| {
+            range.next_back().map(|(k, v)| (k.clone(), v.clone()))
+        }))
+    }
 
-        let range = match (start, end) {
-            (Some(start_key), Some(end_key)) => map.range(start_key..end_key),
-            (Some(start_key), None) => map.range(start_key..),
-            (None, Some(end_key)) => map.range(..end_key),
-            (None, None) => map.range::<KvKey, _>(..),
-        };
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let map = self.lock();
+        Ok(map.get(key).cloned())
+    }
 
-        Ok(range.map(|(k, v)| (k.clone(), v.clone())).collect())
+    fn len(&self) -> KvResult<usize> {
+        Ok(self.lock().len())
     }
 
     fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
-        let mut map = self.map.lock().unwrap();
+        let mut map = self.lock();
         if let Some(v) = value {
             map.insert(key, v);
         } else {
@@ -45,9 +241,168 @@ impl KvBackend for MemoryBackend {
         Ok(())
     }
 
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        Ok(self.lock().remove(key).is_some())
+    }
+
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        Ok(self.lock().remove(key))
+    }
+
     fn clear(&mut self) -> KvResult<()> {
-        let mut map = self.map.lock().unwrap();
+        let mut map = self.lock();
         map.clear();
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntoKey;
+
+    #[test]
+    fn snapshot_is_independent() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        let key = (1u64,).to_key();
+        backend.set(key.clone(), Some(vec![1]))?;
+
+        let mut snapshot = backend.snapshot();
+        backend.set(key.clone(), Some(vec![2]))?;
+        backend.set((2u64,).to_key(), Some(vec![3]))?;
+        assert_eq!(snapshot.get(&key)?, Some(vec![1]));
+        assert_eq!(snapshot.get_range(None, None)?.len(), 1);
+
+        snapshot.clear()?;
+        assert_eq!(backend.get(&key)?, Some(vec![2]));
+
+        assert_eq!(snapshot.len()?, 0);
+
+        // Clones, on the other hand, share state.
+        let alias = backend.clone();
+        backend.set(key.clone(), None)?;
+        assert_eq!(alias.get(&key)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn for_each_in_range_matches_get_range() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        for i in 0..50u64 {
+            backend.set((i % 5, i).to_key(), Some(i.to_be_bytes().to_vec()))?;
+        }
+        let ranges = [
+            (None, None),
+            (Some((2u64,).to_key()), (2u64,).to_key().successor()),
+            (Some((3u64,).to_key()), None),
+            (None, Some((1u64, 30u64).to_key())),
+            (Some((4u64,).to_key()), Some((1u64,).to_key())),
+        ];
+        for (start, end) in ranges {
+            let mut visited = Vec::new();
+            backend.for_each_in_range(start.clone(), end.clone(), |k, v| {
+                visited.push((k.clone(), v.to_vec()))
+            });
+            assert_eq!(visited, backend.get_range(start, end)?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn survives_a_panic_while_locked() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        backend.set((1u64,).to_key(), Some(vec![1]))?;
+
+        let poisoner = backend.clone();
+        let result = std::thread::spawn(move || {
+            poisoner.for_each_in_range(None, None, |_, _| panic!("boom"));
+        })
+        .join();
+        assert!(result.is_err());
+        assert!(backend.map.is_poisoned());
+
+        assert_eq!(backend.get(&(1u64,).to_key())?, Some(vec![1]));
+        backend.set((2u64,).to_key(), Some(vec![2]))?;
+        assert_eq!(backend.len()?, 2);
+        assert_eq!(backend.snapshot().get_range(None, None)?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn len_tracks_inserts_and_deletes() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        assert_eq!(backend.len()?, 0);
+        for i in 0..10u64 {
+            backend.set((i,).to_key(), Some(vec![0]))?;
+        }
+        backend.set((3u64,).to_key(), Some(vec![1]))?;
+        assert_eq!(backend.len()?, 10);
+        backend.set((3u64,).to_key(), None)?;
+        backend.set((99u64,).to_key(), None)?;
+        assert_eq!(backend.len()?, 9);
+        Ok(())
+    }
+
+    #[test]
+    fn delete_reports_presence() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        let key = (1u64,).to_key();
+        backend.set(key.clone(), Some(vec![1]))?;
+        assert!(backend.delete(&key)?);
+        assert!(!backend.delete(&key)?);
+        assert_eq!(backend.get(&key)?, None);
+
+        backend.set(key.clone(), Some(vec![2]))?;
+        assert_eq!(backend.take(&key)?, Some(vec![2]));
+        assert_eq!(backend.take(&key)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn from_sorted_builds_a_queryable_map() -> KvResult<()> {
+        let rows: Vec<_> = (0..1000u64)
+            .map(|i| ((i,).to_key(), vec![i as u8]))
+            .collect();
+        let backend = MemoryBackend::from_sorted(rows.clone());
+        assert_eq!(backend.len()?, 1000);
+        assert_eq!(backend.get_range(None, None)?, rows);
+        assert_eq!(
+            backend.get_range(Some((10u64,).to_key()), Some((13u64,).to_key()))?,
+            rows[10..13].to_vec()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn snapshot_range_sees_one_point_in_time() -> KvResult<()> {
+        let backend = MemoryBackend::new();
+        let mut writer = backend.clone();
+        for i in 0..1000u64 {
+            writer.set((i,).to_key(), Some(vec![0]))?;
+        }
+
+        let mut seen = 0;
+        let snapshot = backend.snapshot_range(None, None);
+        for entry in snapshot.get_range_iter(None, None)? {
+            let (key, value) = entry?;
+            assert_eq!(value, vec![0]);
+            // Rewrite every key, drop a later one and add a new one as we go.
+            writer.set(key.clone(), Some(vec![1]))?;
+            writer.set((999u64 - seen,).to_key(), None)?;
+            writer.set((5000u64 + seen,).to_key(), Some(vec![2]))?;
+            seen += 1;
+        }
+        assert_eq!(seen, 1000);
+
+        let frozen = backend.snapshot_range(Some((5000u64,).to_key()), None);
+        writer.clear()?;
+        assert_eq!(frozen.len()?, 1000);
+        assert!(
+            frozen
+                .get_range(None, None)?
+                .iter()
+                .all(|(_, v)| v == &vec![2])
+        );
+        Ok(())
+    }
+}
@@ -1,25 +1,196 @@
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
 use crate::{KvKey, KvResult};
 
+pub(crate) mod cached_backend;
 pub(crate) mod memory_backend;
+#[cfg(feature = "redb")]
+pub(crate) mod redb_backend;
+#[cfg(feature = "redis")]
+pub(crate) mod redis_backend;
+pub(crate) mod rwlock_memory_backend;
+#[cfg(feature = "sled")]
+pub(crate) mod sled_backend;
 #[cfg(feature = "sqlite")]
 pub(crate) mod sqlite_backend;
+pub(crate) mod tee_backend;
+
+/// Boxed iterator over raw `(key, value)` pairs, as returned by [`KvBackend::get_range_iter`].
+pub type KvRangeIter<'a> = Box<dyn Iterator<Item = KvResult<(KvKey, Vec<u8>)>> + 'a>;
+
+/// A backend shared between a [`crate::Kv`], its clones, and the queries it hands out.
+///
+/// Locking recovers the backend if a panic poisoned the lock, as [`MemoryBackend`] does:
+/// user closures (`Kv::update`, `Kv::transaction`, ...) run under the write lock, and one
+/// panicking shouldn't break every clone of the store. Each backend write is a single
+/// call made after the closure returns, so a panic never leaves one half-applied.
+///
+/// [`MemoryBackend`]: crate::MemoryBackend
+#[derive(Clone)]
+pub(crate) struct SharedBackend(Arc<RwLock<Box<dyn KvBackend>>>);
+
+impl SharedBackend {
+    pub(crate) fn new(backend: Box<dyn KvBackend>) -> Self {
+        Self(Arc::new(RwLock::new(backend)))
+    }
+
+    pub(crate) fn read(&self) -> RwLockReadGuard<'_, Box<dyn KvBackend>> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub(crate) fn write(&self) -> RwLockWriteGuard<'_, Box<dyn KvBackend>> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Number of rows fetched at a time by [`PagedRange`].
+pub(crate) const PAGE_SIZE: usize = 256;
+
+/// Streams a range by repeatedly fetching bounded pages, resuming just after the last key seen.
+///
+/// `fetch` is called with `(start, end, limit)` and must return at most `limit` pairs in key order.
+pub(crate) struct PagedRange<F> {
+    fetch: F,
+    next_start: Option<KvKey>,
+    end: Option<KvKey>,
+    page: std::vec::IntoIter<(KvKey, Vec<u8>)>,
+    done: bool,
+}
+
+impl<F> PagedRange<F>
+where
+    F: FnMut(Option<KvKey>, Option<KvKey>, usize) -> KvResult<Vec<(KvKey, Vec<u8>)>>,
+{
+    pub(crate) fn new(start: Option<KvKey>, end: Option<KvKey>, fetch: F) -> Self {
+        Self {
+            fetch,
+            next_start: start,
+            end,
+            page: Vec::new().into_iter(),
+            done: false,
+        }
+    }
+}
+
+impl<F> Iterator for PagedRange<F>
+where
+    F: FnMut(Option<KvKey>, Option<KvKey>, usize) -> KvResult<Vec<(KvKey, Vec<u8>)>>,
+{
+    type Item = KvResult<(KvKey, Vec<u8>)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(pair) = self.page.next() {
+            return Some(Ok(pair));
+        }
+        if self.done {
+            return None;
+        }
+        let page = match (self.fetch)(self.next_start.take(), self.end.clone(), PAGE_SIZE) {
+            Ok(page) => page,
+            Err(e) => {
+                self.done = true;
+                return Some(Err(e));
+            }
+        };
+        if page.len() < PAGE_SIZE {
+            self.done = true;
+        }
+        self.next_start = page.last().map(|(k, _)| k.immediate_successor());
+        self.page = page.into_iter();
+        self.page.next().map(Ok)
+    }
+}
 
 /// Trait for all key-value store backends.
 ///
 /// Backends must provide the following semantics:
 /// - **Keys are encoded, ordered byte strings**: All key operations should respect the lexicographic ordering of the encoded bytes, as provided by [`KvKey`].
 /// - **Atomicity**: `set` and `clear` must complete their operation or return an error.
+/// - **Batches**: `set_batch` applies many writes at once. The default just calls `set` in a loop; backends with transactions should override it to apply the whole batch atomically.
 /// - **Value format**: Values must be raw binary blobs. Serialization and deserialization are handled by the library; the backend just stores the [`u8`] arrays.
-/// - **Iteration**: `get_range` should return all keys in `[start, end)` order. If `end` is `None`, iteration should go until the end of the keyspace.
+/// - **Iteration**: `get_range` must return all keys in `[start, end)` in ascending byte order; [`crate::Kv::entries`] and [`crate::KvListBuilder::entries`] promise sorted results on top of it. A range whose `start` is at or after its `end` is empty. If `end` is `None`, iteration should go until the end of the keyspace.
+/// - **Streaming**: `get_range_iter` yields the same items as `get_range`, but lazily. The default implementation just wraps `get_range`; backends that can page through their storage should override it.
+/// - **Windows**: `get_range_page` skips `offset` pairs of the range and returns at most `limit` of the rest. The default skips over `get_range_iter`; override it if the backend can skip natively (e.g. SQL `OFFSET`).
+/// - **Endpoints**: `first` and `last` return the smallest and largest pair in `[start, end)`. The defaults scan the range; override them if the backend can seek directly.
+/// - **Point lookups**: `get` must return the value stored under exactly the given key. The default implementation is built on `get_range`; override it if the backend has a cheaper way.
+/// - **Existence checks**: `contains_key` reports whether a key is present. The default calls `get`; override it if the backend can answer without fetching the value.
+/// - **Counting**: `len` returns the total number of stored keys. The default counts `get_range(None, None)`; override it if the backend keeps or can compute a count cheaply. `is_empty` defaults to checking `first`.
+/// - **Deletes**: `delete` removes a key and reports whether it was present. The default checks with `get` and then calls `set(key, None)`; backends that learn this from the removal itself should override it.
+/// - **Takes**: `take` removes a key and returns the value it held. The default is `get` followed by `delete`; override it if the backend can do both in one step.
+/// - **Durability**: `flush` forces writes made so far onto durable storage. The default is a no-op, which is right for in-memory backends; file-backed backends should override it.
 /// - **Error Reporting**: All failures must return a [`KvResult::Err`] with a suitable error value.
+/// - **Thread safety**: Backends must be `Send + Sync`, so a [`crate::Kv`] can be shared across threads. Reads take `&self` and may run concurrently; use interior locking for any state they touch.
 ///
 /// See [`memory_backend`] and (if enabled) [`sqlite_backend`] for correct implementation templates.
-pub trait KvBackend {
+pub trait KvBackend: Send + Sync {
     fn get_range(
         &self,
         start: Option<KvKey>,
         end: Option<KvKey>,
     ) -> KvResult<Vec<(KvKey, Vec<u8>)>>;
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(self.get_range(start, end)?.into_iter().map(Ok)))
+    }
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.get_range_iter(start, end)?
+            .skip(offset)
+            .take(limit.unwrap_or(usize::MAX))
+            .collect()
+    }
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.get_range_iter(start, end)?.next().transpose()
+    }
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.get_range(start, end)?.pop())
+    }
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let pairs = self.get_range(Some(key.clone()), Some(key.immediate_successor()))?;
+        Ok(pairs.into_iter().next().map(|(_, v)| v))
+    }
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        Ok(self.get(key)?.is_some())
+    }
+    fn len(&self) -> KvResult<usize> {
+        Ok(self.get_range(None, None)?.len())
+    }
+    fn is_empty(&self) -> KvResult<bool> {
+        Ok(self.first(None, None)?.is_none())
+    }
     fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()>;
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        let existed = self.get(key)?.is_some();
+        self.set(key.clone(), None)?;
+        Ok(existed)
+    }
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let value = self.get(key)?;
+        if value.is_some() {
+            self.delete(key)?;
+        }
+        Ok(value)
+    }
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        for (key, value) in items {
+            self.set(key, value)?;
+        }
+        Ok(())
+    }
     fn clear(&mut self) -> KvResult<()>;
+    fn flush(&mut self) -> KvResult<()> {
+        Ok(())
+    }
 }
@@ -0,0 +1,245 @@
+use std::ops::Bound;
+use std::path::Path;
+
+use crate::backends::KvRangeIter;
+use crate::{KvBackend, KvError, KvKey, KvResult};
+use redb::{
+    Database, ReadOnlyTable, ReadableDatabase, ReadableTableMetadata, Table, TableDefinition,
+};
+
+const TABLE: TableDefinition<&[u8], &[u8]> = TableDefinition::new("kv");
+
+type RawTable = ReadOnlyTable<&'static [u8], &'static [u8]>;
+type RawPair<'a> = (
+    redb::AccessGuard<'a, &'static [u8]>,
+    redb::AccessGuard<'a, &'static [u8]>,
+);
+
+/// A backend storing entries in a single [redb](https://docs.rs/redb) table, a pure-Rust
+/// alternative to [`crate::SqliteBackend`] for persistent stores.
+///
+/// redb orders `&[u8]` keys bytewise, the same way [`KvKey`] does. Every write (and every
+/// [`KvBackend::set_batch`]) is its own redb write transaction, durable once it returns.
+/// Reads see a snapshot taken when they start.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{Kv, RedbBackend};
+/// let mut kv = Kv::new(Box::new(RedbBackend::in_memory().unwrap()));
+/// kv.set(&("greeting",), "hello".into()).unwrap();
+/// ```
+pub struct RedbBackend {
+    db: Database,
+}
+
+fn redb_error(error: impl Into<redb::Error>) -> KvError {
+    KvError::RedbError(error.into())
+}
+
+fn pair(item: Result<RawPair<'_>, redb::StorageError>) -> KvResult<(KvKey, Vec<u8>)> {
+    let (key, value) = item.map_err(redb_error)?;
+    Ok((KvKey(key.value().to_vec()), value.value().to_vec()))
+}
+
+impl RedbBackend {
+    /// Open the database file at `path`, creating it if it doesn't exist.
+    pub fn file(path: &Path) -> KvResult<Self> {
+        Self::from_database(Database::create(path).map_err(redb_error)?)
+    }
+
+    /// A database held entirely in memory.
+    pub fn in_memory() -> KvResult<Self> {
+        let db = Database::builder()
+            .create_with_backend(redb::backends::InMemoryBackend::new())
+            .map_err(redb_error)?;
+        Self::from_database(db)
+    }
+
+    /// Use an already opened database. Entries go in a table named `kv`.
+    pub fn from_database(db: Database) -> KvResult<Self> {
+        let backend = Self { db };
+        // Create the table up front so reads of a fresh database don't fail.
+        backend.write(|_| Ok(()))?;
+        Ok(backend)
+    }
+
+    fn read(&self) -> KvResult<RawTable> {
+        self.db
+            .begin_read()
+            .map_err(redb_error)?
+            .open_table(TABLE)
+            .map_err(redb_error)
+    }
+
+    /// Run `f` in a write transaction and commit it.
+    fn write<T>(
+        &self,
+        f: impl FnOnce(&mut Table<&'static [u8], &'static [u8]>) -> Result<T, redb::Error>,
+    ) -> KvResult<T> {
+        let txn = self.db.begin_write().map_err(redb_error)?;
+        let out = {
+            let mut table = txn.open_table(TABLE).map_err(redb_error)?;
+            f(&mut table).map_err(KvError::RedbError)?
+        };
+        txn.commit().map_err(redb_error)?;
+        Ok(out)
+    }
+
+    fn range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<redb::Range<'static, &'static [u8], &'static [u8]>> {
+        let table = self.read()?;
+        // An empty or reversed range: hand back an empty iterator over the same table.
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) if start >= end => (Some(start.clone()), Some(start)),
+            bounds => bounds,
+        };
+        let lo = start
+            .as_ref()
+            .map_or(Bound::Unbounded, |key| Bound::Included(key.0.as_slice()));
+        let hi = end
+            .as_ref()
+            .map_or(Bound::Unbounded, |key| Bound::Excluded(key.0.as_slice()));
+        table.range::<&[u8]>((lo, hi)).map_err(redb_error)
+    }
+}
+
+impl KvBackend for RedbBackend {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.range(start, end)?.map(pair).collect()
+    }
+
+    /// Walks a read snapshot lazily, so nothing is buffered.
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(self.range(start, end)?.map(pair)))
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.range(start, end)?.next_back().map(pair).transpose()
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let value = self.read()?.get(key.0.as_slice()).map_err(redb_error)?;
+        Ok(value.map(|v| v.value().to_vec()))
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        Ok(self.read()?.len().map_err(redb_error)? as usize)
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        self.set_batch(vec![(key, value)])
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        Ok(self.take(key)?.is_some())
+    }
+
+    /// Reads and removes the entry in one write transaction.
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        self.write(|table| {
+            let old = table.remove(key.0.as_slice())?;
+            Ok(old.map(|v| v.value().to_vec()))
+        })
+    }
+
+    /// Applies the whole batch in a single write transaction.
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        self.write(|table| {
+            for (key, value) in &items {
+                match value {
+                    Some(val) => {
+                        table.insert(key.0.as_slice(), val.as_slice())?;
+                    }
+                    None => {
+                        table.remove(key.0.as_slice())?;
+                    }
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn clear(&mut self) -> KvResult<()> {
+        self.write(|table| Ok(table.retain(|_, _| false)?))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntoKey, Kv, KvValue};
+
+    #[test]
+    fn redb_passes_conformance_suite() {
+        crate::testing::run_backend_conformance(|| Box::new(RedbBackend::in_memory().unwrap()));
+    }
+
+    #[test]
+    fn redb_orders_keys_like_memory() -> KvResult<()> {
+        let mut redb = Kv::new(Box::new(RedbBackend::in_memory()?));
+        let mut memory = Kv::new(Box::new(crate::MemoryBackend::new()));
+        for kv in [&mut redb, &mut memory] {
+            for i in [3i64, -7, 12, 0, -1] {
+                kv.set((1u64, i), KvValue::I64(i))?;
+            }
+            kv.set(("a",), KvValue::Null)?;
+            kv.set(("a", "b"), KvValue::Null)?;
+            kv.set(("b",), KvValue::Null)?;
+        }
+        assert_eq!(redb.entries()?, memory.entries()?);
+        assert_eq!(
+            redb.list().prefix(&(1u64,)).entries()?,
+            memory.list().prefix(&(1u64,)).entries()?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn redb_prefix_iter() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(RedbBackend::in_memory()?));
+        for i in 0..5i64 {
+            kv.set((String::from("users"), i), KvValue::I64(i))?;
+        }
+        kv.set((String::from("userz"), 0i64), KvValue::Null)?;
+        let results = kv.list().prefix(&(String::from("users"),)).entries()?;
+        assert_eq!(results.len(), 5);
+        let streamed = kv
+            .list()
+            .prefix(&(String::from("users"),))
+            .iter()?
+            .collect::<KvResult<Vec<_>>>()?;
+        assert_eq!(streamed, results);
+        Ok(())
+    }
+
+    #[test]
+    fn redb_survives_reopen() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kv.redb");
+        {
+            let mut kv = Kv::new(Box::new(RedbBackend::file(&path)?));
+            kv.set_batch((0..20u64).map(|i| ((i,), KvValue::I64(i as i64))))?;
+            kv.delete((3u64,))?;
+        }
+        let kv = Kv::new(Box::new(RedbBackend::file(&path)?));
+        assert_eq!(kv.len()?, 19);
+        assert_eq!(kv.get((7u64,))?, Some(KvValue::I64(7)));
+        assert_eq!(kv.get((3u64,))?, None);
+        assert_eq!(
+            kv.entries()?.last().map(|(k, _)| k.clone()),
+            Some((19u64,).to_key())
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,323 @@
+use std::sync::Mutex;
+
+use crate::backends::{KvRangeIter, PagedRange};
+use crate::{KvBackend, KvError, KvKey, KvResult};
+use redis::Connection;
+
+/// A backend storing entries in a Redis server, so several processes can share one store.
+///
+/// Each store lives under a namespace (`"sskv"` unless [`RedisBackend::with_namespace`] is
+/// used) and takes two Redis keys: a sorted set `<namespace>:keys` holding every encoded key
+/// with score 0, and a hash `<namespace>:values` mapping each key to its value. Members of a
+/// sorted set with equal scores are ordered bytewise, so ranges are read with
+/// `ZRANGEBYLEX` in exactly [`KvKey`] order. Writes go through `MULTI`/`EXEC`, so the two
+/// structures change together.
+///
+/// A range read lists the keys first and then fetches their values; an entry deleted by
+/// another client in between is left out of the result.
+///
+/// # Example
+/// ```rust,no_run
+/// use stupid_simple_kv::{Kv, RedisBackend};
+/// let backend = RedisBackend::open("redis://127.0.0.1/").unwrap().with_namespace("app");
+/// let mut kv = Kv::new(Box::new(backend));
+/// kv.set(&("greeting",), "hello".into()).unwrap();
+/// ```
+pub struct RedisBackend {
+    conn: Mutex<Connection>,
+    keys: String,
+    values: String,
+}
+
+impl RedisBackend {
+    /// Connect to the server at `url`, e.g. `redis://127.0.0.1/`.
+    pub fn open(url: &str) -> KvResult<Self> {
+        let conn = redis::Client::open(url)
+            .and_then(|client| client.get_connection())
+            .map_err(KvError::RedisError)?;
+        Ok(Self::from_connection(conn))
+    }
+
+    /// Use an existing connection.
+    pub fn from_connection(conn: Connection) -> Self {
+        Self {
+            conn: Mutex::new(conn),
+            keys: String::new(),
+            values: String::new(),
+        }
+        .with_namespace("sskv")
+    }
+
+    /// Keep this store's entries under `namespace`, so several stores can share a server.
+    /// [`KvBackend::clear`] only removes the entries in this namespace.
+    pub fn with_namespace(mut self, namespace: &str) -> Self {
+        self.keys = format!("{namespace}:keys");
+        self.values = format!("{namespace}:values");
+        self
+    }
+
+    fn query_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        descending: bool,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        // `[` makes a lex bound inclusive and `(` exclusive; `-` and `+` are the open ends.
+        let min = start.map_or(b"-".to_vec(), |key| [b"[".as_slice(), &key.0].concat());
+        let max = end.map_or(b"+".to_vec(), |key| [b"(".as_slice(), &key.0].concat());
+        let mut cmd = if descending {
+            let mut cmd = redis::cmd("ZREVRANGEBYLEX");
+            cmd.arg(&self.keys).arg(max).arg(min);
+            cmd
+        } else {
+            let mut cmd = redis::cmd("ZRANGEBYLEX");
+            cmd.arg(&self.keys).arg(min).arg(max);
+            cmd
+        };
+        if limit.is_some() || offset > 0 {
+            // A negative count means "no limit".
+            let count = limit.map_or(-1, |l| l as i64);
+            cmd.arg("LIMIT").arg(offset).arg(count);
+        }
+
+        let mut conn = self.conn.lock()?;
+        let keys: Vec<Vec<u8>> = cmd.query(&mut *conn).map_err(KvError::RedisError)?;
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+        let values: Vec<Option<Vec<u8>>> = redis::cmd("HMGET")
+            .arg(&self.values)
+            .arg(&keys)
+            .query(&mut *conn)
+            .map_err(KvError::RedisError)?;
+        Ok(keys
+            .into_iter()
+            .zip(values)
+            .filter_map(|(key, value)| Some((KvKey(key), value?)))
+            .collect())
+    }
+
+    fn exec<T: redis::FromRedisValue>(&mut self, pipe: &redis::Pipeline) -> KvResult<T> {
+        pipe.query(self.conn.get_mut()?)
+            .map_err(KvError::RedisError)
+    }
+
+    /// Queue a write of `key` onto an atomic pipeline.
+    fn queue_write(&self, pipe: &mut redis::Pipeline, key: &KvKey, value: Option<&Vec<u8>>) {
+        match value {
+            Some(val) => pipe
+                .cmd("ZADD")
+                .arg(&self.keys)
+                .arg(0)
+                .arg(&key.0)
+                .ignore()
+                .cmd("HSET")
+                .arg(&self.values)
+                .arg(&key.0)
+                .arg(val)
+                .ignore(),
+            None => pipe
+                .cmd("ZREM")
+                .arg(&self.keys)
+                .arg(&key.0)
+                .ignore()
+                .cmd("HDEL")
+                .arg(&self.values)
+                .arg(&key.0)
+                .ignore(),
+        };
+    }
+}
+
+impl KvBackend for RedisBackend {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.query_range(start, end, false, 0, None)
+    }
+
+    /// Streams entries a page at a time, so only one page of results is held in memory.
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(PagedRange::new(
+            start,
+            end,
+            |start, end, limit| self.query_range(start, end, false, 0, Some(limit)),
+        )))
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.query_range(start, end, false, offset, limit)
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.query_range(start, end, false, 0, Some(1))?.pop())
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.query_range(start, end, true, 0, Some(1))?.pop())
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        redis::cmd("HGET")
+            .arg(&self.values)
+            .arg(&key.0)
+            .query(&mut *self.conn.lock()?)
+            .map_err(KvError::RedisError)
+    }
+
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        redis::cmd("HEXISTS")
+            .arg(&self.values)
+            .arg(&key.0)
+            .query(&mut *self.conn.lock()?)
+            .map_err(KvError::RedisError)
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        redis::cmd("ZCARD")
+            .arg(&self.keys)
+            .query(&mut *self.conn.lock()?)
+            .map_err(KvError::RedisError)
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        self.queue_write(&mut pipe, &key, value.as_ref());
+        self.exec(&pipe)
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("ZREM")
+            .arg(&self.keys)
+            .arg(&key.0)
+            .ignore()
+            .cmd("HDEL")
+            .arg(&self.values)
+            .arg(&key.0);
+        let (removed,): (usize,) = self.exec(&pipe)?;
+        Ok(removed > 0)
+    }
+
+    /// Reads and deletes the entry in one `MULTI` block.
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let mut pipe = redis::pipe();
+        pipe.atomic().cmd("HGET").arg(&self.values).arg(&key.0);
+        self.queue_write(&mut pipe, key, None);
+        let (value,): (Option<Vec<u8>>,) = self.exec(&pipe)?;
+        Ok(value)
+    }
+
+    /// Applies the whole batch in a single `MULTI` block.
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (key, value) in &items {
+            self.queue_write(&mut pipe, key, value.as_ref());
+        }
+        self.exec(&pipe)
+    }
+
+    /// Removes this namespace's keys; the rest of the server is left alone.
+    fn clear(&mut self) -> KvResult<()> {
+        let mut pipe = redis::pipe();
+        pipe.atomic()
+            .cmd("DEL")
+            .arg(&self.keys)
+            .arg(&self.values)
+            .ignore();
+        self.exec(&pipe)
+    }
+}
+
+/// These tests need a Redis server, so they're ignored by default. Run them with
+/// `SSKV_REDIS_URL=redis://127.0.0.1/ cargo test --features redis -- --ignored redis`.
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{IntoKey, Kv, KvValue};
+
+    /// A fresh, empty namespace per call, so tests don't see each other's entries.
+    fn backend() -> RedisBackend {
+        static NEXT: AtomicUsize = AtomicUsize::new(0);
+        let url = std::env::var("SSKV_REDIS_URL").expect("set SSKV_REDIS_URL to run this test");
+        let namespace = format!(
+            "sskv-test-{}-{}",
+            std::process::id(),
+            NEXT.fetch_add(1, Ordering::Relaxed)
+        );
+        let mut backend = RedisBackend::open(&url)
+            .expect("SSKV_REDIS_URL is set but the server is unreachable")
+            .with_namespace(&namespace);
+        backend.clear().unwrap();
+        backend
+    }
+
+    #[test]
+    #[ignore = "needs a Redis server at SSKV_REDIS_URL"]
+    fn redis_passes_conformance_suite() {
+        crate::testing::run_backend_conformance(|| Box::new(backend()));
+    }
+
+    #[test]
+    #[ignore = "needs a Redis server at SSKV_REDIS_URL"]
+    fn redis_range_scans() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(backend()));
+        for i in (0..50i64).rev() {
+            kv.set((1u64, i), KvValue::I64(i))?;
+        }
+        kv.set((2u64, 0i64), KvValue::Null)?;
+        kv.set(("other",), KvValue::Null)?;
+
+        let prefixed = kv.list().prefix(&(1u64,)).entries()?;
+        let values: Vec<KvValue> = prefixed.into_iter().map(|(_, v)| v).collect();
+        assert_eq!(values, (0..50).map(KvValue::I64).collect::<Vec<_>>());
+
+        let window = kv
+            .list()
+            .start(&(1u64, 10i64))
+            .end(&(1u64, 13i64))
+            .entries()?;
+        let keys: Vec<KvKey> = window.into_iter().map(|(k, _)| k).collect();
+        assert_eq!(keys, [(1u64, 10i64), (1, 11), (1, 12)].map(|k| k.to_key()));
+
+        let page = kv.list().prefix(&(1u64,)).offset(45).limit(10).entries()?;
+        assert_eq!(page.len(), 5);
+        assert_eq!(kv.len()?, 52);
+        Ok(())
+    }
+
+    #[test]
+    #[ignore = "needs a Redis server at SSKV_REDIS_URL"]
+    fn redis_clear_only_touches_its_namespace() -> KvResult<()> {
+        let (mut a, mut b) = (backend(), backend());
+        a.set((1u64,).to_key(), Some(b"a".to_vec()))?;
+        b.set((1u64,).to_key(), Some(b"b".to_vec()))?;
+        a.clear()?;
+        assert!(a.is_empty()?);
+        assert_eq!(b.get(&(1u64,).to_key())?, Some(b"b".to_vec()));
+        b.clear()
+    }
+}
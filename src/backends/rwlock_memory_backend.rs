@@ -0,0 +1,166 @@
+use std::collections::{BTreeMap, btree_map};
+use std::sync::{Arc, RwLock};
+
+use crate::backends::memory_backend::map_range;
+use crate::backends::{KvRangeIter, PagedRange};
+use crate::{KvBackend, KvKey, KvResult};
+
+/// An in-memory backend like [`crate::MemoryBackend`], but guarded by a read-write lock.
+///
+/// Any number of readers can scan the map at once; writers wait for exclusive access. Pick
+/// this over [`crate::MemoryBackend`] when several threads read through clones of the same
+/// backend. As with `MemoryBackend`, clones share the same underlying map.
+#[derive(Debug, Default, Clone)]
+pub struct RwLockMemoryBackend {
+    map: Arc<RwLock<BTreeMap<KvKey, Vec<u8>>>>,
+}
+
+impl RwLockMemoryBackend {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn with_range<T>(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        f: impl FnOnce(btree_map::Range<'_, KvKey, Vec<u8>>) -> T,
+    ) -> KvResult<T> {
+        let map = self.map.read()?;
+        Ok(f(map_range(&map, start, end)))
+    }
+
+    fn collect_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.with_range(start, end, |range| {
+            range
+                .skip(offset)
+                .take(limit.unwrap_or(usize::MAX))
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect()
+        })
+    }
+}
+
+impl KvBackend for RwLockMemoryBackend {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.collect_range(start, end, 0, None)
+    }
+
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(PagedRange::new(
+            start,
+            end,
+            |start, end, limit| self.collect_range(start, end, 0, Some(limit)),
+        )))
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.collect_range(start, end, offset, limit)
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.with_range(start, end, |mut range| {
+            range.next().map(|(k, v)| (k.clone(), v.clone()))
+        })
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.with_range(start, end, |mut range| {
+            range.next_back().map(|(k, v)| (k.clone(), v.clone()))
+        })
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        Ok(self.map.read()?.get(key).cloned())
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        Ok(self.map.read()?.len())
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        let mut map = self.map.write()?;
+        if let Some(v) = value {
+            map.insert(key, v);
+        } else {
+            map.remove(&key);
+        }
+        Ok(())
+    }
+
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        let mut map = self.map.write()?;
+        for (key, value) in items {
+            match value {
+                Some(v) => map.insert(key, v),
+                None => map.remove(&key),
+            };
+        }
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        Ok(self.map.write()?.remove(key).is_some())
+    }
+
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        Ok(self.map.write()?.remove(key))
+    }
+
+    fn clear(&mut self) -> KvResult<()> {
+        self.map.write()?.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::IntoKey;
+
+    #[test]
+    fn batch_range_and_clear() -> KvResult<()> {
+        let mut backend = RwLockMemoryBackend::new();
+        backend.set_batch(
+            (0..10u64)
+                .map(|i| ((i,).to_key(), Some(vec![i as u8])))
+                .collect(),
+        )?;
+        backend.set_batch(vec![((4u64,).to_key(), None)])?;
+        assert_eq!(backend.len()?, 9);
+
+        let range = backend.get_range(Some((2u64,).to_key()), Some((6u64,).to_key()))?;
+        let values: Vec<u8> = range.into_iter().map(|(_, v)| v[0]).collect();
+        assert_eq!(values, [2, 3, 5]);
+        assert_eq!(backend.last(None, None)?.map(|(_, v)| v), Some(vec![9]));
+
+        let alias = backend.clone();
+        backend.clear()?;
+        assert!(alias.is_empty()?);
+        Ok(())
+    }
+}
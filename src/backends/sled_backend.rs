@@ -0,0 +1,202 @@
+use std::ops::Bound;
+use std::path::Path;
+
+use crate::backends::KvRangeIter;
+use crate::{KvBackend, KvError, KvKey, KvResult};
+
+/// A backend storing entries in a [sled](https://docs.rs/sled) tree.
+///
+/// sled orders keys bytewise, the same way [`KvKey`] does, so range reads map straight
+/// onto its native range scans. Reads are lazy and need no locking of our own.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{Kv, SledBackend};
+/// let mut kv = Kv::new(Box::new(SledBackend::temporary().unwrap()));
+/// kv.set(&("greeting",), "hello".into()).unwrap();
+/// ```
+pub struct SledBackend {
+    tree: sled::Tree,
+}
+
+impl SledBackend {
+    /// Open (or create) a sled database at `path` and use its default tree.
+    pub fn open(path: &Path) -> KvResult<Self> {
+        let db = sled::open(path).map_err(KvError::SledError)?;
+        Ok(Self::from_tree((*db).clone()))
+    }
+
+    /// A database in a temporary directory that is removed when the backend is dropped.
+    pub fn temporary() -> KvResult<Self> {
+        let db = sled::Config::new()
+            .temporary(true)
+            .open()
+            .map_err(KvError::SledError)?;
+        Ok(Self::from_tree((*db).clone()))
+    }
+
+    /// Store entries in `tree`, e.g. one opened with [`sled::Db::open_tree`] on a database
+    /// the application already uses. [`KvBackend::clear`] empties only this tree.
+    pub fn from_tree(tree: sled::Tree) -> Self {
+        Self { tree }
+    }
+
+    fn range(&self, start: Option<KvKey>, end: Option<KvKey>) -> sled::Iter {
+        let lo = start.map_or(Bound::Unbounded, |key| Bound::Included(key.0));
+        let hi = end.map_or(Bound::Unbounded, |key| Bound::Excluded(key.0));
+        self.tree.range::<Vec<u8>, _>((lo, hi))
+    }
+}
+
+fn pair(item: sled::Result<(sled::IVec, sled::IVec)>) -> KvResult<(KvKey, Vec<u8>)> {
+    let (key, value) = item.map_err(KvError::SledError)?;
+    Ok((KvKey(key.to_vec()), value.to_vec()))
+}
+
+impl KvBackend for SledBackend {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.range(start, end).map(pair).collect()
+    }
+
+    /// Walks the tree lazily, so nothing is buffered.
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(self.range(start, end).map(pair)))
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.range(start, end).next_back().map(pair).transpose()
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let value = self.tree.get(&key.0).map_err(KvError::SledError)?;
+        Ok(value.map(|v| v.to_vec()))
+    }
+
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        self.tree.contains_key(&key.0).map_err(KvError::SledError)
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        Ok(self.tree.len())
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        match value {
+            Some(val) => self.tree.insert(key.0, val),
+            None => self.tree.remove(key.0),
+        }
+        .map_err(KvError::SledError)?;
+        Ok(())
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        Ok(self.take(key)?.is_some())
+    }
+
+    /// sled's `remove` returns the old value, so this is a single atomic step.
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let value = self.tree.remove(&key.0).map_err(KvError::SledError)?;
+        Ok(value.map(|v| v.to_vec()))
+    }
+
+    /// Applies the whole batch atomically with a [`sled::Batch`].
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        let mut batch = sled::Batch::default();
+        for (key, value) in items {
+            match value {
+                Some(val) => batch.insert(key.0, val),
+                None => batch.remove(key.0),
+            }
+        }
+        self.tree.apply_batch(batch).map_err(KvError::SledError)
+    }
+
+    /// Removes the tree's keys one by one; another handle on the same tree may observe a
+    /// partially cleared state.
+    fn clear(&mut self) -> KvResult<()> {
+        self.tree.clear().map_err(KvError::SledError)
+    }
+
+    /// Blocks until every write so far has reached disk.
+    fn flush(&mut self) -> KvResult<()> {
+        self.tree.flush().map_err(KvError::SledError)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{IntoKey, Kv, KvValue};
+
+    #[test]
+    fn sled_passes_conformance_suite() {
+        crate::testing::run_backend_conformance(|| Box::new(SledBackend::temporary().unwrap()));
+    }
+
+    #[test]
+    fn sled_kv_set_get_delete() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let mut kv = Kv::new(Box::new(SledBackend::open(dir.path())?));
+        let tup = (String::from("num"),);
+        let value = KvValue::I64(42);
+
+        kv.set(&tup, value.clone())?;
+        assert_eq!(kv.get(&tup)?, Some(value.clone()));
+        assert_eq!(kv.delete(&tup)?, Some((tup.to_key(), value)));
+        assert_eq!(kv.get(&tup)?, None);
+        assert!(!kv.remove(&tup)?);
+        Ok(())
+    }
+
+    #[test]
+    fn sled_prefix_iter() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(SledBackend::temporary()?));
+        for i in (0..5i64).rev() {
+            kv.set((String::from("users"), i), KvValue::I64(i))?;
+        }
+        kv.set((String::from("userz"), 0i64), KvValue::Null)?;
+        let results = kv.list().prefix(&(String::from("users"),)).entries()?;
+        let vals: Vec<_> = results.into_iter().map(|(_, v)| v).collect();
+        assert_eq!(vals, (0..5).map(KvValue::I64).collect::<Vec<_>>());
+
+        let last = kv.list().prefix(&(String::from("users"),)).iter()?.last();
+        assert_eq!(last.transpose()?.map(|(_, v)| v), Some(KvValue::I64(4)));
+        Ok(())
+    }
+
+    #[test]
+    fn sled_survives_reopen() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        {
+            let mut kv = Kv::new(Box::new(SledBackend::open(dir.path())?));
+            kv.set_batch((0..20u64).map(|i| ((i,), KvValue::I64(i as i64))))?;
+            kv.flush()?;
+        }
+        let kv = Kv::new(Box::new(SledBackend::open(dir.path())?));
+        assert_eq!(kv.len()?, 20);
+        assert_eq!(kv.get((7u64,))?, Some(KvValue::I64(7)));
+        Ok(())
+    }
+
+    #[test]
+    fn sled_clear_leaves_other_trees() -> KvResult<()> {
+        let db = sled::Config::new().temporary(true).open().unwrap();
+        let mut a = SledBackend::from_tree(db.open_tree("a").unwrap());
+        let mut b = SledBackend::from_tree(db.open_tree("b").unwrap());
+        a.set((1u64,).to_key(), Some(vec![1]))?;
+        b.set((1u64,).to_key(), Some(vec![2]))?;
+        a.clear()?;
+        assert!(a.is_empty()?);
+        assert_eq!(b.get(&(1u64,).to_key())?, Some(vec![2]));
+        Ok(())
+    }
+}
@@ -1,40 +1,236 @@
+use std::collections::HashSet;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
 
+use crate::backends::{KvRangeIter, PagedRange};
 use crate::{KvBackend, KvError, KvKey, KvResult};
-use rusqlite::{Connection, params};
+use rusqlite::{Connection, OptionalExtension, params};
 
+/// `PRAGMA synchronous` levels, see <https://www.sqlite.org/pragma.html#pragma_synchronous>.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SqliteSynchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+
+impl SqliteSynchronous {
+    fn as_sql(self) -> &'static str {
+        match self {
+            SqliteSynchronous::Off => "OFF",
+            SqliteSynchronous::Normal => "NORMAL",
+            SqliteSynchronous::Full => "FULL",
+            SqliteSynchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// Connection settings for [`SqliteBackend::open_with_options`].
+///
+/// Every field defaults to SQLite's own default and the table to `kv`, so
+/// `SqliteOptions::default()` behaves exactly like [`SqliteBackend::file`].
+///
+/// # Example
+/// ```rust
+/// use std::time::Duration;
+/// use stupid_simple_kv::SqliteOptions;
+/// let options = SqliteOptions {
+///     busy_timeout: Some(Duration::from_secs(5)),
+///     wal: true,
+///     ..Default::default()
+/// };
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct SqliteOptions {
+    /// How long to wait on a locked database before failing with "database is locked".
+    pub busy_timeout: Option<Duration>,
+    /// Switch the database to write-ahead logging (`PRAGMA journal_mode = WAL`).
+    pub wal: bool,
+    /// Override `PRAGMA synchronous`.
+    pub synchronous: Option<SqliteSynchronous>,
+    /// Keep entries in this table instead of `kv`; see [`SqliteBackend::file_with_table`]
+    /// for the allowed names.
+    pub table: Option<String>,
+}
+
+/// A backend storing entries in a single SQLite table, named `kv` unless opened with
+/// [`SqliteBackend::file_with_table`] or [`SqliteOptions::table`].
+///
+/// The connection sits behind a mutex so the backend can be shared across threads; queries
+/// from different threads take turns on it.
 pub struct SqliteBackend {
-    conn: Connection,
+    conn: Mutex<Connection>,
+    /// The table name, quoted for use in SQL.
+    table: String,
+    /// The set of stored keys, if enabled with [`SqliteBackend::with_key_index`]. The inner
+    /// `None` means it hasn't been loaded yet.
+    key_index: Option<Mutex<Option<HashSet<Vec<u8>>>>>,
 }
 
 impl SqliteBackend {
     pub fn in_memory() -> KvResult<Self> {
         let conn = Connection::open_in_memory().map_err(KvError::SqliteError)?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS kv (key BLOB PRIMARY KEY, value BLOB NOT NULL);",
-        )
-        .map_err(KvError::SqliteError)?;
-        Ok(SqliteBackend { conn })
+        Self::from_connection(conn, "kv")
     }
 
     pub fn file(path: &Path) -> KvResult<Self> {
+        Self::open_with_options(path, SqliteOptions::default())
+    }
+
+    /// Open a database file, keeping entries in the table `table` (created if missing), so
+    /// several stores can share one file.
+    ///
+    /// The name must be a plain identifier: ASCII letters, digits and underscores, not
+    /// starting with a digit or with SQLite's reserved `sqlite_` prefix. Anything else fails
+    /// with [`KvError::Other`] before the file is opened.
+    ///
+    /// # Example
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, SqliteBackend};
+    /// let dir = tempfile::tempdir().unwrap();
+    /// let path = dir.path().join("app.db");
+    /// let mut users = Kv::new(Box::new(SqliteBackend::file_with_table(&path, "users").unwrap()));
+    /// let sessions = Kv::new(Box::new(SqliteBackend::file_with_table(&path, "sessions").unwrap()));
+    /// users.set(&("alice",), 1i64.into()).unwrap();
+    /// assert!(sessions.is_empty().unwrap());
+    /// ```
+    pub fn file_with_table(path: &Path, table: &str) -> KvResult<Self> {
+        let options = SqliteOptions {
+            table: Some(table.to_string()),
+            ..Default::default()
+        };
+        Self::open_with_options(path, options)
+    }
+
+    /// Open a database file, applying the given [`SqliteOptions`] before first use.
+    ///
+    /// An invalid [`SqliteOptions::table`] fails before the file is opened, as with
+    /// [`SqliteBackend::file_with_table`].
+    pub fn open_with_options(path: &Path, options: SqliteOptions) -> KvResult<Self> {
+        let table = options.table.as_deref().unwrap_or("kv");
+        validate_table_name(table)?;
         let conn = Connection::open(path).map_err(KvError::SqliteError)?;
-        conn.execute_batch(
-            "CREATE TABLE IF NOT EXISTS kv (key BLOB PRIMARY KEY, value BLOB NOT NULL);",
-        )
+        if let Some(timeout) = options.busy_timeout {
+            conn.busy_timeout(timeout).map_err(KvError::SqliteError)?;
+        }
+        if options.wal {
+            conn.pragma_update(None, "journal_mode", "WAL")
+                .map_err(KvError::SqliteError)?;
+        }
+        if let Some(sync) = options.synchronous {
+            conn.pragma_update(None, "synchronous", sync.as_sql())
+                .map_err(KvError::SqliteError)?;
+        }
+        Self::from_connection(conn, table)
+    }
+
+    fn from_connection(conn: Connection, table: &str) -> KvResult<Self> {
+        let table = format!("\"{table}\"");
+        conn.execute_batch(&format!(
+            "CREATE TABLE IF NOT EXISTS {table} (key BLOB PRIMARY KEY, value BLOB NOT NULL);"
+        ))
         .map_err(KvError::SqliteError)?;
-        Ok(SqliteBackend { conn })
+        Ok(SqliteBackend {
+            conn: Mutex::new(conn),
+            table,
+            key_index: None,
+        })
+    }
+
+    /// Keep an in-memory set of every stored key, so lookups of absent keys never reach SQLite.
+    ///
+    /// The set is loaded with a keys-only scan on the first lookup, then kept up to date by
+    /// this backend's own writes. It costs memory proportional to the total key size, and it
+    /// goes stale if another connection writes to the same database, so only enable it when
+    /// this backend is the database's sole writer.
+    ///
+    /// # Example
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, SqliteBackend};
+    /// let backend = SqliteBackend::in_memory().unwrap().with_key_index();
+    /// let kv = Kv::new(Box::new(backend));
+    /// assert_eq!(kv.get(&("missing",)).unwrap(), None);
+    /// ```
+    pub fn with_key_index(mut self) -> Self {
+        self.key_index = Some(Mutex::new(None));
+        self
+    }
+
+    /// Look `key` up in the key index, loading it first if needed. `None` if there is no index.
+    fn indexed_contains(&self, key: &KvKey) -> KvResult<Option<bool>> {
+        let Some(index) = &self.key_index else {
+            return Ok(None);
+        };
+        let mut index = index.lock()?;
+        let keys = match &mut *index {
+            Some(keys) => keys,
+            None => index.insert(self.scan_keys()?),
+        };
+        Ok(Some(keys.contains(&key.0)))
+    }
+
+    fn scan_keys(&self) -> KvResult<HashSet<Vec<u8>>> {
+        let conn = self.conn.lock()?;
+        let mut stmt = conn
+            .prepare(&format!("SELECT key FROM {}", self.table))
+            .map_err(KvError::SqliteError)?;
+        let keys = stmt
+            .query_map([], |row| row.get(0))
+            .map_err(KvError::SqliteError)?
+            .collect::<Result<_, _>>()
+            .map_err(KvError::SqliteError)?;
+        Ok(keys)
+    }
+
+    /// Record writes that have reached the database in the key index, if it's loaded.
+    fn update_index<'a>(
+        &mut self,
+        writes: impl IntoIterator<Item = (&'a KvKey, bool)>,
+    ) -> KvResult<()> {
+        if let Some(index) = &mut self.key_index
+            && let Some(keys) = index.get_mut()?
+        {
+            for (key, present) in writes {
+                if present {
+                    keys.insert(key.0.clone());
+                } else {
+                    keys.remove(&key.0);
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-impl KvBackend for SqliteBackend {
-    fn get_range(
+fn validate_table_name(table: &str) -> KvResult<()> {
+    let mut chars = table.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && !table.to_ascii_lowercase().starts_with("sqlite_");
+    if valid {
+        Ok(())
+    } else {
+        Err(KvError::Other(format!(
+            "Invalid SQLite table name {table:?}"
+        )))
+    }
+}
+
+impl SqliteBackend {
+    fn query_range(
         &self,
         start: Option<KvKey>,
         end: Option<KvKey>,
+        descending: bool,
+        offset: usize,
+        limit: Option<usize>,
     ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
         // Build SQL WHERE clause for start/end
-        let mut sql = String::from("SELECT key, value FROM kv");
+        let mut sql = format!("SELECT key, value FROM {}", self.table);
         let mut clauses = Vec::new();
         let mut params_vec: Vec<Vec<u8>> = Vec::new();
 
@@ -50,9 +246,19 @@ impl KvBackend for SqliteBackend {
             sql.push_str(" WHERE ");
             sql.push_str(&clauses.join(" AND "));
         }
-        sql.push_str(" ORDER BY key ASC");
+        sql.push_str(if descending {
+            " ORDER BY key DESC"
+        } else {
+            " ORDER BY key ASC"
+        });
+        if limit.is_some() || offset > 0 {
+            // SQLite only accepts OFFSET after a LIMIT; a negative limit means "no limit".
+            let limit = limit.map_or(-1, |l| l as i64);
+            sql.push_str(&format!(" LIMIT {limit} OFFSET {offset}"));
+        }
 
-        let mut stmt = self.conn.prepare(&sql).map_err(KvError::SqliteError)?;
+        let conn = self.conn.lock()?;
+        let mut stmt = conn.prepare(&sql).map_err(KvError::SqliteError)?;
         let params: Vec<&dyn rusqlite::ToSql> = params_vec
             .iter()
             .map(|v| v as &dyn rusqlite::ToSql)
@@ -70,38 +276,214 @@ impl KvBackend for SqliteBackend {
             .map_err(KvError::SqliteError)?;
         Ok(results)
     }
+}
+
+impl KvBackend for SqliteBackend {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.query_range(start, end, false, 0, None)
+    }
+
+    /// Streams rows a page at a time, so only one page of results is held in memory.
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        Ok(Box::new(PagedRange::new(
+            start,
+            end,
+            |start, end, limit| self.query_range(start, end, false, 0, Some(limit)),
+        )))
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.query_range(start, end, false, offset, limit)
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.query_range(start, end, false, 0, Some(1))?.pop())
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        Ok(self.query_range(start, end, true, 0, Some(1))?.pop())
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        if self.indexed_contains(key)? == Some(false) {
+            return Ok(None);
+        }
+        self.conn
+            .lock()?
+            .query_row(
+                &format!("SELECT value FROM {} WHERE key = ?1", self.table),
+                params![key.0],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(KvError::SqliteError)
+    }
+
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        if let Some(present) = self.indexed_contains(key)? {
+            return Ok(present);
+        }
+        self.conn
+            .lock()?
+            .query_row(
+                &format!("SELECT 1 FROM {} WHERE key = ?1", self.table),
+                params![key.0],
+                |_| Ok(()),
+            )
+            .optional()
+            .map(|row| row.is_some())
+            .map_err(KvError::SqliteError)
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        let count: i64 = self
+            .conn
+            .lock()?
+            .query_row(&format!("SELECT COUNT(*) FROM {}", self.table), [], |row| {
+                row.get(0)
+            })
+            .map_err(KvError::SqliteError)?;
+        Ok(count as usize)
+    }
 
     fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        let present = value.is_some();
         match value {
             Some(val) => {
                 self.conn
+                    .get_mut()?
                     .execute(
-                        "REPLACE INTO kv (key, value) VALUES (?1, ?2)",
+                        &format!("REPLACE INTO {} (key, value) VALUES (?1, ?2)", self.table),
                         params![key.0, val],
                     )
                     .map_err(KvError::SqliteError)?;
             }
             None => {
                 self.conn
-                    .execute("DELETE FROM kv WHERE key = ?1", params![key.0])
+                    .get_mut()?
+                    .execute(
+                        &format!("DELETE FROM {} WHERE key = ?1", self.table),
+                        params![key.0],
+                    )
                     .map_err(KvError::SqliteError)?;
             }
         }
-        Ok(())
+        self.update_index([(&key, present)])
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        let changed = self
+            .conn
+            .get_mut()?
+            .execute(
+                &format!("DELETE FROM {} WHERE key = ?1", self.table),
+                params![key.0],
+            )
+            .map_err(KvError::SqliteError)?;
+        self.update_index([(key, false)])?;
+        Ok(changed > 0)
+    }
+
+    /// Reads and deletes the row in one transaction.
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let tx = self
+            .conn
+            .get_mut()?
+            .transaction()
+            .map_err(KvError::SqliteError)?;
+        let value: Option<Vec<u8>> = tx
+            .query_row(
+                &format!("SELECT value FROM {} WHERE key = ?1", self.table),
+                params![key.0],
+                |row| row.get(0),
+            )
+            .optional()
+            .map_err(KvError::SqliteError)?;
+        if value.is_some() {
+            tx.execute(
+                &format!("DELETE FROM {} WHERE key = ?1", self.table),
+                params![key.0],
+            )
+            .map_err(KvError::SqliteError)?;
+        }
+        tx.commit().map_err(KvError::SqliteError)?;
+        self.update_index([(key, false)])?;
+        Ok(value)
+    }
+
+    /// Applies the whole batch in a single transaction, reusing prepared statements across rows.
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        let tx = self
+            .conn
+            .get_mut()?
+            .transaction()
+            .map_err(KvError::SqliteError)?;
+        {
+            let mut replace = tx
+                .prepare_cached(&format!(
+                    "REPLACE INTO {} (key, value) VALUES (?1, ?2)",
+                    self.table
+                ))
+                .map_err(KvError::SqliteError)?;
+            let mut delete = tx
+                .prepare_cached(&format!("DELETE FROM {} WHERE key = ?1", self.table))
+                .map_err(KvError::SqliteError)?;
+            for (key, value) in &items {
+                match value {
+                    Some(val) => replace.execute(params![key.0, val]),
+                    None => delete.execute(params![key.0]),
+                }
+                .map_err(KvError::SqliteError)?;
+            }
+        }
+        tx.commit().map_err(KvError::SqliteError)?;
+        self.update_index(items.iter().map(|(key, value)| (key, value.is_some())))
     }
 
     fn clear(&mut self) -> KvResult<()> {
         self.conn
-            .execute("DELETE FROM kv", [])
+            .get_mut()?
+            .execute(&format!("DELETE FROM {}", self.table), [])
             .map_err(KvError::SqliteError)?;
+        if let Some(index) = &mut self.key_index {
+            *index.get_mut()? = Some(HashSet::new());
+        }
         Ok(())
     }
+
+    /// In WAL mode, checkpoints the log into the main database file. Otherwise every
+    /// committed write is already in the main file and this only costs a round-trip.
+    fn flush(&mut self) -> KvResult<()> {
+        self.conn
+            .get_mut()?
+            .query_row("PRAGMA wal_checkpoint(FULL)", [], |_| Ok(()))
+            .map_err(KvError::SqliteError)
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod tests {
     use super::*;
-    use crate::{Kv, KvValue};
+    use crate::{IntoKey, Kv, KvValue};
 
     #[test]
     fn sqlite_set_and_get() -> KvResult<()> {
@@ -144,4 +526,265 @@ mod tests {
         assert!(vals.contains(&KvValue::I64(2)));
         Ok(())
     }
+
+    #[test]
+    fn sqlite_delete_reports_presence() -> KvResult<()> {
+        let mut backend = SqliteBackend::in_memory()?;
+        let key = KvKey(vec![1, 2, 3]);
+        backend.set(key.clone(), Some(vec![9]))?;
+        assert!(backend.delete(&key)?);
+        assert!(!backend.delete(&key)?);
+        assert_eq!(backend.get(&key)?, None);
+
+        backend.set(key.clone(), Some(vec![8]))?;
+        assert_eq!(backend.take(&key)?, Some(vec![8]));
+        assert_eq!(backend.take(&key)?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_key_index_short_circuits_absent_keys() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kv.db");
+        {
+            let mut kv = Kv::new(Box::new(SqliteBackend::file(&path)?));
+            kv.set_batch((0..50u64).map(|i| ((i,), KvValue::I64(i as i64))))?;
+        }
+
+        let mut backend = SqliteBackend::file(&path)?.with_key_index();
+        for i in 0..50u64 {
+            assert!(backend.contains_key(&(i,).to_key())?);
+        }
+        assert!(backend.get(&(7u64,).to_key())?.is_some());
+        assert!(!backend.contains_key(&(50u64,).to_key())?);
+
+        // Writes keep the index in step.
+        backend.set((50u64,).to_key(), Some(vec![1]))?;
+        backend.delete(&(0u64,).to_key())?;
+        backend.take(&(1u64,).to_key())?;
+        backend.set_batch(vec![
+            ((51u64,).to_key(), Some(vec![2])),
+            ((2u64,).to_key(), None),
+        ])?;
+        assert_eq!(backend.get(&(50u64,).to_key())?, Some(vec![1]));
+        assert!(backend.contains_key(&(51u64,).to_key())?);
+        for i in 0..3u64 {
+            assert!(!backend.contains_key(&(i,).to_key())?);
+        }
+
+        // With the table gone, any lookup that reached SQLite would fail.
+        backend
+            .conn
+            .get_mut()
+            .unwrap()
+            .execute_batch("ALTER TABLE kv RENAME TO kv_hidden")
+            .unwrap();
+        assert_eq!(backend.get(&(2u64,).to_key())?, None);
+        assert_eq!(backend.get(&(1000u64,).to_key())?, None);
+        assert!(!backend.contains_key(&(1000u64,).to_key())?);
+        assert!(backend.get(&(3u64,).to_key()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_tables_in_one_file_are_isolated() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kv.db");
+        let mut a = Kv::new(Box::new(SqliteBackend::file_with_table(&path, "store_a")?));
+        let mut b = Kv::new(Box::new(SqliteBackend::file_with_table(&path, "store_b")?));
+        a.set(("shared", 1u64), KvValue::I64(1))?;
+        b.set(("shared", 1u64), KvValue::I64(2))?;
+        b.set(("only_b",), KvValue::Null)?;
+
+        assert_eq!(a.get(("shared", 1u64))?, Some(KvValue::I64(1)));
+        assert_eq!(b.get(("shared", 1u64))?, Some(KvValue::I64(2)));
+        assert_eq!(a.len()?, 1);
+        a.clear()?;
+        assert_eq!(b.len()?, 2);
+
+        // The default table is separate again.
+        assert!(Kv::new(Box::new(SqliteBackend::file(&path)?)).is_empty()?);
+
+        for bad in [
+            "",
+            "1st",
+            "kv; DROP TABLE store_b",
+            "a-b",
+            "sqlite_master",
+            "\"kv\"",
+        ] {
+            assert!(
+                SqliteBackend::file_with_table(&path, bad).is_err(),
+                "{bad:?} should be rejected"
+            );
+        }
+        assert_eq!(b.len()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_len() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(SqliteBackend::in_memory()?));
+        assert!(kv.is_empty()?);
+        for i in 0..10u64 {
+            kv.set(&(i,), KvValue::Null)?;
+        }
+        kv.set(&(3u64,), KvValue::Bool(true))?;
+        assert_eq!(kv.len()?, 10);
+        kv.delete(&(3u64,))?;
+        assert_eq!(kv.len()?, 9);
+        assert!(!kv.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_open_with_options_applies_pragmas() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let options = SqliteOptions {
+            busy_timeout: Some(Duration::from_millis(2500)),
+            wal: true,
+            synchronous: Some(SqliteSynchronous::Normal),
+            table: None,
+        };
+        let backend = SqliteBackend::open_with_options(&dir.path().join("kv.db"), options)?;
+
+        let pragma = |name: &str| -> rusqlite::Result<String> {
+            backend
+                .conn
+                .lock()
+                .unwrap()
+                .query_row(&format!("PRAGMA {name}"), [], |row| {
+                    row.get::<_, rusqlite::types::Value>(0)
+                })
+                .map(|v| match v {
+                    rusqlite::types::Value::Integer(i) => i.to_string(),
+                    rusqlite::types::Value::Text(s) => s,
+                    other => format!("{other:?}"),
+                })
+        };
+        assert_eq!(pragma("journal_mode").unwrap(), "wal");
+        assert_eq!(pragma("busy_timeout").unwrap(), "2500");
+        assert_eq!(pragma("synchronous").unwrap(), "1");
+
+        let mut kv = Kv::new(Box::new(backend));
+        kv.set(&("wal",), KvValue::Bool(true))?;
+        assert_eq!(kv.get(&("wal",))?, Some(KvValue::Bool(true)));
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_open_with_options_uses_table() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kv.db");
+        let options = SqliteOptions {
+            wal: true,
+            table: Some("users".to_string()),
+            ..Default::default()
+        };
+        let mut users = Kv::new(Box::new(SqliteBackend::open_with_options(&path, options)?));
+        users.set(&("alice",), KvValue::I64(1))?;
+
+        let same = Kv::new(Box::new(SqliteBackend::file_with_table(&path, "users")?));
+        assert_eq!(same.get(&("alice",))?, Some(KvValue::I64(1)));
+        assert!(Kv::new(Box::new(SqliteBackend::file(&path)?)).is_empty()?);
+
+        let bad = SqliteOptions {
+            table: Some("sqlite_master".to_string()),
+            ..Default::default()
+        };
+        assert!(SqliteBackend::open_with_options(&path, bad).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_flush_then_reopen() -> KvResult<()> {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("kv.db");
+        let options = SqliteOptions {
+            wal: true,
+            ..Default::default()
+        };
+        {
+            let mut kv = Kv::new(Box::new(SqliteBackend::open_with_options(&path, options)?));
+            kv.set_batch((0..100u64).map(|i| ((i,), KvValue::I64(i as i64))))?;
+            kv.flush()?;
+        }
+        {
+            let mut kv = Kv::new(Box::new(SqliteBackend::file(&path)?));
+            assert_eq!(kv.len()?, 100);
+            assert_eq!(kv.get(&(42u64,))?, Some(KvValue::I64(42)));
+            kv.flush()?;
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_batch_load_and_read_back() -> KvResult<()> {
+        let backend = Box::new(SqliteBackend::in_memory()?);
+        let mut kv = Kv::new(backend);
+        kv.set_batch((0..10_000i64).map(|i| ((String::from("bulk"), i), KvValue::I64(i * 2))))?;
+
+        let results = kv.list().prefix(&(String::from("bulk"),)).entries()?;
+        assert_eq!(results.len(), 10_000);
+        for (i, (key, value)) in results.into_iter().enumerate() {
+            let (_, idx): (String, i64) = key.try_into()?;
+            assert_eq!(idx, i as i64);
+            assert_eq!(value, KvValue::I64(idx * 2));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_first_and_last() -> KvResult<()> {
+        let backend = Box::new(SqliteBackend::in_memory()?);
+        let mut kv = Kv::new(backend);
+        for i in 0..5i64 {
+            kv.set(&(String::from("a"), i), KvValue::I64(i))?;
+            kv.set(&(String::from("b"), i), KvValue::I64(10 + i))?;
+        }
+        let prefix = (String::from("a"),);
+        let (_, first) = kv.first(Some(&prefix))?.unwrap();
+        let (_, last) = kv.last(Some(&prefix))?.unwrap();
+        assert_eq!((first, last), (KvValue::I64(0), KvValue::I64(4)));
+        assert_eq!(kv.last(None)?.unwrap().1, KvValue::I64(14));
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_offset_and_limit() -> KvResult<()> {
+        let backend = Box::new(SqliteBackend::in_memory()?);
+        let mut kv = Kv::new(backend);
+        for i in 0..20i64 {
+            kv.set(&(String::from("grid"), i), KvValue::I64(i))?;
+        }
+        let prefix = (String::from("grid"),);
+        let window = kv.list().prefix(&prefix).offset(5).limit(3).entries()?;
+        let vals: Vec<_> = window.into_iter().map(|(_, v)| v).collect();
+        assert_eq!(
+            vals,
+            vec![KvValue::I64(5), KvValue::I64(6), KvValue::I64(7)]
+        );
+
+        // Offset without a limit runs to the end of the range.
+        assert_eq!(kv.list().prefix(&prefix).offset(15).entries()?.len(), 5);
+        assert!(kv.list().prefix(&prefix).offset(20).entries()?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn sqlite_range_iter_spans_pages() -> KvResult<()> {
+        let backend = Box::new(SqliteBackend::in_memory()?);
+        let mut kv = Kv::new(backend);
+        for i in 0..1000i64 {
+            kv.set(&(String::from("rows"), i), KvValue::I64(i))?;
+        }
+        let mut expected = 0i64;
+        for item in kv.list().prefix(&(String::from("rows"),)).iter()? {
+            let (_, v) = item?;
+            assert_eq!(v, KvValue::I64(expected));
+            expected += 1;
+        }
+        assert_eq!(expected, 1000);
+        Ok(())
+    }
 }
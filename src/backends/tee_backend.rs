@@ -0,0 +1,226 @@
+use crate::backends::KvRangeIter;
+use crate::{KvBackend, KvError, KvKey, KvResult};
+
+type ErrorHook = Box<dyn Fn(&KvError) + Send + Sync>;
+
+/// A backend wrapper that applies every write to two backends and reads from the first.
+///
+/// This is meant for migrations: keep serving from `primary` while `secondary` receives the
+/// same writes (and a backfill of older data), then switch over once they agree.
+///
+/// Writes go to `primary` first; if that fails, `secondary` is left alone. By default an
+/// error from `secondary` is returned too, even though `primary` has already applied the
+/// write. Use [`TeeBackend::on_secondary_error`] to report those errors elsewhere instead.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{Kv, MemoryBackend, TeeBackend};
+/// let backend = TeeBackend::new(MemoryBackend::new(), MemoryBackend::new());
+/// let mut kv = Kv::new(Box::new(backend));
+/// kv.set(&("a",), 1i64.into()).unwrap();
+/// ```
+pub struct TeeBackend<P: KvBackend, S: KvBackend> {
+    primary: P,
+    secondary: S,
+    on_secondary_error: Option<ErrorHook>,
+}
+
+impl<P: KvBackend, S: KvBackend> TeeBackend<P, S> {
+    /// Read from `primary` and write to both.
+    pub fn new(primary: P, secondary: S) -> Self {
+        Self {
+            primary,
+            secondary,
+            on_secondary_error: None,
+        }
+    }
+
+    /// Pass errors from the secondary backend to `f` instead of returning them, so a failing
+    /// secondary never fails a write that `primary` accepted.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{MemoryBackend, TeeBackend};
+    /// let backend = TeeBackend::new(MemoryBackend::new(), MemoryBackend::new())
+    ///     .on_secondary_error(|e| eprintln!("secondary write failed: {e}"));
+    /// ```
+    pub fn on_secondary_error(mut self, f: impl Fn(&KvError) + Send + Sync + 'static) -> Self {
+        self.on_secondary_error = Some(Box::new(f));
+        self
+    }
+
+    /// Unwrap the pair, returning `(primary, secondary)`.
+    pub fn into_inner(self) -> (P, S) {
+        (self.primary, self.secondary)
+    }
+
+    /// Apply a write to the secondary backend, routing its error to the hook if one is set.
+    fn mirror<T>(&mut self, write: impl FnOnce(&mut S) -> KvResult<T>) -> KvResult<()> {
+        match (write(&mut self.secondary), &self.on_secondary_error) {
+            (Ok(_), _) => Ok(()),
+            (Err(e), Some(hook)) => {
+                hook(&e);
+                Ok(())
+            }
+            (Err(e), None) => Err(e),
+        }
+    }
+}
+
+impl<P: KvBackend, S: KvBackend> KvBackend for TeeBackend<P, S> {
+    fn get_range(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.primary.get_range(start, end)
+    }
+
+    fn get_range_iter(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<KvRangeIter<'_>> {
+        self.primary.get_range_iter(start, end)
+    }
+
+    fn get_range_page(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+        offset: usize,
+        limit: Option<usize>,
+    ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+        self.primary.get_range_page(start, end, offset, limit)
+    }
+
+    fn first(
+        &self,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.primary.first(start, end)
+    }
+
+    fn last(&self, start: Option<KvKey>, end: Option<KvKey>) -> KvResult<Option<(KvKey, Vec<u8>)>> {
+        self.primary.last(start, end)
+    }
+
+    fn get(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        self.primary.get(key)
+    }
+
+    fn contains_key(&self, key: &KvKey) -> KvResult<bool> {
+        self.primary.contains_key(key)
+    }
+
+    fn len(&self) -> KvResult<usize> {
+        self.primary.len()
+    }
+
+    fn is_empty(&self) -> KvResult<bool> {
+        self.primary.is_empty()
+    }
+
+    fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+        self.primary.set(key.clone(), value.clone())?;
+        self.mirror(|secondary| secondary.set(key, value))
+    }
+
+    fn delete(&mut self, key: &KvKey) -> KvResult<bool> {
+        let existed = self.primary.delete(key)?;
+        self.mirror(|secondary| secondary.delete(key))?;
+        Ok(existed)
+    }
+
+    fn take(&mut self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        let value = self.primary.take(key)?;
+        self.mirror(|secondary| secondary.delete(key))?;
+        Ok(value)
+    }
+
+    fn set_batch(&mut self, items: Vec<(KvKey, Option<Vec<u8>>)>) -> KvResult<()> {
+        self.primary.set_batch(items.clone())?;
+        self.mirror(|secondary| secondary.set_batch(items))
+    }
+
+    fn clear(&mut self) -> KvResult<()> {
+        self.primary.clear()?;
+        self.mirror(|secondary| secondary.clear())
+    }
+
+    fn flush(&mut self) -> KvResult<()> {
+        self.primary.flush()?;
+        self.mirror(|secondary| secondary.flush())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use super::*;
+    use crate::{IntoKey, MemoryBackend};
+
+    /// Rejects every write.
+    struct FailingBackend;
+
+    impl KvBackend for FailingBackend {
+        fn get_range(
+            &self,
+            _start: Option<KvKey>,
+            _end: Option<KvKey>,
+        ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+            Ok(Vec::new())
+        }
+
+        fn set(&mut self, _key: KvKey, _value: Option<Vec<u8>>) -> KvResult<()> {
+            Err(KvError::Other("read-only".into()))
+        }
+
+        fn clear(&mut self) -> KvResult<()> {
+            Err(KvError::Other("read-only".into()))
+        }
+    }
+
+    #[test]
+    fn writes_reach_both_and_reads_come_from_primary() -> KvResult<()> {
+        let mut secondary = MemoryBackend::new();
+        secondary.set(("only-secondary",).to_key(), Some(vec![0]))?;
+        let mut tee = TeeBackend::new(MemoryBackend::new(), secondary);
+
+        tee.set(("a",).to_key(), Some(vec![1]))?;
+        tee.set_batch(vec![
+            (("b",).to_key(), Some(vec![2])),
+            (("c",).to_key(), Some(vec![3])),
+        ])?;
+        assert_eq!(tee.take(&("c",).to_key())?, Some(vec![3]));
+
+        assert_eq!(tee.get(&("only-secondary",).to_key())?, None);
+        assert_eq!(tee.len()?, 2);
+
+        let (primary, secondary) = tee.into_inner();
+        let expected = vec![(("a",).to_key(), vec![1]), (("b",).to_key(), vec![2])];
+        assert_eq!(primary.get_range(None, None)?, expected);
+        assert_eq!(secondary.get(&("a",).to_key())?, Some(vec![1]));
+        assert_eq!(secondary.get(&("b",).to_key())?, Some(vec![2]));
+        assert_eq!(secondary.get(&("c",).to_key())?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn secondary_errors_propagate_unless_hooked() -> KvResult<()> {
+        let mut tee = TeeBackend::new(MemoryBackend::new(), FailingBackend);
+        assert!(tee.set(("a",).to_key(), Some(vec![1])).is_err());
+        // The primary still applied the write.
+        assert_eq!(tee.get(&("a",).to_key())?, Some(vec![1]));
+
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let mut tee = TeeBackend::new(MemoryBackend::new(), FailingBackend)
+            .on_secondary_error(move |e| log.lock().unwrap().push(e.to_string()));
+        tee.set(("a",).to_key(), Some(vec![1]))?;
+        tee.clear()?;
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        Ok(())
+    }
+}
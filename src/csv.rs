@@ -0,0 +1,183 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value as JsonValue;
+
+use crate::keys::display::{parse_display_string_to_key, to_display_string_with};
+use crate::{KeyCodec, KvError, KvKey, KvResult, KvValue};
+
+/// Render entries as CSV. See [`crate::Kv::dump_csv`] for the format.
+pub(crate) fn write_csv(
+    entries: &[(KvKey, KvValue)],
+    key_codec: &dyn KeyCodec,
+) -> KvResult<String> {
+    let mut columns = BTreeSet::new();
+    for (key, value) in entries {
+        let KvValue::Object(fields) = value else {
+            return Err(KvError::Other(format!(
+                "CSV export only supports object values, but {key:?} holds {value:?}"
+            )));
+        };
+        columns.extend(fields.keys().map(String::as_str));
+    }
+
+    let mut out = String::new();
+    let header = std::iter::once("key").chain(columns.iter().copied());
+    write_record(&mut out, header);
+
+    for (key, value) in entries {
+        let KvValue::Object(fields) = value else {
+            unreachable!("non-object values are rejected above");
+        };
+        let display = to_display_string_with(key, key_codec)?;
+        let cells: Vec<String> = columns
+            .iter()
+            .map(|column| fields.get(*column).map(encode_cell).unwrap_or_default())
+            .collect();
+        write_record(
+            &mut out,
+            std::iter::once(display.as_str()).chain(cells.iter().map(String::as_str)),
+        );
+    }
+    Ok(out)
+}
+
+/// Parse CSV written by [`write_csv`] back into entries.
+pub(crate) fn read_csv(input: &str) -> KvResult<Vec<(KvKey, KvValue)>> {
+    let mut records = parse_records(input)?.into_iter();
+    let Some(header) = records.next() else {
+        return Ok(Vec::new());
+    };
+    if header.first().map(String::as_str) != Some("key") {
+        return Err(KvError::Other(
+            "CSV header must start with a `key` column".to_string(),
+        ));
+    }
+
+    let mut entries = Vec::new();
+    for (line, record) in records.enumerate() {
+        if record.len() != header.len() {
+            return Err(KvError::Other(format!(
+                "CSV row {} has {} cells, expected {}",
+                line + 1,
+                record.len(),
+                header.len()
+            )));
+        }
+        let mut cells = record.into_iter();
+        let display = cells.next().unwrap_or_default();
+        let key = parse_display_string_to_key(&display).ok_or(KvError::KeyDecodeError(format!(
+            "Could not decode CSV key {display} to KvKey."
+        )))?;
+        let fields: BTreeMap<String, KvValue> = header[1..]
+            .iter()
+            .zip(cells)
+            .filter(|(_, cell)| !cell.is_empty())
+            .map(|(column, cell)| (column.clone(), decode_cell(&cell)))
+            .collect();
+        entries.push((key, KvValue::Object(fields)));
+    }
+    Ok(entries)
+}
+
+/// Strings are written as-is unless they would read back as something else (empty, or
+/// valid JSON); everything else is JSON-encoded.
+fn encode_cell(value: &KvValue) -> String {
+    if let KvValue::String(s) = value
+        && !s.is_empty()
+        && serde_json::from_str::<JsonValue>(s).is_err()
+    {
+        return s.clone();
+    }
+    JsonValue::from(value).to_string()
+}
+
+fn decode_cell(cell: &str) -> KvValue {
+    match serde_json::from_str::<JsonValue>(cell) {
+        Ok(json) => KvValue::from(&json),
+        Err(_) => KvValue::String(cell.to_string()),
+    }
+}
+
+fn write_record<'a>(out: &mut String, cells: impl Iterator<Item = &'a str>) {
+    for (i, cell) in cells.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        if cell.contains([',', '"', '\n', '\r']) {
+            out.push('"');
+            out.push_str(&cell.replace('"', "\"\""));
+            out.push('"');
+        } else {
+            out.push_str(cell);
+        }
+    }
+    out.push('\n');
+}
+
+/// Split RFC 4180 style CSV into records. Quoted cells may contain commas, doubled quotes,
+/// and line breaks.
+fn parse_records(input: &str) -> KvResult<Vec<Vec<String>>> {
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut cell = String::new();
+    let mut in_quotes = false;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    cell.push('"');
+                    chars.next();
+                }
+                '"' => in_quotes = false,
+                _ => cell.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if cell.is_empty() => in_quotes = true,
+            ',' => record.push(std::mem::take(&mut cell)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                record.push(std::mem::take(&mut cell));
+                records.push(std::mem::take(&mut record));
+            }
+            _ => cell.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(KvError::Other(
+            "CSV input ends inside a quoted cell".to_string(),
+        ));
+    }
+    if !cell.is_empty() || !record.is_empty() {
+        record.push(cell);
+        records.push(record);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_quoted_cells() {
+        let input = "a,\"b,c\",\"say \"\"hi\"\"\"\r\n\"multi\nline\",,x";
+        let records = parse_records(input).unwrap();
+        assert_eq!(
+            records,
+            vec![vec!["a", "b,c", "say \"hi\""], vec!["multi\nline", "", "x"],]
+        );
+    }
+
+    #[test]
+    fn ambiguous_strings_are_json_encoded() {
+        for s in ["", "42", "true", "null", "\"quoted\""] {
+            let value = KvValue::String(s.to_string());
+            assert_eq!(decode_cell(&encode_cell(&value)), value);
+        }
+        assert_eq!(encode_cell(&KvValue::String("plain".into())), "plain");
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::{JsonBinary, KvError, KvKey, KvResult, KvValue};
+
+/// Hex digits per path component, keeping file names well under the usual 255-byte limit.
+const COMPONENT_LEN: usize = 200;
+
+/// Every entry file and directory name starts with this, so nothing else in the dump
+/// directory is ever mistaken for one (and the empty key isn't a bare `.json` dotfile).
+const NAME_PREFIX: &str = "key-";
+
+fn io_error(action: &str, path: &Path, e: std::io::Error) -> KvError {
+    KvError::Other(format!("error {action} {}: {e}", path.display()))
+}
+
+/// Where the value for `key` is stored under `root`: `key-`, its lowercase hex encoding
+/// and `.json`, split into nested `key-` directories if that would make too long a file
+/// name. The empty key is `key-.json`.
+fn entry_path(root: &Path, key: &KvKey) -> PathBuf {
+    let hex = key.to_hex();
+    let mut path = root.to_path_buf();
+    let mut rest = hex.as_str();
+    while rest.len() > COMPONENT_LEN {
+        let (dir, tail) = rest.split_at(COMPONENT_LEN);
+        path.push(format!("{NAME_PREFIX}{dir}"));
+        rest = tail;
+    }
+    path.push(format!("{NAME_PREFIX}{rest}.json"));
+    path
+}
+
+/// The hex part of a name written by [`entry_path`], if `name` is one.
+fn entry_hex(name: &str) -> Option<&str> {
+    let hex = name.strip_prefix(NAME_PREFIX)?;
+    hex.bytes()
+        .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+        .then_some(hex)
+}
+
+/// Collect `(hex, path)` for every entry file under `dir`. Anything not named like one,
+/// such as a `.git` directory, a `README.md` or a `cafe.json`, is ignored.
+fn walk(dir: &Path, hex: &str, out: &mut Vec<(String, PathBuf)>) -> KvResult<()> {
+    let entries = fs::read_dir(dir).map_err(|e| io_error("reading", dir, e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| io_error("reading", dir, e))?;
+        let path = entry.path();
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        let is_dir = entry
+            .file_type()
+            .map_err(|e| io_error("reading", &path, e))?
+            .is_dir();
+        if is_dir {
+            if let Some(part) = entry_hex(&name)
+                && part.len() == COMPONENT_LEN
+            {
+                walk(&path, &format!("{hex}{part}"), out)?;
+            }
+        } else if let Some(part) = name.strip_suffix(".json").and_then(entry_hex)
+            && (hex.len() + part.len()).is_multiple_of(2)
+        {
+            out.push((format!("{hex}{part}"), path));
+        }
+    }
+    Ok(())
+}
+
+/// Write one file per entry under `root`. See [`crate::Kv::dump_dir`] for the layout.
+pub(crate) fn write_dir(
+    root: &Path,
+    entries: &[(KvKey, KvValue)],
+    binary: JsonBinary,
+) -> KvResult<()> {
+    fs::create_dir_all(root).map_err(|e| io_error("creating", root, e))?;
+    let mut stale = Vec::new();
+    walk(root, "", &mut stale)?;
+
+    let mut written = BTreeSet::new();
+    for (key, value) in entries {
+        let path = entry_path(root, key);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| io_error("creating", parent, e))?;
+        }
+        let mut json = serde_json::to_string_pretty(&value.to_json(binary))
+            .map_err(|e| KvError::Other(format!("serde error writing json: {e}")))?;
+        json.push('\n');
+        fs::write(&path, json).map_err(|e| io_error("writing", &path, e))?;
+        written.insert(path);
+    }
+
+    for (_, path) in stale {
+        if written.contains(&path) {
+            continue;
+        }
+        fs::remove_file(&path).map_err(|e| io_error("removing", &path, e))?;
+        // Drop directories emptied along the way; a non-empty one just stays.
+        let mut dir = path.parent();
+        while let Some(d) = dir.filter(|d| *d != root) {
+            if fs::remove_dir(d).is_err() {
+                break;
+            }
+            dir = d.parent();
+        }
+    }
+    Ok(())
+}
+
+/// Read every entry file under `root`, in key order.
+pub(crate) fn read_dir(root: &Path) -> KvResult<Vec<(KvKey, KvValue)>> {
+    let mut files = Vec::new();
+    walk(root, "", &mut files)?;
+    let mut entries = files
+        .into_iter()
+        .map(|(hex, path)| {
+            let key = KvKey::from_hex(&hex)?;
+            let text = fs::read_to_string(&path).map_err(|e| io_error("reading", &path, e))?;
+            let json: serde_json::Value = serde_json::from_str(&text).map_err(|e| {
+                KvError::Other(format!("serde error parsing {}: {e}", path.display()))
+            })?;
+            Ok((key, KvValue::from(&json)))
+        })
+        .collect::<KvResult<Vec<_>>>()?;
+    entries.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(entries)
+}
@@ -0,0 +1,105 @@
+use crate::value_codec::decode_stored;
+use crate::{Kv, KvKey, KvResult, KvValue};
+
+type Modifier<'a> = Box<dyn FnOnce(&mut KvValue) + 'a>;
+
+/// A pending insert-or-modify of a single key, created by [`Kv::entry`].
+///
+/// Nothing touches the backend until [`Entry::or_insert`], [`Entry::or_insert_with`] or
+/// [`Entry::modify`] is called. That call reads the key, applies any [`Entry::and_modify`]
+/// edits or stores the default, and writes the result back, all under a single write lock.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+/// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+/// for _ in 0..3 {
+///     kv.entry(&("hits",))
+///         .and_modify(|v| *v = KvValue::I64(v.as_i64().unwrap_or(0) + 1))
+///         .or_insert(KvValue::I64(1))
+///         .unwrap();
+/// }
+/// assert_eq!(kv.get(&("hits",)).unwrap(), Some(KvValue::I64(3)));
+/// ```
+#[must_use = "an entry does nothing until `or_insert`, `or_insert_with` or `modify` is called"]
+pub struct Entry<'a> {
+    kv: &'a mut Kv,
+    key: KvKey,
+    modify: Option<Modifier<'a>>,
+}
+
+impl<'a> Entry<'a> {
+    pub(crate) fn new(kv: &'a mut Kv, key: KvKey) -> Self {
+        Self {
+            kv,
+            key,
+            modify: None,
+        }
+    }
+
+    /// The encoded key this entry refers to.
+    pub fn key(&self) -> &KvKey {
+        &self.key
+    }
+
+    /// Edit the stored value in place if the key is present. Calls chain, running in order.
+    /// The edits only take effect once the entry is finished with [`Entry::or_insert`],
+    /// [`Entry::or_insert_with`] or [`Entry::modify`].
+    #[must_use = "edits are only applied by `or_insert`, `or_insert_with` or `modify`"]
+    pub fn and_modify(mut self, f: impl FnOnce(&mut KvValue) + 'a) -> Self {
+        self.modify = Some(match self.modify.take() {
+            Some(previous) => Box::new(move |value: &mut KvValue| {
+                previous(value);
+                f(value);
+            }),
+            None => Box::new(f),
+        });
+        self
+    }
+
+    /// Store `default` if the key is absent. Returns the value the key ends up holding.
+    pub fn or_insert(self, default: KvValue) -> KvResult<KvValue> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Store `f()` if the key is absent; `f` only runs in that case. Returns the value the
+    /// key ends up holding.
+    pub fn or_insert_with(self, f: impl FnOnce() -> KvValue) -> KvResult<KvValue> {
+        self.finish(Some(f))
+            .map(|value| value.expect("a default was given"))
+    }
+
+    /// Apply the [`Entry::and_modify`] edits if the key is present, without inserting
+    /// anything otherwise. Returns the value the key ends up holding, if any.
+    pub fn modify(self) -> KvResult<Option<KvValue>> {
+        self.finish(None::<fn() -> KvValue>)
+    }
+
+    fn finish(self, default: Option<impl FnOnce() -> KvValue>) -> KvResult<Option<KvValue>> {
+        let Entry { kv, key, modify } = self;
+        let key = kv.checked_key(&key)?;
+        let value = {
+            let mut backend = kv.backend.write();
+            let current = backend
+                .get(&key)?
+                .map(|b| decode_stored(&*kv.codec, &b))
+                .transpose()?;
+            let value = match (current, modify) {
+                // Present and untouched: nothing to write.
+                (Some(value), None) => return Ok(Some(value)),
+                (Some(mut value), Some(modify)) => {
+                    modify(&mut value);
+                    value
+                }
+                (None, _) => match default {
+                    Some(f) => f(),
+                    None => return Ok(None),
+                },
+            };
+            backend.set(key.clone(), Some(kv.encode_value(&value)?))?;
+            value
+        };
+        kv.notify(&key, true);
+        Ok(Some(value))
+    }
+}
@@ -0,0 +1,757 @@
+use std::borrow::Cow;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::keys::key_segment::KeySegmentTag;
+
+/// A byte encoding for key segments.
+///
+/// Every codec must be order-preserving for the types it supports natively: comparing two
+/// encoded keys bytewise must give the same result as comparing the original segments.
+/// Segment types a codec has no native representation for are mapped onto the core types
+/// by the default methods (chars become one-character strings, `Option` presence becomes a
+/// bool, timestamps become `i64` nanoseconds, IP addresses become fixed-width hex strings,
+/// enum discriminants become `u64`s).
+///
+/// Keys built with different codecs are not comparable, so a store should stick to one.
+/// Use [`crate::IntoKey::to_key_with`] to build keys and [`crate::KvKey::decode_with`] to
+/// read them back.
+pub trait KeyCodec {
+    /// A stable identifier for this encoding, including its version, suitable for
+    /// recording alongside persisted data.
+    fn id(&self) -> &'static str;
+
+    fn encode_u64(&self, n: u64, out: &mut Vec<u8>);
+    fn encode_i64(&self, n: i64, out: &mut Vec<u8>);
+    fn encode_bool(&self, b: bool, out: &mut Vec<u8>);
+    fn encode_str(&self, s: &str, out: &mut Vec<u8>);
+
+    fn encode_char(&self, c: char, out: &mut Vec<u8>) {
+        self.encode_str(c.encode_utf8(&mut [0; 4]), out);
+    }
+
+    /// Encode the header of an `Option` segment; `present` is `true` for `Some`.
+    fn encode_presence(&self, present: bool, out: &mut Vec<u8>) {
+        self.encode_bool(present, out);
+    }
+
+    fn encode_timestamp_nanos(&self, nanos: i64, out: &mut Vec<u8>) {
+        self.encode_i64(nanos, out);
+    }
+
+    /// Encode an IP address so that every IPv4 address sorts before every IPv6 one.
+    ///
+    /// The default writes a string of the family and the address as zero-padded lowercase
+    /// hex (`"4:c0a80001"`, `"6:20010db8…"`), which orders correctly under any codec that
+    /// orders strings bytewise or by length first.
+    fn encode_ip(&self, addr: IpAddr, out: &mut Vec<u8>) {
+        let s = match addr {
+            IpAddr::V4(v4) => format!("4:{:08x}", u32::from(v4)),
+            IpAddr::V6(v6) => format!("6:{:032x}", u128::from(v6)),
+        };
+        self.encode_str(&s, out);
+    }
+
+    /// Encode the discriminant of a [`crate::KeyEnum`].
+    fn encode_enum(&self, discriminant: u8, out: &mut Vec<u8>) {
+        self.encode_u64(discriminant as u64, out);
+    }
+
+    // The decode methods read one segment off the front of `rem` and advance it. They
+    // return `None`, leaving `rem` untouched, if the next segment is missing or of another type.
+
+    fn decode_u64(&self, rem: &mut &[u8]) -> Option<u64>;
+    fn decode_i64(&self, rem: &mut &[u8]) -> Option<i64>;
+    fn decode_bool(&self, rem: &mut &[u8]) -> Option<bool>;
+    fn decode_str<'a>(&self, rem: &mut &'a [u8]) -> Option<Cow<'a, str>>;
+
+    fn decode_char(&self, rem: &mut &[u8]) -> Option<char> {
+        let mut rest = *rem;
+        let s = self.decode_str(&mut rest)?;
+        let mut chars = s.chars();
+        let (Some(c), None) = (chars.next(), chars.next()) else {
+            return None;
+        };
+        *rem = rest;
+        Some(c)
+    }
+
+    fn decode_presence(&self, rem: &mut &[u8]) -> Option<bool> {
+        self.decode_bool(rem)
+    }
+
+    fn decode_timestamp_nanos(&self, rem: &mut &[u8]) -> Option<i64> {
+        self.decode_i64(rem)
+    }
+
+    fn decode_ip(&self, rem: &mut &[u8]) -> Option<IpAddr> {
+        let mut rest = *rem;
+        let s = self.decode_str(&mut rest)?;
+        let (family, hex) = s.split_once(':')?;
+        if !hex.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+            return None;
+        }
+        let addr = match (family, hex.len()) {
+            ("4", 8) => IpAddr::V4(Ipv4Addr::from(u32::from_str_radix(hex, 16).ok()?)),
+            ("6", 32) => IpAddr::V6(Ipv6Addr::from(u128::from_str_radix(hex, 16).ok()?)),
+            _ => return None,
+        };
+        *rem = rest;
+        Some(addr)
+    }
+
+    fn decode_enum(&self, rem: &mut &[u8]) -> Option<u8> {
+        let mut rest = *rem;
+        let discriminant = u8::try_from(self.decode_u64(&mut rest)?).ok()?;
+        *rem = rest;
+        Some(discriminant)
+    }
+
+    /// How many leading bytes of the encoded key `prefix` a key must share to be listed
+    /// under it. Prefix scans use this as their bound, so a codec that orders differently
+    /// decoding segments together (see [`crate::Kv::with_string_collation`]) can shorten
+    /// it to match them all. The default keeps the whole prefix.
+    fn prefix_len(&self, prefix: &[u8]) -> usize {
+        prefix.len()
+    }
+}
+
+/// Segment-walking helpers built on a codec's decoders, for the crate's error messages and
+/// prefix stripping. Kept out of [`KeyCodec`] so they aren't part of what codec authors
+/// implement or callers see.
+pub(crate) trait KeyCodecExt: KeyCodec {
+    /// Name the type of the segment at the front of `rem` (`"u64"`, `"string"`, `"option"`,
+    /// ...), or `None` if nothing decodes there. Used for error messages.
+    fn peek_kind(&self, rem: &[u8]) -> Option<&'static str> {
+        type Probe<'p> = (&'static str, &'p dyn Fn(&mut &[u8]) -> bool);
+        let kinds: [Probe<'_>; 9] = [
+            ("u64", &|r| self.decode_u64(r).is_some()),
+            ("i64", &|r| self.decode_i64(r).is_some()),
+            ("bool", &|r| self.decode_bool(r).is_some()),
+            ("string", &|r| self.decode_str(r).is_some()),
+            ("char", &|r| self.decode_char(r).is_some()),
+            ("timestamp", &|r| self.decode_timestamp_nanos(r).is_some()),
+            ("ip", &|r| self.decode_ip(r).is_some()),
+            ("enum", &|r| self.decode_enum(r).is_some()),
+            ("option", &|r| self.decode_presence(r).is_some()),
+        ];
+        kinds
+            .into_iter()
+            .find(|(_, decodes)| decodes(&mut &*rem))
+            .map(|(kind, _)| kind)
+    }
+
+    /// Skip over one whole segment (an `Option` counts as one, together with its value).
+    fn skip_segment(&self, rem: &mut &[u8]) -> Option<()> {
+        let skipped = self.decode_u64(rem).is_some()
+            || self.decode_i64(rem).is_some()
+            || self.decode_bool(rem).is_some()
+            || self.decode_str(rem).is_some()
+            || self.decode_char(rem).is_some()
+            || self.decode_timestamp_nanos(rem).is_some()
+            || self.decode_ip(rem).is_some()
+            || self.decode_enum(rem).is_some();
+        if !skipped && self.decode_presence(rem)? {
+            self.skip_segment(rem)?;
+        }
+        Some(())
+    }
+
+    /// Re-encode the whole key `rem` segment by segment with `to`, or `None` if some
+    /// segment doesn't decode.
+    fn transcode(&self, mut rem: &[u8], to: &dyn KeyCodec) -> Option<Vec<u8>> {
+        let mut out = Vec::with_capacity(rem.len());
+        while !rem.is_empty() {
+            self.transcode_segment(&mut rem, to, &mut out)?;
+        }
+        Some(out)
+    }
+
+    /// Re-encode one segment (an `Option` together with its value) with `to`.
+    fn transcode_segment(
+        &self,
+        rem: &mut &[u8],
+        to: &dyn KeyCodec,
+        out: &mut Vec<u8>,
+    ) -> Option<()> {
+        if let Some(n) = self.decode_u64(rem) {
+            to.encode_u64(n, out);
+        } else if let Some(n) = self.decode_i64(rem) {
+            to.encode_i64(n, out);
+        } else if let Some(b) = self.decode_bool(rem) {
+            to.encode_bool(b, out);
+        } else if let Some(s) = self.decode_str(rem) {
+            to.encode_str(&s, out);
+        } else if let Some(c) = self.decode_char(rem) {
+            to.encode_char(c, out);
+        } else if let Some(nanos) = self.decode_timestamp_nanos(rem) {
+            to.encode_timestamp_nanos(nanos, out);
+        } else if let Some(addr) = self.decode_ip(rem) {
+            to.encode_ip(addr, out);
+        } else if let Some(d) = self.decode_enum(rem) {
+            to.encode_enum(d, out);
+        } else {
+            let present = self.decode_presence(rem)?;
+            to.encode_presence(present, out);
+            if present {
+                self.transcode_segment(rem, to, out)?;
+            }
+        }
+        Some(())
+    }
+}
+
+impl<C: KeyCodec + ?Sized> KeyCodecExt for C {}
+
+/// The crate's native key encoding, used by [`crate::IntoKey::to_key`].
+///
+/// Each segment is a type tag followed by a fixed-width big-endian payload. Strings are
+/// prefixed with their UTF-8 byte length as a big-endian `u64` (8 bytes). Encoding,
+/// [`crate::KeyDecoder`] and [`crate::display`] all go through this codec, so there is a
+/// single definition of the format.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LegacyCodec;
+
+fn take_fixed<const N: usize>(rem: &mut &[u8], tag: KeySegmentTag) -> Option<[u8; N]> {
+    if rem.len() < 1 + N || rem[0] != tag as u8 {
+        return None;
+    }
+    let bytes = rem[1..1 + N].try_into().ok()?;
+    *rem = &rem[1 + N..];
+    Some(bytes)
+}
+
+impl KeyCodec for LegacyCodec {
+    fn id(&self) -> &'static str {
+        "sskv-legacy/1"
+    }
+
+    fn encode_u64(&self, n: u64, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::U64 as u8);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+
+    fn encode_i64(&self, n: i64, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::I64 as u8);
+        out.extend_from_slice(&n.to_be_bytes());
+    }
+
+    fn encode_bool(&self, b: bool, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::Bool as u8);
+        out.push(b as u8);
+    }
+
+    fn encode_str(&self, s: &str, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::String as u8);
+        out.extend_from_slice(&(s.len() as u64).to_be_bytes());
+        out.extend_from_slice(s.as_bytes());
+    }
+
+    fn encode_char(&self, c: char, out: &mut Vec<u8>) {
+        // UTF-8 is prefix-free and sorts bytewise in code point order, so no length is needed.
+        out.push(KeySegmentTag::Char as u8);
+        out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+    }
+
+    /// Encoded as the option tag and a presence byte (`0` for `None`, `1` for `Some`), so
+    /// `None` sorts before every `Some(_)`.
+    fn encode_presence(&self, present: bool, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::Option as u8);
+        out.push(present as u8);
+    }
+
+    /// The sign bit is flipped so that pre-1970 (negative) timestamps sort before later ones.
+    fn encode_timestamp_nanos(&self, nanos: i64, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::Timestamp as u8);
+        out.extend_from_slice(&((nanos as u64) ^ (1 << 63)).to_be_bytes());
+    }
+
+    /// Encoded as the IP tag, the family (`4` or `6`), and the address octets in network order.
+    fn encode_ip(&self, addr: IpAddr, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::Ip as u8);
+        match addr {
+            IpAddr::V4(v4) => {
+                out.push(4);
+                out.extend_from_slice(&v4.octets());
+            }
+            IpAddr::V6(v6) => {
+                out.push(6);
+                out.extend_from_slice(&v6.octets());
+            }
+        }
+    }
+
+    fn encode_enum(&self, discriminant: u8, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::Enum as u8);
+        out.push(discriminant);
+    }
+
+    fn decode_u64(&self, rem: &mut &[u8]) -> Option<u64> {
+        take_fixed(rem, KeySegmentTag::U64).map(u64::from_be_bytes)
+    }
+
+    fn decode_i64(&self, rem: &mut &[u8]) -> Option<i64> {
+        take_fixed(rem, KeySegmentTag::I64).map(i64::from_be_bytes)
+    }
+
+    fn decode_bool(&self, rem: &mut &[u8]) -> Option<bool> {
+        take_fixed::<1>(rem, KeySegmentTag::Bool).map(|[b]| b != 0)
+    }
+
+    fn decode_str<'a>(&self, rem: &mut &'a [u8]) -> Option<Cow<'a, str>> {
+        if rem.len() < 9 || rem[0] != KeySegmentTag::String as u8 {
+            return None;
+        }
+        // Check the declared length against what is actually left before converting or
+        // adding anything, so a corrupt length can't wrap `usize` on 32-bit targets.
+        let len = u64::from_be_bytes(rem[1..9].try_into().ok()?);
+        let body = &rem[9..];
+        if len > body.len() as u64 {
+            return None;
+        }
+        let (s, rest) = body.split_at(len as usize);
+        let out = str::from_utf8(s).ok()?;
+        *rem = rest;
+        Some(Cow::Borrowed(out))
+    }
+
+    fn decode_char(&self, rem: &mut &[u8]) -> Option<char> {
+        if rem.len() < 2 || rem[0] != KeySegmentTag::Char as u8 {
+            return None;
+        }
+        let (c, len) = decode_char(&rem[1..])?;
+        *rem = &rem[1 + len..];
+        Some(c)
+    }
+
+    fn decode_presence(&self, rem: &mut &[u8]) -> Option<bool> {
+        take_fixed::<1>(rem, KeySegmentTag::Option).map(|[b]| b != 0)
+    }
+
+    fn decode_timestamp_nanos(&self, rem: &mut &[u8]) -> Option<i64> {
+        take_fixed(rem, KeySegmentTag::Timestamp)
+            .map(|b| (u64::from_be_bytes(b) ^ (1 << 63)) as i64)
+    }
+
+    fn decode_ip(&self, rem: &mut &[u8]) -> Option<IpAddr> {
+        let (addr, len) = match rem {
+            [tag, 4, octets @ ..] if *tag == KeySegmentTag::Ip as u8 => {
+                let octets: [u8; 4] = octets.get(..4)?.try_into().ok()?;
+                (IpAddr::from(octets), 4)
+            }
+            [tag, 6, octets @ ..] if *tag == KeySegmentTag::Ip as u8 => {
+                let octets: [u8; 16] = octets.get(..16)?.try_into().ok()?;
+                (IpAddr::from(octets), 16)
+            }
+            _ => return None,
+        };
+        *rem = &rem[2 + len..];
+        Some(addr)
+    }
+
+    fn decode_enum(&self, rem: &mut &[u8]) -> Option<u8> {
+        take_fixed::<1>(rem, KeySegmentTag::Enum).map(|[d]| d)
+    }
+}
+
+/// [`LegacyCodec`] with string segments ordered by a collation function, as set up by
+/// [`crate::Kv::with_string_collation`].
+///
+/// Each string is written as the string tag followed by two zero-terminated strings (see
+/// [`write_terminated`]): the collation key `collate(s)`, which decides the order, then
+/// `s` itself, which is what decodes back. Unlike the length-prefixed legacy strings
+/// these sort by content alone, so `"b"` follows `"ab"`. All other segment types are
+/// encoded exactly as by [`LegacyCodec`].
+pub(crate) struct CollatingCodec<F> {
+    pub(crate) collate: F,
+}
+
+impl<F: Fn(&str) -> String> KeyCodec for CollatingCodec<F> {
+    fn id(&self) -> &'static str {
+        "sskv-collated/1"
+    }
+
+    fn encode_u64(&self, n: u64, out: &mut Vec<u8>) {
+        LegacyCodec.encode_u64(n, out);
+    }
+
+    fn encode_i64(&self, n: i64, out: &mut Vec<u8>) {
+        LegacyCodec.encode_i64(n, out);
+    }
+
+    fn encode_bool(&self, b: bool, out: &mut Vec<u8>) {
+        LegacyCodec.encode_bool(b, out);
+    }
+
+    fn encode_str(&self, s: &str, out: &mut Vec<u8>) {
+        out.push(KeySegmentTag::String as u8);
+        write_terminated(&(self.collate)(s), out);
+        write_terminated(s, out);
+    }
+
+    fn encode_char(&self, c: char, out: &mut Vec<u8>) {
+        LegacyCodec.encode_char(c, out);
+    }
+
+    fn encode_presence(&self, present: bool, out: &mut Vec<u8>) {
+        LegacyCodec.encode_presence(present, out);
+    }
+
+    fn encode_timestamp_nanos(&self, nanos: i64, out: &mut Vec<u8>) {
+        LegacyCodec.encode_timestamp_nanos(nanos, out);
+    }
+
+    fn encode_ip(&self, addr: IpAddr, out: &mut Vec<u8>) {
+        LegacyCodec.encode_ip(addr, out);
+    }
+
+    fn encode_enum(&self, discriminant: u8, out: &mut Vec<u8>) {
+        LegacyCodec.encode_enum(discriminant, out);
+    }
+
+    fn decode_u64(&self, rem: &mut &[u8]) -> Option<u64> {
+        LegacyCodec.decode_u64(rem)
+    }
+
+    fn decode_i64(&self, rem: &mut &[u8]) -> Option<i64> {
+        LegacyCodec.decode_i64(rem)
+    }
+
+    fn decode_bool(&self, rem: &mut &[u8]) -> Option<bool> {
+        LegacyCodec.decode_bool(rem)
+    }
+
+    fn decode_str<'a>(&self, rem: &mut &'a [u8]) -> Option<Cow<'a, str>> {
+        if rem.first() != Some(&(KeySegmentTag::String as u8)) {
+            return None;
+        }
+        let (_, rest) = read_terminated(&rem[1..])?;
+        let (original, rest) = read_terminated(rest)?;
+        *rem = rest;
+        Some(original)
+    }
+
+    fn decode_char(&self, rem: &mut &[u8]) -> Option<char> {
+        LegacyCodec.decode_char(rem)
+    }
+
+    fn decode_presence(&self, rem: &mut &[u8]) -> Option<bool> {
+        LegacyCodec.decode_presence(rem)
+    }
+
+    fn decode_timestamp_nanos(&self, rem: &mut &[u8]) -> Option<i64> {
+        LegacyCodec.decode_timestamp_nanos(rem)
+    }
+
+    fn decode_ip(&self, rem: &mut &[u8]) -> Option<IpAddr> {
+        LegacyCodec.decode_ip(rem)
+    }
+
+    fn decode_enum(&self, rem: &mut &[u8]) -> Option<u8> {
+        LegacyCodec.decode_enum(rem)
+    }
+
+    /// A trailing string segment is cut after its collation key, so the prefix matches
+    /// every string that collates the same.
+    fn prefix_len(&self, prefix: &[u8]) -> usize {
+        let mut rem = prefix;
+        let mut last = None;
+        while !rem.is_empty() {
+            last = Some(prefix.len() - rem.len());
+            if self.skip_segment(&mut rem).is_none() {
+                return prefix.len();
+            }
+        }
+        match last.map(|start| &prefix[start..]) {
+            Some([tag, body @ ..]) if *tag == KeySegmentTag::String as u8 => {
+                read_terminated(body).map_or(prefix.len(), |(_, rest)| prefix.len() - rest.len())
+            }
+            _ => prefix.len(),
+        }
+    }
+}
+
+/// Write `s` with each `0x00` escaped as `0x00 0xFF`, then a `0x00` terminator.
+///
+/// UTF-8 never contains `0xFF`, so the terminator is unambiguous, and the result orders
+/// bytewise exactly like the strings themselves: a string sorts before its extensions.
+fn write_terminated(s: &str, out: &mut Vec<u8>) {
+    for &b in s.as_bytes() {
+        out.push(b);
+        if b == 0 {
+            out.push(0xff);
+        }
+    }
+    out.push(0);
+}
+
+/// Read a string written by [`write_terminated`], returning it and the bytes after it.
+/// The string is borrowed unless it contained escaped `0x00`s.
+fn read_terminated(body: &[u8]) -> Option<(Cow<'_, str>, &[u8])> {
+    let mut i = 0;
+    let mut unescaped: Option<Vec<u8>> = None;
+    loop {
+        match *body.get(i)? {
+            0 if body.get(i + 1) == Some(&0xff) => {
+                unescaped.get_or_insert_with(|| body[..i].to_vec()).push(0);
+                i += 2;
+            }
+            0 => break,
+            b => {
+                if let Some(buf) = &mut unescaped {
+                    buf.push(b);
+                }
+                i += 1;
+            }
+        }
+    }
+    let out = match unescaped {
+        None => Cow::Borrowed(str::from_utf8(&body[..i]).ok()?),
+        Some(buf) => Cow::Owned(String::from_utf8(buf).ok()?),
+    };
+    Some((out, &body[i + 1..]))
+}
+
+/// Decode the UTF-8 character at the start of `bytes`, returning it and its encoded length.
+pub(crate) fn decode_char(bytes: &[u8]) -> Option<(char, usize)> {
+    let len = match bytes.first()? {
+        0x00..=0x7F => 1,
+        0xC0..=0xDF => 2,
+        0xE0..=0xEF => 3,
+        0xF0..=0xF7 => 4,
+        _ => return None,
+    };
+    let s = str::from_utf8(bytes.get(..len)?).ok()?;
+    Some((s.chars().next()?, len))
+}
+
+/// Byte-for-byte compatible with Deno KV's key encoding (the FoundationDB tuple layer
+/// subset used by `denokv`).
+///
+/// | Rust type     | Deno KV key part | Encoding                                            |
+/// |---------------|------------------|-----------------------------------------------------|
+/// | `String`/`&str` | `string`       | `0x02`, UTF-8 with `0x00` escaped as `0x00 0xFF`, `0x00` |
+/// | `u64`/`i64`   | `bigint`         | `0x0c..=0x1c` by sign and byte length, big-endian magnitude (one's complement if negative) |
+/// | `bool`        | `boolean`        | `0x26` (false) / `0x27` (true)                     |
+///
+/// `char`s are encoded as one-character strings, `Option` presence as a boolean,
+/// timestamps as bigint nanoseconds since the epoch, enum discriminants as bigints, and IP
+/// addresses as hex strings (see [`KeyCodec::encode_ip`]), so they read back in Deno as
+/// those types. JavaScript `number` (double) and `Uint8Array` key parts have no Rust counterpart
+/// here yet.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DenoKvCodec;
+
+const DENO_STRING: u8 = 0x02;
+const DENO_INT_ZERO: u8 = 0x14;
+const DENO_FALSE: u8 = 0x26;
+const DENO_TRUE: u8 = 0x27;
+
+impl DenoKvCodec {
+    fn encode_int(negative: bool, magnitude: u64, out: &mut Vec<u8>) {
+        let bytes = magnitude.to_be_bytes();
+        let skip = (magnitude.leading_zeros() / 8) as usize;
+        let len = (8 - skip) as u8;
+        if negative {
+            out.push(DENO_INT_ZERO - len);
+            out.extend(bytes[skip..].iter().map(|b| !b));
+        } else {
+            out.push(DENO_INT_ZERO + len);
+            out.extend_from_slice(&bytes[skip..]);
+        }
+    }
+
+    /// Returns whether the integer is negative, and its magnitude.
+    fn decode_int(rem: &mut &[u8]) -> Option<(bool, u64)> {
+        let code = *rem.first()?;
+        let (negative, len) = match code {
+            0x0c..DENO_INT_ZERO => (true, (DENO_INT_ZERO - code) as usize),
+            DENO_INT_ZERO..=0x1c => (false, (code - DENO_INT_ZERO) as usize),
+            _ => return None,
+        };
+        let payload = rem.get(1..1 + len)?;
+        let mut bytes = [0u8; 8];
+        for (dst, src) in bytes[8 - len..].iter_mut().zip(payload) {
+            *dst = if negative { !src } else { *src };
+        }
+        *rem = &rem[1 + len..];
+        Some((negative, u64::from_be_bytes(bytes)))
+    }
+}
+
+impl KeyCodec for DenoKvCodec {
+    fn id(&self) -> &'static str {
+        "denokv/1"
+    }
+
+    fn encode_u64(&self, n: u64, out: &mut Vec<u8>) {
+        Self::encode_int(false, n, out);
+    }
+
+    fn encode_i64(&self, n: i64, out: &mut Vec<u8>) {
+        Self::encode_int(n < 0, n.unsigned_abs(), out);
+    }
+
+    fn encode_bool(&self, b: bool, out: &mut Vec<u8>) {
+        out.push(if b { DENO_TRUE } else { DENO_FALSE });
+    }
+
+    fn encode_str(&self, s: &str, out: &mut Vec<u8>) {
+        out.push(DENO_STRING);
+        write_terminated(s, out);
+    }
+
+    fn decode_u64(&self, rem: &mut &[u8]) -> Option<u64> {
+        let mut rest = *rem;
+        match Self::decode_int(&mut rest)? {
+            (false, n) => {
+                *rem = rest;
+                Some(n)
+            }
+            (true, _) => None,
+        }
+    }
+
+    fn decode_i64(&self, rem: &mut &[u8]) -> Option<i64> {
+        let mut rest = *rem;
+        let n = match Self::decode_int(&mut rest)? {
+            (false, n) => i64::try_from(n).ok()?,
+            (true, n) if n <= i64::MIN.unsigned_abs() => (n as i64).wrapping_neg(),
+            (true, _) => return None,
+        };
+        *rem = rest;
+        Some(n)
+    }
+
+    fn decode_bool(&self, rem: &mut &[u8]) -> Option<bool> {
+        let b = match *rem.first()? {
+            DENO_FALSE => false,
+            DENO_TRUE => true,
+            _ => return None,
+        };
+        *rem = &rem[1..];
+        Some(b)
+    }
+
+    fn decode_str<'a>(&self, rem: &mut &'a [u8]) -> Option<Cow<'a, str>> {
+        if rem.first() != Some(&DENO_STRING) {
+            return None;
+        }
+        let (out, rest) = read_terminated(&rem[1..])?;
+        *rem = rest;
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{FromKvKey, IntoKey, KeyDecoder};
+
+    fn deno(key: &dyn IntoKey) -> Vec<u8> {
+        key.to_key_with(&DenoKvCodec).0
+    }
+
+    // Expected bytes are what Deno KV produces for the equivalent key, e.g.
+    // `["users", 42n]` for `("users", 42u64)`.
+    #[test]
+    fn deno_golden_fixtures() {
+        assert_eq!(deno(&("a",)), [0x02, 0x61, 0x00]);
+        assert_eq!(deno(&("a\0b",)), [0x02, 0x61, 0x00, 0xff, 0x62, 0x00]);
+        assert_eq!(deno(&("",)), [0x02, 0x00]);
+        assert_eq!(deno(&(0u64,)), [0x14]);
+        assert_eq!(deno(&(1u64,)), [0x15, 0x01]);
+        assert_eq!(deno(&(255u64,)), [0x15, 0xff]);
+        assert_eq!(deno(&(256u64,)), [0x16, 0x01, 0x00]);
+        assert_eq!(
+            deno(&(u64::MAX,)),
+            [0x1c, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(deno(&(-1i64,)), [0x13, 0xfe]);
+        assert_eq!(deno(&(-256i64,)), [0x12, 0xfe, 0xff]);
+        assert_eq!(
+            deno(&(i64::MIN,)),
+            [0x0c, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]
+        );
+        assert_eq!(deno(&(false, true)), [0x26, 0x27]);
+        assert_eq!(
+            deno(&("users", 42u64)),
+            [0x02, b'u', b's', b'e', b'r', b's', 0x00, 0x15, 0x2a]
+        );
+    }
+
+    #[test]
+    fn deno_roundtrip() {
+        let tup = ("a\0b:c".to_string(), -70000i64, 3u64, true, i64::MIN, 'é');
+        let key = tup.to_key_with(&DenoKvCodec);
+        let out: (String, i64, u64, bool, i64, char) = key.decode_with(&DenoKvCodec).unwrap();
+        assert_eq!(out, tup);
+
+        // Borrowed strings only decode when no unescaping was needed.
+        let plain = ("plain",).to_key_with(&DenoKvCodec);
+        let mut decoder = KeyDecoder::with_codec(&plain.0, &DenoKvCodec);
+        assert_eq!(<&str>::from_kv_key(&mut decoder), Some("plain"));
+        let escaped = ("nul\0",).to_key_with(&DenoKvCodec);
+        let mut decoder = KeyDecoder::with_codec(&escaped.0, &DenoKvCodec);
+        assert_eq!(<&str>::from_kv_key(&mut decoder), None);
+
+        assert!(
+            (-1i64,)
+                .to_key_with(&DenoKvCodec)
+                .decode_with::<(u64,)>(&DenoKvCodec)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn deno_ints_are_order_preserving() {
+        let mut ints = vec![
+            0i64,
+            -1,
+            1,
+            -256,
+            255,
+            256,
+            i64::MIN,
+            i64::MAX,
+            -70000,
+            70000,
+        ];
+        let mut keys: Vec<_> = ints
+            .iter()
+            .map(|n| (*n,).to_key_with(&DenoKvCodec))
+            .collect();
+        ints.sort();
+        keys.sort();
+        let decoded: Vec<i64> = keys
+            .iter()
+            .map(|k| k.decode_with::<(i64,)>(&DenoKvCodec).unwrap().0)
+            .collect();
+        assert_eq!(decoded, ints);
+    }
+
+    #[test]
+    fn collated_strings_order_by_content_and_roundtrip() {
+        let codec = CollatingCodec {
+            collate: |s: &str| s.to_lowercase(),
+        };
+        let mut strings = vec!["b", "AB", "a\0b", "a", "", "ab", "a\0", "Ab", "b\0"];
+        let mut keys: Vec<_> = strings
+            .iter()
+            .map(|s| (*s, 1u64).to_key_with(&codec))
+            .collect();
+        keys.sort();
+        let decoded: Vec<String> = keys
+            .iter()
+            .map(|k| k.decode_with::<(String, u64)>(&codec).unwrap().0)
+            .collect();
+        strings.sort_by_key(|s| (s.to_lowercase(), s.to_string()));
+        assert_eq!(decoded, strings);
+
+        // A trailing string narrows a prefix only as far as its collation key.
+        let prefix = ("ab",).to_key_with(&codec);
+        let bound = &prefix.0[..codec.prefix_len(&prefix.0)];
+        assert_eq!(keys.iter().filter(|k| k.0.starts_with(bound)).count(), 3);
+        let prefix = ("ab", 1u64).to_key_with(&codec);
+        assert_eq!(codec.prefix_len(&prefix.0), prefix.0.len());
+    }
+}
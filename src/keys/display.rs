@@ -1,118 +1,269 @@
-use super::{KvKey, key_segment::KeySegmentTag};
+//! Human-readable key strings, as used by the JSON, NDJSON and CSV dumps.
+//!
+//! Segments are joined with `:` and rendered as follows, so every key survives a round trip
+//! through [`to_display_string`] and [`parse_display_string_to_key`]:
+//!
+//! | Segment       | Display                                          |
+//! |---------------|--------------------------------------------------|
+//! | `String`      | `"text"`, with `"` and `\` escaped as `\"` and `\\` |
+//! | `char`        | `'c'`, with `:`, `"` and `\` backslash-escaped     |
+//! | `u64`         | `7u` (the `u` suffix is always present)          |
+//! | `i64`         | `7i` / `-7i` (the `i` suffix is always present)  |
+//! | `bool`        | `true` / `false`                                 |
+//! | `Option`      | `none` / `some(<segment>)`                       |
+//! | timestamp     | `<nanoseconds since the epoch>t`                 |
+//! | IP address    | `ip"192.0.2.1"` / `ip"2001:db8::1"`              |
+//! | enum          | `<discriminant>e`                                |
+//!
+//! A number whose suffix doesn't fit it, such as `-1u` or `18446744073709551616u`, is
+//! rejected rather than read as a string. The exception is `e`: `-5e` or `300e` can't be a
+//! discriminant, so they're read as the older unquoted strings described below.
+//!
+//! Older dumps wrote strings without quotes (escaping `:` as `\:`); such segments are still
+//! read as strings when they don't match any of the forms above.
+
+use super::KvKey;
+use super::codec::{KeyCodec, KeyCodecExt, LegacyCodec, decode_char};
+use super::key_segment::KeySegmentTag;
+use crate::{KvError, KvResult};
 use std::str::FromStr;
 
+/// Like [`to_display_string`], for a key encoded with `codec`. Display strings always
+/// describe the [`LegacyCodec`] form, so other encodings are converted to it first.
+pub(crate) fn to_display_string_with(key: &KvKey, codec: &dyn KeyCodec) -> KvResult<String> {
+    let display = if codec.id() == LegacyCodec.id() {
+        to_display_string(&key.0)
+    } else {
+        codec
+            .transcode(&key.0, &LegacyCodec)
+            .and_then(|legacy| to_display_string(&legacy))
+    };
+    display.ok_or_else(|| {
+        KvError::KeyDecodeError(format!(
+            "Key {key:?} doesn't decode with key codec {}, so it has no display string",
+            codec.id()
+        ))
+    })
+}
+
+/// Like [`parse_display_string_to_key`], encoding the key with `codec`.
+pub(crate) fn parse_display_string_with(display: &str, codec: &dyn KeyCodec) -> Option<KvKey> {
+    let key = parse_display_string_to_key(display)?;
+    if codec.id() == LegacyCodec.id() {
+        return Some(key);
+    }
+    LegacyCodec.transcode(&key.0, codec).map(KvKey)
+}
+
 pub fn to_display_string(mut rem: &[u8]) -> Option<String> {
     let mut parts = Vec::new();
     while !rem.is_empty() {
-        if rem[0] == KeySegmentTag::String as u8 {
-            if rem.len() < 9 {
-                return None;
-            }
-            let len = usize::from_be_bytes(rem[1..9].try_into().ok()?);
-            if rem.len() < 9 + len {
-                return None;
-            }
-            let s = std::str::from_utf8(&rem[9..9 + len]).ok()?;
-            // Escape colons not already escaped
-            let mut escaped = String::with_capacity(s.len());
-            let mut chars = s.chars().peekable();
-            while let Some(c) = chars.next() {
-                if c == '\\' {
-                    if chars.peek() == Some(&':') {
-                        // keep the backslash to allow for \: (escaped colon)
-                        escaped.push('\\');
-                        // next iteration will handle the colon
-                    }
-                    // else, ignore the backslash per your instructions
-                } else if c == ':' {
-                    escaped.push_str("\\:");
-                } else {
-                    escaped.push(c);
-                }
-            }
-            parts.push(escaped);
-            rem = &rem[9 + len..];
-        } else if rem[0] == KeySegmentTag::Bool as u8 {
-            if rem.len() < 2 {
-                return None;
-            }
-            let b = rem[1] != 0;
-            parts.push(b.to_string());
-            rem = &rem[2..];
-        } else if rem[0] == KeySegmentTag::I64 as u8 {
-            if rem.len() < 9 {
-                return None;
-            }
-            let bytes: [u8; 8] = rem[1..9].try_into().ok()?;
-            let n = i64::from_be_bytes(bytes);
-            parts.push(format!("{n}i"));
-            rem = &rem[9..];
-        } else if rem[0] == KeySegmentTag::U64 as u8 {
-            if rem.len() < 9 {
-                return None;
+        let (part, rest) = display_segment(rem)?;
+        parts.push(part);
+        rem = rest;
+    }
+    Some(parts.join(":"))
+}
+
+/// Render the segment at the start of `rem`, returning it and the remaining bytes.
+fn display_segment(rem: &[u8]) -> Option<(String, &[u8])> {
+    let tag = *rem.first()?;
+    if tag == KeySegmentTag::String as u8 {
+        let mut rest = rem;
+        let s = LegacyCodec.decode_str(&mut rest)?;
+        let mut quoted = String::with_capacity(s.len() + 2);
+        quoted.push('"');
+        for c in s.chars() {
+            if matches!(c, '"' | '\\') {
+                quoted.push('\\');
             }
-            let bytes: [u8; 8] = rem[1..9].try_into().ok()?;
-            let n = u64::from_be_bytes(bytes);
-            parts.push(format!("{n}u"));
-            rem = &rem[9..];
+            quoted.push(c);
+        }
+        quoted.push('"');
+        Some((quoted, rest))
+    } else if tag == KeySegmentTag::Char as u8 {
+        let (c, len) = decode_char(&rem[1..])?;
+        let display = if matches!(c, ':' | '"' | '\\') {
+            format!("'\\{c}'")
         } else {
-            // Unknown tag - bail out
-            return None;
+            format!("'{c}'")
+        };
+        Some((display, &rem[1 + len..]))
+    } else if tag == KeySegmentTag::Option as u8 {
+        match rem.get(1)? {
+            0 => Some(("none".to_string(), &rem[2..])),
+            _ => {
+                let (inner, rest) = display_segment(&rem[2..])?;
+                Some((format!("some({inner})"), rest))
+            }
         }
+    } else if tag == KeySegmentTag::Bool as u8 {
+        let mut rest = rem;
+        let b = LegacyCodec.decode_bool(&mut rest)?;
+        Some((b.to_string(), rest))
+    } else if tag == KeySegmentTag::I64 as u8 {
+        let mut rest = rem;
+        let n = LegacyCodec.decode_i64(&mut rest)?;
+        Some((format!("{n}i"), rest))
+    } else if tag == KeySegmentTag::Timestamp as u8 {
+        let mut rest = rem;
+        let nanos = LegacyCodec.decode_timestamp_nanos(&mut rest)?;
+        Some((format!("{nanos}t"), rest))
+    } else if tag == KeySegmentTag::Ip as u8 {
+        let mut rest = rem;
+        let addr = LegacyCodec.decode_ip(&mut rest)?;
+        Some((format!("ip\"{addr}\""), rest))
+    } else if tag == KeySegmentTag::Enum as u8 {
+        let mut rest = rem;
+        let d = LegacyCodec.decode_enum(&mut rest)?;
+        Some((format!("{d}e"), rest))
+    } else if tag == KeySegmentTag::U64 as u8 {
+        let mut rest = rem;
+        let n = LegacyCodec.decode_u64(&mut rest)?;
+        Some((format!("{n}u"), rest))
+    } else {
+        // Unknown tag - bail out
+        None
     }
-    Some(parts.join(":"))
 }
 
 pub fn parse_display_string_to_key(display: &str) -> Option<KvKey> {
     let mut key = KvKey::new();
-    let mut buf = String::with_capacity(display.len());
-    let mut chars = display.chars().peekable();
+    if display.is_empty() {
+        return Some(key);
+    }
+    for part in split_segments(display)? {
+        push_display_segment(&mut key, part)?;
+    }
+    Some(key)
+}
 
+/// Split a display string on the colons between segments.
+///
+/// Colons inside a quoted string, or escaped with a backslash, don't count. Returns `None` if
+/// a quoted string is never closed.
+fn split_segments(display: &str) -> Option<Vec<&str>> {
     let mut parts = Vec::new();
+    let mut start = 0;
+    let mut in_string = false;
+    let mut chars = display.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Whatever follows a backslash is taken literally.
+            '\\' => {
+                chars.next();
+            }
+            '"' => in_string = !in_string,
+            ':' if !in_string => {
+                parts.push(&display[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if in_string {
+        return None;
+    }
+    parts.push(&display[start..]);
+    Some(parts)
+}
 
+/// Drop the backslash from every `\x` escape.
+fn unescape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
     while let Some(c) = chars.next() {
         if c == '\\' {
-            if let Some(':') = chars.peek() {
-                buf.push(':');
-                chars.next(); // consume the colon
+            if let Some(escaped) = chars.next() {
+                out.push(escaped);
             }
-        } else if c == ':' {
-            parts.push(std::mem::take(&mut buf));
         } else {
-            buf.push(c);
+            out.push(c);
         }
     }
-    parts.push(buf);
+    out
+}
 
-    for part in parts {
-        // Try bool
-        if part == "true" {
-            key.push(&true);
-            continue;
-        }
-        if part == "false" {
-            key.push(&false);
-            continue;
+/// Split `-?[0-9]+[uite]` into its number and suffix.
+fn split_number_suffix(part: &str) -> Option<(&str, char)> {
+    let suffix = part
+        .chars()
+        .last()
+        .filter(|c| matches!(c, 'u' | 'i' | 't' | 'e'))?;
+    let digits = &part[..part.len() - 1];
+    let unsigned = digits.strip_prefix('-').unwrap_or(digits);
+    (!unsigned.is_empty() && unsigned.bytes().all(|b| b.is_ascii_digit()))
+        .then_some((digits, suffix))
+}
+
+/// Parse a whole `"..."` segment, failing if anything follows the closing quote.
+fn parse_quoted(part: &str) -> Option<String> {
+    let mut out = String::with_capacity(part.len());
+    let mut chars = part.strip_prefix('"')?.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.push(chars.next()?),
+            '"' => return chars.next().is_none().then_some(out),
+            _ => out.push(c),
         }
-        // i64: digits (possibly negative) + 'i'
-        if part.ends_with('i') && part.len() > 1 {
-            let digits = &part[..part.len() - 1];
-            if let Ok(num) = i64::from_str(digits) {
-                key.push(&num);
-                continue;
-            }
+    }
+    None
+}
+
+/// Parse a single display segment and append it to `key`.
+fn push_display_segment(key: &mut KvKey, part: &str) -> Option<()> {
+    // String: double-quoted, with `"` and `\` backslash-escaped
+    if part.starts_with('"') {
+        key.push(&parse_quoted(part)?);
+        return Some(());
+    }
+    // Option: none, or some(<segment>)
+    if part == "none" {
+        LegacyCodec.encode_presence(false, &mut key.0);
+        return Some(());
+    }
+    if let Some(inner) = part.strip_prefix("some(").and_then(|p| p.strip_suffix(')')) {
+        LegacyCodec.encode_presence(true, &mut key.0);
+        return push_display_segment(key, inner);
+    }
+    // IP address: quoted like a string, so an IPv6 address's colons don't split it
+    if let Some(addr) = part.strip_prefix("ip\"").and_then(|p| p.strip_suffix('"')) {
+        LegacyCodec.encode_ip(std::net::IpAddr::from_str(addr).ok()?, &mut key.0);
+        return Some(());
+    }
+    // Try bool
+    if part == "true" {
+        key.push(&true);
+        return Some(());
+    }
+    if part == "false" {
+        key.push(&false);
+        return Some(());
+    }
+    // char: a single (possibly escaped) character in single quotes
+    if let Some(inner) = part.strip_prefix('\'').and_then(|p| p.strip_suffix('\'')) {
+        let inner = unescape(inner);
+        let mut inner = inner.chars();
+        if let (Some(c), None) = (inner.next(), inner.next()) {
+            key.push(&c);
+            return Some(());
         }
-        // u64: digits + 'u'
-        if part.ends_with('u') && part.len() > 1 {
-            let digits = &part[..part.len() - 1];
-            if let Ok(num) = u64::from_str(digits) {
-                key.push(&num);
-                continue;
-            }
+    }
+    // Integers, timestamps and enums: a decimal number followed by a type suffix. The enum
+    // suffix is newer than unquoted strings, so `e` only counts on a discriminant that fits
+    // a `u8`; anything else (`-5e`, `300e`) is still read as a string.
+    if let Some((digits, suffix)) = split_number_suffix(part)
+        && (suffix != 'e' || u8::from_str(digits).is_ok())
+    {
+        match suffix {
+            'u' => key.push(&u64::from_str(digits).ok()?),
+            'i' => key.push(&i64::from_str(digits).ok()?),
+            'e' => LegacyCodec.encode_enum(u8::from_str(digits).ok()?, &mut key.0),
+            _ => LegacyCodec.encode_timestamp_nanos(i64::from_str(digits).ok()?, &mut key.0),
         }
-        // Otherwise treat as string
-        key.push(&part);
+        return Some(());
     }
-
-    Some(key)
+    // Older dumps wrote strings unquoted, with colons escaped as `\:`.
+    key.push(&unescape(part));
+    Some(())
 }
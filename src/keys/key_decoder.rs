@@ -1,91 +1,306 @@
-use crate::keys::key_segment::KeySegmentTag;
-use crate::{KvError, KvKey};
+use std::borrow::Cow;
 
+use crate::keys::codec::{KeyCodec, KeyCodecExt, LegacyCodec};
+use crate::{KvError, KvKey, KvResult};
+
+/// Reads typed segments off the front of an encoded key, one at a time.
+///
+/// Each `next_*` method returns `None` (without consuming anything) if the next segment is
+/// missing or of a different type.
+#[derive(Clone, Copy)]
 pub struct KeyDecoder<'a> {
     rem: &'a [u8],
+    codec: &'a dyn KeyCodec,
 }
 
 impl<'a> KeyDecoder<'a> {
+    /// Decode a key built with the default [`LegacyCodec`].
     pub fn new(rem: &'a [u8]) -> Self {
-        Self { rem }
+        Self::with_codec(rem, &LegacyCodec)
     }
 
-    pub fn next_str(&mut self) -> Option<&'a str> {
-        if self.rem.len() < 9 || self.rem[0] != KeySegmentTag::String as u8 {
-            return None;
-        }
+    /// Decode a key built with `codec`.
+    pub fn with_codec(rem: &'a [u8], codec: &'a dyn KeyCodec) -> Self {
+        Self { rem, codec }
+    }
 
-        let len = usize::from_be_bytes(self.rem[1..9].try_into().ok()?);
-        if self.rem.len() < 9 + len {
-            return None;
-        }
+    /// Returns `true` once every segment has been consumed.
+    pub fn is_empty(&self) -> bool {
+        self.rem.is_empty()
+    }
+
+    /// Strings are borrowed from the key unless the codec had to unescape them.
+    pub fn next_str(&mut self) -> Option<Cow<'a, str>> {
+        self.codec.decode_str(&mut self.rem)
+    }
 
-        let out = str::from_utf8(&self.rem[9..len + 9]).ok()?;
-        self.rem = &self.rem[9 + len..];
-        Some(out)
+    pub fn next_char(&mut self) -> Option<char> {
+        self.codec.decode_char(&mut self.rem)
+    }
+
+    /// Reads the header of an optional segment, returning whether a value follows it.
+    pub fn next_presence(&mut self) -> Option<bool> {
+        self.codec.decode_presence(&mut self.rem)
     }
 
     pub fn next_bool(&mut self) -> Option<bool> {
-        if self.rem.len() < 2 || self.rem[0] != KeySegmentTag::Bool as u8 {
-            return None;
-        }
-        let byte = self.rem[1];
-        self.rem = &self.rem[2..];
-        Some(byte != 0)
+        self.codec.decode_bool(&mut self.rem)
     }
 
     pub fn next_i64(&mut self) -> Option<i64> {
-        if self.rem.len() < 9 || self.rem[0] != KeySegmentTag::I64 as u8 {
-            return None;
-        }
-        let bytes: [u8; 8] = self.rem[1..9].try_into().ok()?;
-        let int = i64::from_be_bytes(bytes);
-        self.rem = &self.rem[9..];
-        Some(int)
+        self.codec.decode_i64(&mut self.rem)
+    }
+
+    /// Reads a timestamp segment as nanoseconds since the Unix epoch.
+    pub fn next_timestamp_nanos(&mut self) -> Option<i64> {
+        self.codec.decode_timestamp_nanos(&mut self.rem)
     }
 
     pub fn next_u64(&mut self) -> Option<u64> {
-        if self.rem.len() < 9 || self.rem[0] != KeySegmentTag::U64 as u8 {
-            return None;
+        self.codec.decode_u64(&mut self.rem)
+    }
+
+    pub fn next_ip(&mut self) -> Option<std::net::IpAddr> {
+        self.codec.decode_ip(&mut self.rem)
+    }
+
+    /// Reads the discriminant of a [`crate::KeyEnum`] segment.
+    pub fn next_enum(&mut self) -> Option<u8> {
+        self.codec.decode_enum(&mut self.rem)
+    }
+
+    /// The number of segments left to decode, or `None` if they don't parse.
+    pub(crate) fn remaining_segments(&self) -> Option<usize> {
+        let mut rem = self.rem;
+        let mut count = 0;
+        while !rem.is_empty() {
+            self.codec.skip_segment(&mut rem)?;
+            count += 1;
+        }
+        Some(count)
+    }
+
+    /// Explain why decoding a `T` as segment `index` of a `expected`-segment key failed.
+    pub(crate) fn mismatch<T: FromKvKey<'a>>(&self, index: usize, expected: usize) -> KvError {
+        if self.rem.is_empty() {
+            return KvError::WrongSegmentCount {
+                expected,
+                found: index,
+            };
+        }
+        match self.codec.peek_kind(self.rem) {
+            Some(got) => KvError::WrongSegmentType {
+                index,
+                expected: T::kind(),
+                got,
+            },
+            None => KvError::KeyDecodeError(format!(
+                "Segment {index} is corrupt: expected {}, found tag byte {:#04x}",
+                T::kind(),
+                self.rem[0]
+            )),
         }
-        let bytes: [u8; 8] = self.rem[1..9].try_into().ok()?;
-        let num = u64::from_be_bytes(bytes);
-        self.rem = &self.rem[9..];
-        Some(num)
     }
 }
 
+/// Types that can be decoded from the segments of a [`KvKey`].
+///
+/// Implemented for the same types as [`crate::IntoKey`], and tuples and arrays thereof.
 pub trait FromKvKey<'a>: Sized {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self>;
+
+    /// A short name for this type in decode errors, e.g. `"u64"` or `"string"`.
+    fn kind() -> &'static str {
+        std::any::type_name::<Self>()
+    }
+
+    /// How many key segments this type spans; more than one for tuples and arrays.
+    fn segments() -> usize {
+        1
+    }
+
+    /// Like [`Self::from_kv_key`], but explains a failure: `index` is the position of this
+    /// type's first segment in a key meant to have `expected` segments. Tuples and arrays
+    /// override this to point at the element that didn't fit.
+    fn decode_at(decoder: &mut KeyDecoder<'a>, index: usize, expected: usize) -> KvResult<Self> {
+        Self::from_kv_key(decoder).ok_or_else(|| decoder.mismatch::<Self>(index, expected))
+    }
+}
+
+/// Decode all of `key` as a `T` with `codec`, failing with a structured error if a segment
+/// has the wrong type or the key has too few or too many segments.
+pub(crate) fn decode_key<'a, T: FromKvKey<'a>>(
+    key: &'a KvKey,
+    codec: &'a dyn KeyCodec,
+) -> KvResult<T> {
+    let expected = T::segments();
+    let mut decoder = KeyDecoder::with_codec(&key.0, codec);
+    let out = T::decode_at(&mut decoder, 0, expected)?;
+    if !decoder.is_empty() {
+        return Err(match decoder.remaining_segments() {
+            Some(extra) => KvError::WrongSegmentCount {
+                expected,
+                found: expected + extra,
+            },
+            None => KvError::KeyDecodeError(format!(
+                "Key {key:?} has trailing bytes that are not a segment"
+            )),
+        });
+    }
+    Ok(out)
 }
 
 impl<'a> FromKvKey<'a> for i64 {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
         decoder.next_i64()
     }
+
+    fn kind() -> &'static str {
+        "i64"
+    }
 }
 
 impl<'a> FromKvKey<'a> for u64 {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
         decoder.next_u64()
     }
+
+    fn kind() -> &'static str {
+        "u64"
+    }
 }
 
 impl<'a> FromKvKey<'a> for bool {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
         decoder.next_bool()
     }
+
+    fn kind() -> &'static str {
+        "bool"
+    }
+}
+
+impl<'a> FromKvKey<'a> for char {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        decoder.next_char()
+    }
+
+    fn kind() -> &'static str {
+        "char"
+    }
+}
+
+impl<'a, T: FromKvKey<'a>> FromKvKey<'a> for Option<T> {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        if decoder.next_presence()? {
+            T::from_kv_key(decoder).map(Some)
+        } else {
+            Some(None)
+        }
+    }
+
+    fn kind() -> &'static str {
+        "option"
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl<'a> FromKvKey<'a> for chrono::DateTime<chrono::Utc> {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        decoder
+            .next_timestamp_nanos()
+            .map(chrono::DateTime::from_timestamp_nanos)
+    }
+
+    fn kind() -> &'static str {
+        "timestamp"
+    }
+}
+
+#[cfg(feature = "net")]
+impl<'a> FromKvKey<'a> for std::net::IpAddr {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        decoder.next_ip()
+    }
+
+    fn kind() -> &'static str {
+        "ip"
+    }
+}
+
+/// Fails (without consuming anything) for IPv6 segments.
+#[cfg(feature = "net")]
+impl<'a> FromKvKey<'a> for std::net::Ipv4Addr {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        let mut peek = *decoder;
+        match peek.next_ip()? {
+            std::net::IpAddr::V4(v4) => {
+                *decoder = peek;
+                Some(v4)
+            }
+            std::net::IpAddr::V6(_) => None,
+        }
+    }
+
+    fn kind() -> &'static str {
+        "ipv4"
+    }
+}
+
+/// Fails (without consuming anything) for IPv4 segments.
+#[cfg(feature = "net")]
+impl<'a> FromKvKey<'a> for std::net::Ipv6Addr {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        let mut peek = *decoder;
+        match peek.next_ip()? {
+            std::net::IpAddr::V6(v6) => {
+                *decoder = peek;
+                Some(v6)
+            }
+            std::net::IpAddr::V4(_) => None,
+        }
+    }
+
+    fn kind() -> &'static str {
+        "ipv6"
+    }
 }
 
+/// Fails (without consuming anything) for discriminants `T` has no variant for.
+impl<'a, T: crate::KeyEnum> FromKvKey<'a> for T {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        let mut peek = *decoder;
+        let variant = T::from_discriminant(peek.next_enum()?)?;
+        *decoder = peek;
+        Some(variant)
+    }
+
+    fn kind() -> &'static str {
+        "enum"
+    }
+}
+
+/// Fails for strings that had to be unescaped by the codec; decode to `String` instead.
 impl<'a> FromKvKey<'a> for &'a str {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
-        decoder.next_str()
+        match decoder.next_str()? {
+            Cow::Borrowed(s) => Some(s),
+            Cow::Owned(_) => None,
+        }
+    }
+
+    fn kind() -> &'static str {
+        "string"
     }
 }
 
 impl<'a> FromKvKey<'a> for String {
     fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
-        decoder.next_str().map(String::from)
+        decoder.next_str().map(Cow::into_owned)
+    }
+
+    fn kind() -> &'static str {
+        "string"
     }
 }
 
@@ -102,6 +317,24 @@ macro_rules! impl_key_decode_for_tuple {
                     )+
                 ))
             }
+
+            fn segments() -> usize {
+                0 $(+ <$name as FromKvKey>::segments())+
+            }
+
+            #[allow(unused_assignments)]
+            fn decode_at(
+                decoder: &mut KeyDecoder<'a>,
+                mut index: usize,
+                expected: usize,
+            ) -> KvResult<Self> {
+                $(
+                    #[allow(non_snake_case)]
+                    let $name = <$name as FromKvKey>::decode_at(decoder, index, expected)?;
+                    index += <$name as FromKvKey>::segments();
+                )+
+                Ok(($($name,)+))
+            }
         }
     };
 }
@@ -115,21 +348,43 @@ impl_key_decode_for_tuple!(A, B, C, D, E, F);
 impl_key_decode_for_tuple!(A, B, C, D, E, F, G);
 impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H);
 impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_key_decode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
+
+/// `N` segments of the same type, e.g. the parts of a path.
+impl<'a, T: FromKvKey<'a>, const N: usize> FromKvKey<'a> for [T; N] {
+    fn from_kv_key(decoder: &mut KeyDecoder<'a>) -> Option<Self> {
+        let items = (0..N)
+            .map(|_| T::from_kv_key(decoder))
+            .collect::<Option<Vec<T>>>()?;
+        items.try_into().ok()
+    }
+
+    fn segments() -> usize {
+        N * T::segments()
+    }
+
+    fn decode_at(decoder: &mut KeyDecoder<'a>, index: usize, expected: usize) -> KvResult<Self> {
+        let items = (0..N)
+            .map(|i| T::decode_at(decoder, index + i * T::segments(), expected))
+            .collect::<KvResult<Vec<T>>>()?;
+        Ok(items
+            .try_into()
+            .unwrap_or_else(|_| unreachable!("collected exactly N items")))
+    }
+}
 
 macro_rules! impl_kv_key_try_from_tuple {
     ($($name:ident),+) => {
         impl<$($name: for<'a> FromKvKey<'a>),+> TryFrom<KvKey> for ($($name,)+) {
             type Error = KvError;
             fn try_from(key: KvKey) -> Result<Self, Self::Error> {
-                let mut decoder = KeyDecoder::new(&key.0);
-                $(
-                    #[allow(non_snake_case)]
-                    let $name = <$name as FromKvKey>::from_kv_key(&mut decoder)
-                        .ok_or_else(|| KvError::KeyDecodeError(
-                            format!("Failed to decode key segment \"{}\"", stringify!($name)))
-                        )?;
-                )+
-                Ok(($($name,)+))
+                decode_key(&key, &LegacyCodec)
             }
         }
     }
@@ -144,3 +399,10 @@ impl_kv_key_try_from_tuple!(A, B, C, D, E, F);
 impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G);
 impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H);
 impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_kv_key_try_from_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
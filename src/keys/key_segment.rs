@@ -1,51 +1,173 @@
+use crate::keys::codec::{KeyCodec, LegacyCodec};
 use crate::keys::{IntoKey, KvKey};
 
+/// The type tags of [`LegacyCodec`] segments: the one list both encoding and decoding use.
 #[repr(u8)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub(crate) enum KeySegmentTag {
     U64 = 0x01,
     I64 = 0x02,
     Bool = 0x03,
     String = 0x04,
+    Char = 0x05,
+    Option = 0x06,
+    Timestamp = 0x07,
+    Ip = 0x08,
+    Enum = 0x09,
+}
+
+#[cfg(test)]
+impl KeySegmentTag {
+    pub(crate) const ALL: [KeySegmentTag; 9] = [
+        KeySegmentTag::U64,
+        KeySegmentTag::I64,
+        KeySegmentTag::Bool,
+        KeySegmentTag::String,
+        KeySegmentTag::Char,
+        KeySegmentTag::Option,
+        KeySegmentTag::Timestamp,
+        KeySegmentTag::Ip,
+        KeySegmentTag::Enum,
+    ];
 }
 
 pub trait KeySegment {
-    fn encode_into(&self, out: &mut Vec<u8>);
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>);
+
+    fn encode_into(&self, out: &mut Vec<u8>) {
+        self.encode_with(&LegacyCodec, out);
+    }
 }
 
 impl KeySegment for u64 {
-    fn encode_into(&self, out: &mut Vec<u8>) {
-        out.push(KeySegmentTag::U64 as u8);
-        out.extend_from_slice(&self.to_be_bytes());
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_u64(*self, out);
     }
 }
 
 impl KeySegment for i64 {
-    fn encode_into(&self, out: &mut Vec<u8>) {
-        out.push(KeySegmentTag::I64 as u8);
-        out.extend_from_slice(&self.to_be_bytes());
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_i64(*self, out);
     }
 }
 
 impl KeySegment for bool {
-    fn encode_into(&self, out: &mut Vec<u8>) {
-        out.push(KeySegmentTag::Bool as u8);
-        out.push(*self as u8);
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_bool(*self, out);
     }
 }
 
 impl KeySegment for String {
-    fn encode_into(&self, out: &mut Vec<u8>) {
-        out.push(KeySegmentTag::String as u8);
-        out.extend_from_slice(&(self.len() as u64).to_be_bytes());
-        out.extend_from_slice(self.as_bytes());
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_str(self, out);
     }
 }
 
 impl KeySegment for &str {
-    fn encode_into(&self, out: &mut Vec<u8>) {
-        out.push(KeySegmentTag::String as u8);
-        out.extend_from_slice(&(self.len() as u64).to_be_bytes());
-        out.extend_from_slice(self.as_bytes());
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_str(self, out);
+    }
+}
+
+impl KeySegment for char {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_char(*self, out);
+    }
+}
+
+/// A presence header followed by the inner segment if present. Codecs encode the header so
+/// that `None` sorts before every `Some(_)`.
+impl<T: KeySegment> KeySegment for Option<T> {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_presence(self.is_some(), out);
+        if let Some(inner) = self {
+            inner.encode_with(codec, out);
+        }
+    }
+}
+
+/// Timestamps are stored with nanosecond precision, which limits them to the range
+/// 1677-09-21T00:12:43.145224192Z to 2262-04-11T23:47:16.854775807Z.
+///
+/// # Panics
+/// Panics when encoding a timestamp outside that range.
+#[cfg(feature = "chrono")]
+impl KeySegment for chrono::DateTime<chrono::Utc> {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        let nanos = self
+            .timestamp_nanos_opt()
+            .expect("timestamp is outside the range representable as i64 nanoseconds");
+        codec.encode_timestamp_nanos(nanos, out);
+    }
+}
+
+/// IPv4 addresses sort before IPv6 ones, and each family in numeric order, so a subnet is a
+/// contiguous range. IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`) count as IPv6.
+#[cfg(feature = "net")]
+impl KeySegment for std::net::IpAddr {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_ip(*self, out);
+    }
+}
+
+/// Encoded exactly like the matching [`std::net::IpAddr`], so the two decode as each other.
+#[cfg(feature = "net")]
+impl KeySegment for std::net::Ipv4Addr {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_ip((*self).into(), out);
+    }
+}
+
+/// Encoded exactly like the matching [`std::net::IpAddr`], so the two decode as each other.
+#[cfg(feature = "net")]
+impl KeySegment for std::net::Ipv6Addr {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_ip((*self).into(), out);
+    }
+}
+
+/// A fieldless enum usable as a key segment, stored as a one-byte discriminant.
+///
+/// Keys sort by discriminant, so with the usual `#[repr(u8)]` and `self as u8` they sort in
+/// declaration order. Implementing this gives the enum [`KeySegment`] and
+/// [`crate::FromKvKey`], so it can appear in tuple keys and be decoded back.
+///
+/// ```rust
+/// use stupid_simple_kv::{IntoKey, KeyEnum, KvKey};
+///
+/// #[repr(u8)]
+/// #[derive(Debug, Clone, Copy, PartialEq)]
+/// enum Status {
+///     Queued,
+///     Running,
+///     Done,
+/// }
+///
+/// impl KeyEnum for Status {
+///     fn to_discriminant(self) -> u8 {
+///         self as u8
+///     }
+///
+///     fn from_discriminant(d: u8) -> Option<Self> {
+///         [Status::Queued, Status::Running, Status::Done].get(d as usize).copied()
+///     }
+/// }
+///
+/// let key = (Status::Running, 7u64).to_key();
+/// let back: (Status, u64) = key.try_into().unwrap();
+/// assert_eq!(back, (Status::Running, 7));
+/// ```
+pub trait KeyEnum: Copy {
+    fn to_discriminant(self) -> u8;
+
+    /// The variant with discriminant `d`, or `None` if there isn't one. Keys holding an
+    /// unknown discriminant then fail to decode instead of producing a wrong variant.
+    fn from_discriminant(d: u8) -> Option<Self>;
+}
+
+impl<T: KeyEnum> KeySegment for T {
+    fn encode_with(&self, codec: &dyn KeyCodec, out: &mut Vec<u8>) {
+        codec.encode_enum(self.to_discriminant(), out);
     }
 }
 
@@ -53,11 +175,15 @@ macro_rules! impl_key_encode_for_tuple {
     ($($name:ident),+) => {
         impl<$($name: KeySegment),+> IntoKey for ($($name,)+) {
             fn to_key(&self) -> KvKey {
+                self.to_key_with(&LegacyCodec)
+            }
+
+            fn to_key_with(&self, codec: &dyn KeyCodec) -> KvKey {
                 #[allow(non_snake_case)]
                 let ($($name,)+) = self;
                 let mut key = KvKey::new();
                 $(
-                    key.push($name);
+                    key.push_with($name, codec);
                 )+
                 key
             }
@@ -74,3 +200,10 @@ impl_key_encode_for_tuple!(A, B, C, D, E, F);
 impl_key_encode_for_tuple!(A, B, C, D, E, F, G);
 impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H);
 impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O);
+impl_key_encode_for_tuple!(A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P);
@@ -1,12 +1,63 @@
 use key_segment::KeySegment;
+mod codec;
 pub mod display;
 mod key_decoder;
 mod key_segment;
 
+pub(crate) use codec::{CollatingCodec, KeyCodecExt};
+pub use codec::{DenoKvCodec, KeyCodec, LegacyCodec};
+use key_decoder::decode_key;
+pub use key_decoder::{FromKvKey, KeyDecoder};
+pub use key_segment::KeyEnum;
+
+use crate::{KvError, KvResult};
+
 /// Key type for stupid-simple-kv. Must be order-preserving (lexicographically).
 #[derive(Clone, PartialEq, PartialOrd, Eq, Ord, Debug)]
 pub struct KvKey(pub(crate) Vec<u8>);
 
+const BASE64URL_ALPHABET: &[u8; 64] =
+    b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+
+/// Encode `bytes` as unpadded URL-safe base64 (RFC 4648 §5).
+pub(crate) fn base64url_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let bits = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |acc, (i, &b)| acc | (b as u32) << (16 - 8 * i));
+        for i in 0..=chunk.len() {
+            let index = (bits >> (18 - 6 * i)) & 0x3f;
+            out.push(BASE64URL_ALPHABET[index as usize] as char);
+        }
+    }
+    out
+}
+
+/// Decode the output of [`base64url_encode`], or `None` if `text` isn't canonical
+/// unpadded base64url.
+pub(crate) fn base64url_decode(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 4 == 1 {
+        return None;
+    }
+    let mut bytes = Vec::with_capacity(text.len() / 4 * 3 + 2);
+    for chunk in text.as_bytes().chunks(4) {
+        let mut bits = 0u32;
+        for (i, &c) in chunk.iter().enumerate() {
+            let value = BASE64URL_ALPHABET.iter().position(|&a| a == c)?;
+            bits |= (value as u32) << (18 - 6 * i);
+        }
+        let decoded = &bits.to_be_bytes()[1..chunk.len()];
+        // Reject non-canonical input whose unused low bits are set.
+        if bits & (0xff_ffff >> (8 * decoded.len())) != 0 {
+            return None;
+        }
+        bytes.extend_from_slice(decoded);
+    }
+    Some(bytes)
+}
+
 impl Default for KvKey {
     fn default() -> Self {
         Self::new()
@@ -18,16 +69,152 @@ impl KvKey {
         Self(Vec::with_capacity(128))
     }
 
+    /// Wrap raw key bytes, such as those previously returned by [`Self::as_bytes`].
+    ///
+    /// The bytes are not validated; they are used as-is.
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        Self(bytes)
+    }
+
+    /// The encoded bytes of this key, e.g. for storing it in an external index.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    /// The encoded bytes of this key as a lowercase hex string.
+    pub fn to_hex(&self) -> String {
+        self.0.iter().map(|b| format!("{b:02x}")).collect()
+    }
+
+    /// Parse a key from a hex string produced by [`Self::to_hex`].
+    pub fn from_hex(hex: &str) -> KvResult<Self> {
+        let invalid = || KvError::KeyDecodeError(format!("Invalid hex key {hex:?}"));
+        if !hex.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| {
+                hex.get(i..i + 2)
+                    .and_then(|pair| u8::from_str_radix(pair, 16).ok())
+                    .ok_or_else(invalid)
+            })
+            .collect::<KvResult<Vec<u8>>>()
+            .map(Self)
+    }
+
+    /// The encoded bytes of this key as unpadded URL-safe base64 (RFC 4648 §5).
+    ///
+    /// Like [`Self::to_hex`] this round-trips exactly, but is a third shorter.
+    pub fn to_base64url(&self) -> String {
+        base64url_encode(&self.0)
+    }
+
+    /// Parse a key from an unpadded base64url string produced by [`Self::to_base64url`].
+    pub fn from_base64url(text: &str) -> KvResult<Self> {
+        base64url_decode(text)
+            .map(Self)
+            .ok_or_else(|| KvError::KeyDecodeError(format!("Invalid base64url key {text:?}")))
+    }
+
     pub fn push(&mut self, part: &dyn KeySegment) {
         part.encode_into(&mut self.0);
     }
 
+    /// Append a segment encoded with `codec`.
+    pub fn push_with(&mut self, part: &dyn KeySegment, codec: &dyn KeyCodec) {
+        part.encode_with(codec, &mut self.0);
+    }
+
+    /// Decode a key built with `codec` (see [`IntoKey::to_key_with`]) into `T`.
+    ///
+    /// # Errors
+    /// Fails if the segments don't match `T`, including when there are segments left over.
+    pub fn decode_with<T: for<'a> FromKvKey<'a>>(&self, codec: &dyn KeyCodec) -> KvResult<T> {
+        decode_key(self, codec)
+    }
+
+    /// Decode the leading segments of this key into `T`, ignoring any that follow.
+    ///
+    /// `TryFrom<KvKey>` rejects keys with segments left over; use this when only a prefix
+    /// of the key is wanted, e.g. the `(table, id)` of a `(table, id, field)` key.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let key = ("users", 7u64, "email").to_key();
+    /// let (table, id): (String, u64) = key.try_into_prefix().unwrap();
+    /// assert_eq!((table.as_str(), id), ("users", 7));
+    /// ```
+    pub fn try_into_prefix<T: for<'a> FromKvKey<'a>>(&self) -> KvResult<T> {
+        let mut decoder = KeyDecoder::new(&self.0);
+        T::from_kv_key(&mut decoder).ok_or_else(|| {
+            KvError::KeyDecodeError(format!(
+                "Key {self:?} does not start with a {}",
+                std::any::type_name::<T>()
+            ))
+        })
+    }
+
+    /// Decode this key into `T`, borrowing string segments from the key instead of copying
+    /// them, so `(u64, &str)` decodes without allocating.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let key = (7u64, "alice").to_key();
+    /// let (id, name): (u64, &str) = key.decode_borrowed().unwrap();
+    /// assert_eq!((id, name), (7, "alice"));
+    /// ```
+    ///
+    /// # Errors
+    /// Fails if the segments don't match `T`, including when there are segments left over.
+    pub fn decode_borrowed<'a, T: FromKvKey<'a>>(&'a self) -> KvResult<T> {
+        decode_key(self, &LegacyCodec)
+    }
+
     pub fn starts_with(&self, key: &KvKey) -> bool {
         self.0.starts_with(&key.0)
     }
 
-    /// Returns the smallest key that is strictly greater than this one.
-    /// Useful for exclusive upper bounds in range queries.
+    /// Whether this key falls in `[start, end)`, with `None` leaving that side unbounded.
+    ///
+    /// These are the same bounds the backends use for range scans, so this can filter keys
+    /// in application code exactly as a scan would, e.g. for a watch stream.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let start = (1u64,).to_key();
+    /// let end = (3u64,).to_key();
+    /// assert!((1u64, "x").to_key().in_range(Some(&start), Some(&end)));
+    /// assert!(!(3u64,).to_key().in_range(Some(&start), Some(&end)));
+    /// ```
+    pub fn in_range(&self, start: Option<&KvKey>, end: Option<&KvKey>) -> bool {
+        start.is_none_or(|start| self >= start) && end.is_none_or(|end| self < end)
+    }
+
+    /// The number of leading encoded bytes this key shares with `other`.
+    ///
+    /// This counts bytes, not segments, so the shared part may end partway through a segment.
+    /// `KvKey::from_bytes(key.as_bytes()[..n].to_vec())` recovers the shared prefix.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let a = ("users", 1u64).to_key();
+    /// let b = ("users", 2u64).to_key();
+    /// assert_eq!(a.common_prefix_len(&b), a.as_bytes().len() - 1);
+    /// ```
+    pub fn common_prefix_len(&self, other: &KvKey) -> usize {
+        self.0
+            .iter()
+            .zip(&other.0)
+            .take_while(|(a, b)| a == b)
+            .count()
+    }
+
+    /// Returns the smallest key that is greater than this one and every key it prefixes,
+    /// i.e. the exclusive upper bound of a prefix scan.
+    ///
+    /// Returns `None` when every byte is `0xFF`. No key outside the prefix sorts after such a
+    /// key, so an unbounded end is then the exact prefix range rather than an approximation.
     pub fn successor(&self) -> Option<KvKey> {
         let mut bytes = self.0.clone();
         for i in (0..bytes.len()).rev() {
@@ -41,52 +228,109 @@ impl KvKey {
         // All bytes were 0xFF, no higher key possible
         None
     }
+
+    /// Append `segment`'s encoded segments to the end of this key.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let mut key = ("users",).to_key();
+    /// key.push_segment(&(42u64,));
+    /// assert_eq!(key, ("users", 42u64).to_key());
+    /// ```
+    pub fn push_segment(&mut self, segment: &dyn IntoKey) {
+        self.0.extend_from_slice(&segment.to_key().0);
+    }
+
+    /// Returns a new key consisting of this key's segments followed by `other`'s.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::IntoKey;
+    /// let parent = ("users", 42u64).to_key();
+    /// assert_eq!(parent.join(&("posts", 7u64)), ("users", 42u64, "posts", 7u64).to_key());
+    /// ```
+    pub fn join(&self, other: &dyn IntoKey) -> KvKey {
+        let mut key = self.clone();
+        key.push_segment(other);
+        key
+    }
+
+    /// Like [`Self::join`], but encodes `other` with `codec`.
+    pub(crate) fn join_with(&self, other: &dyn IntoKey, codec: &dyn KeyCodec) -> KvKey {
+        let mut key = self.clone();
+        key.0.extend_from_slice(&other.to_key_with(codec).0);
+        key
+    }
+
+    /// Returns the very next key after this one, i.e. this key with a `0x00` byte appended.
+    /// Unlike [`Self::successor`], keys that extend this one are *not* skipped.
+    pub(crate) fn immediate_successor(&self) -> KvKey {
+        let mut bytes = self.0.clone();
+        bytes.push(0);
+        KvKey(bytes)
+    }
 }
 
 /// Trait to convert any Rust type or tuple into a key suitable for [`Kv`] operations.
 ///
-/// Implemented for `u64`, `i64`, `bool`, `String`, `&str`, [`KvKey`], and upto 16-tuples thereof.
+/// Implemented for `u64`, `i64`, `bool`, `char`, `String`, `&str`, [`KvKey`], and upto 16-tuples thereof.
 pub trait IntoKey {
     fn to_key(&self) -> KvKey;
-}
 
-impl IntoKey for u64 {
-    fn to_key(&self) -> KvKey {
-        let mut key = KvKey::new();
-        key.push(self);
-        key
+    /// Build the key with a specific [`KeyCodec`] instead of the default [`LegacyCodec`].
+    ///
+    /// The default implementation ignores `codec`, which is right for already-encoded keys
+    /// such as [`KvKey`] but should be overridden by types made of segments.
+    fn to_key_with(&self, codec: &dyn KeyCodec) -> KvKey {
+        let _ = codec;
+        self.to_key()
     }
 }
 
-impl IntoKey for i64 {
-    fn to_key(&self) -> KvKey {
-        let mut key = KvKey::new();
-        key.push(self);
-        key
-    }
+macro_rules! impl_into_key_for_segment {
+    ($($ty:ty),+) => {
+        $(
+            impl IntoKey for $ty {
+                fn to_key(&self) -> KvKey {
+                    self.to_key_with(&LegacyCodec)
+                }
+
+                fn to_key_with(&self, codec: &dyn KeyCodec) -> KvKey {
+                    let mut key = KvKey::new();
+                    key.push_with(self, codec);
+                    key
+                }
+            }
+        )+
+    };
 }
 
-impl IntoKey for String {
+impl_into_key_for_segment!(u64, i64, String, bool, char, &str);
+#[cfg(feature = "chrono")]
+impl_into_key_for_segment!(chrono::DateTime<chrono::Utc>);
+
+/// `N` segments of the same type, one per element.
+impl<T: KeySegment, const N: usize> IntoKey for [T; N] {
     fn to_key(&self) -> KvKey {
-        let mut key = KvKey::new();
-        key.push(self);
-        key
+        self.to_key_with(&LegacyCodec)
     }
-}
 
-impl IntoKey for bool {
-    fn to_key(&self) -> KvKey {
+    fn to_key_with(&self, codec: &dyn KeyCodec) -> KvKey {
         let mut key = KvKey::new();
-        key.push(self);
+        for segment in self {
+            key.push_with(segment, codec);
+        }
         key
     }
 }
 
-impl IntoKey for &str {
+/// Lets APIs taking `impl IntoKey` accept borrowed keys, including `&dyn IntoKey`.
+impl<T: IntoKey + ?Sized> IntoKey for &T {
     fn to_key(&self) -> KvKey {
-        let mut key = KvKey::new();
-        key.push(self);
-        key
+        (**self).to_key()
+    }
+
+    fn to_key_with(&self, codec: &dyn KeyCodec) -> KvKey {
+        (**self).to_key_with(codec)
     }
 }
 
@@ -98,6 +342,8 @@ impl IntoKey for KvKey {
 
 #[cfg(test)]
 mod tests {
+    #[cfg(feature = "chrono")]
+    use crate::KvKey;
     use crate::{KvResult, keys::IntoKey};
 
     // These macros/traits must already be in your crate:
@@ -133,6 +379,56 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn roundtrip_twelve_tuple() -> KvResult<()> {
+        let tup = (
+            1u64,
+            -2i64,
+            "three",
+            true,
+            '5',
+            6u64,
+            Some(7u64),
+            "eight",
+            -9i64,
+            false,
+            'b',
+            12u64,
+        );
+        let key = tup.to_key();
+        type Twelve = (
+            u64,
+            i64,
+            String,
+            bool,
+            char,
+            u64,
+            Option<u64>,
+            String,
+            i64,
+            bool,
+            char,
+            u64,
+        );
+        let out: Twelve = key.try_into()?;
+        assert_eq!(out.2, "three");
+        assert_eq!(out.6, Some(7));
+        assert_eq!(out.11, 12);
+        assert_eq!((out.9, out.10), (false, 'b'));
+        Ok(())
+    }
+
+    #[test]
+    fn roundtrip_arrays() -> KvResult<()> {
+        let key = ["usr", "local", "bin"].to_key();
+        assert_eq!(key, ("usr", "local", "bin").to_key());
+        let path: [String; 3] = key.decode_with(&crate::LegacyCodec)?;
+        assert_eq!(path, ["usr", "local", "bin"]);
+        assert!(key.decode_with::<[String; 2]>(&crate::LegacyCodec).is_err());
+        assert!(key.decode_with::<[String; 4]>(&crate::LegacyCodec).is_err());
+        Ok(())
+    }
+
     #[test]
     fn roundtrip_tuple_tryfrom() -> KvResult<()> {
         let tup = (7u64, "hello world", true);
@@ -162,6 +458,356 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn tuple_decode_checks_arity() -> KvResult<()> {
+        let key = (1u64, "a", true).to_key();
+
+        let exact: (u64, String, bool) = key.clone().try_into()?;
+        assert_eq!(exact, (1, "a".to_string(), true));
+
+        let too_few: KvResult<(u64, String)> = key.clone().try_into();
+        assert!(too_few.is_err());
+        let too_many: KvResult<(u64, String, bool, bool)> = key.clone().try_into();
+        assert!(too_many.is_err());
+
+        let prefix: (u64, String) = key.try_into_prefix()?;
+        assert_eq!(prefix, (1, "a".to_string()));
+        let whole: (u64, String, bool) = key.try_into_prefix()?;
+        assert_eq!(whole, exact);
+        assert!(key.try_into_prefix::<(u64, String, bool, bool)>().is_err());
+        assert!(key.try_into_prefix::<(String,)>().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn decode_errors_are_structured() {
+        use crate::KvError;
+
+        let out: KvResult<(u64, String, bool)> = (55u64, "xyz").to_key().try_into();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentCount {
+                expected: 3,
+                found: 2
+            })
+        ));
+        let out: KvResult<(u64,)> = (55u64, "xyz", Some(1i64)).to_key().try_into();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentCount {
+                expected: 1,
+                found: 3
+            })
+        ));
+
+        let out: KvResult<(u64, i64)> = (55u64, "xyz").to_key().try_into();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentType {
+                index: 1,
+                expected: "i64",
+                got: "string"
+            })
+        ));
+        let out: KvResult<(Option<bool>, char)> = (None::<u64>, true).to_key().try_into();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentType {
+                index: 1,
+                expected: "char",
+                got: "bool"
+            })
+        ));
+    }
+
+    #[test]
+    fn decode_borrowed_str_points_into_the_key() -> KvResult<()> {
+        use crate::KvError;
+
+        let key = (42u64, "borrowed").to_key();
+        let (id, name): (u64, &str) = key.decode_borrowed()?;
+        assert_eq!((id, name), (42, "borrowed"));
+        let range = key.as_bytes().as_ptr_range();
+        assert!(range.contains(&name.as_ptr()));
+
+        let out: KvResult<(u64,)> = key.decode_borrowed();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentCount {
+                expected: 1,
+                found: 2
+            })
+        ));
+        let out: KvResult<(&str, u64)> = key.decode_borrowed();
+        assert!(matches!(
+            out,
+            Err(KvError::WrongSegmentType {
+                index: 0,
+                expected: "string",
+                got: "u64"
+            })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn decode_errors_name_the_segment() {
+        use crate::KvKey;
+
+        let err = <(u64, i64, bool)>::try_from((55u64, -1i64, "yes").to_key()).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error decoding key: expected segment 2 to be bool, got string"
+        );
+
+        // A tag no segment type uses: report the byte itself.
+        let mut bytes = (55u64,).to_key().as_bytes().to_vec();
+        bytes.extend_from_slice(&[0xee, 0x00]);
+        let err = <(u64, String)>::try_from(KvKey::from_bytes(bytes)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Error decoding key: Segment 1 is corrupt: expected string, found tag byte 0xee"
+        );
+    }
+
+    #[test]
+    fn decode_error_bogus_string_length() {
+        use crate::{KvError, KvKey};
+
+        // A string tag claiming far more bytes than follow it.
+        for declared in [4u64, u32::MAX as u64 + 1, u64::MAX] {
+            let mut bytes = vec![0x04];
+            bytes.extend_from_slice(&declared.to_be_bytes());
+            bytes.extend_from_slice(b"abc");
+            let key = KvKey::from_bytes(bytes);
+
+            let out: KvResult<(String,)> = key.clone().try_into();
+            assert!(matches!(out, Err(KvError::KeyDecodeError(_))));
+            assert_eq!(crate::display::to_display_string(key.as_bytes()), None);
+        }
+    }
+
+    #[test]
+    fn string_length_width_is_consistent() -> KvResult<()> {
+        use super::KeyCodecExt;
+        use crate::{KeyDecoder, KvKey, LegacyCodec};
+
+        let s = "x".repeat(300);
+        let key = (s.as_str(), 7u64).to_key();
+        // Tag, 8-byte big-endian length, bytes, then the next segment.
+        let mut expected = vec![0x04];
+        expected.extend_from_slice(&300u64.to_be_bytes());
+        expected.extend_from_slice(s.as_bytes());
+        expected.push(0x01);
+        expected.extend_from_slice(&7u64.to_be_bytes());
+        assert_eq!(key.as_bytes(), expected);
+
+        let mut decoder = KeyDecoder::new(key.as_bytes());
+        assert_eq!(decoder.next_str().as_deref(), Some(s.as_str()));
+        assert_eq!(decoder.next_u64(), Some(7));
+        assert!(decoder.is_empty());
+
+        let mut rest = key.as_bytes();
+        LegacyCodec.skip_segment(&mut rest);
+        assert_eq!(rest, &key.as_bytes()[1 + 8 + 300..]);
+
+        let display = crate::display::to_display_string(key.as_bytes()).unwrap();
+        assert_eq!(
+            crate::display::parse_display_string_to_key(&display),
+            Some(key.clone())
+        );
+        let mut built = KvKey::new();
+        built.push(&s);
+        built.push(&7u64);
+        assert_eq!(built, key);
+        Ok(())
+    }
+
+    #[test]
+    fn every_segment_type_uses_its_tag() {
+        use super::KeyCodecExt;
+        use super::key_segment::KeySegmentTag;
+        use crate::{KeyCodec, KeyDecoder, KvKey, LegacyCodec};
+
+        let cases: [(KeySegmentTag, KvKey); 9] = [
+            (KeySegmentTag::U64, (5u64,).to_key()),
+            (KeySegmentTag::I64, (-5i64,).to_key()),
+            (KeySegmentTag::Bool, (true,).to_key()),
+            (KeySegmentTag::String, ("s",).to_key()),
+            (KeySegmentTag::Char, ('c',).to_key()),
+            (KeySegmentTag::Option, (Some(1u64),).to_key()),
+            (KeySegmentTag::Timestamp, {
+                let mut key = KvKey::new();
+                LegacyCodec.encode_timestamp_nanos(1, &mut key.0);
+                key
+            }),
+            (KeySegmentTag::Ip, {
+                let mut key = KvKey::new();
+                LegacyCodec.encode_ip(std::net::Ipv4Addr::LOCALHOST.into(), &mut key.0);
+                key
+            }),
+            (KeySegmentTag::Enum, {
+                let mut key = KvKey::new();
+                LegacyCodec.encode_enum(2, &mut key.0);
+                key
+            }),
+        ];
+        assert_eq!(cases.len(), KeySegmentTag::ALL.len());
+        for (tag, key) in &cases {
+            assert_eq!(
+                key.as_bytes()[0],
+                *tag as u8,
+                "{tag:?} encodes with its tag"
+            );
+            assert!(LegacyCodec.peek_kind(key.as_bytes()).is_some(), "{tag:?}");
+        }
+
+        // Each decoder accepts exactly the segment type it is for.
+        type Decode = fn(&mut KeyDecoder) -> bool;
+        let decoders: [Decode; 9] = [
+            |d| d.next_u64().is_some(),
+            |d| d.next_i64().is_some(),
+            |d| d.next_bool().is_some(),
+            |d| d.next_str().is_some(),
+            |d| d.next_char().is_some(),
+            |d| d.next_presence() == Some(true) && d.next_u64().is_some(),
+            |d| d.next_timestamp_nanos().is_some(),
+            |d| d.next_ip().is_some(),
+            |d| d.next_enum().is_some(),
+        ];
+        for (i, (tag, key)) in cases.iter().enumerate() {
+            for (j, decode) in decoders.iter().enumerate() {
+                let mut decoder = KeyDecoder::new(key.as_bytes());
+                let ok = decode(&mut decoder) && decoder.is_empty();
+                assert_eq!(ok, i == j, "{tag:?} segment with decoder {j}");
+            }
+        }
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn ip_addresses_roundtrip_and_sort() -> KvResult<()> {
+        use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+        use crate::{DenoKvCodec, KeyCodec, KvKey};
+
+        let sorted: Vec<IpAddr> = [
+            "0.0.0.0",
+            "10.0.0.1",
+            "10.0.0.255",
+            "10.0.1.0",
+            "192.168.1.9",
+            "192.168.1.10",
+            "255.255.255.255",
+            "::",
+            "::1",
+            "::ffff:10.0.0.1",
+            "2001:db8::1",
+            "2001:db8::ff",
+            "2001:db8:0:1::",
+            "ffff::",
+        ]
+        .iter()
+        .map(|s| s.parse().unwrap())
+        .collect();
+        let codecs: [&dyn KeyCodec; 2] = [&crate::LegacyCodec, &DenoKvCodec];
+        for codec in codecs {
+            let keys: Vec<KvKey> = sorted.iter().map(|ip| (*ip,).to_key_with(codec)).collect();
+            assert!(keys.is_sorted(), "{}", codec.id());
+            for (ip, key) in sorted.iter().zip(&keys) {
+                assert_eq!(key.decode_with::<(IpAddr,)>(codec)?, (*ip,));
+            }
+        }
+
+        // The specific address types share the encoding and refuse the other family.
+        let v4 = Ipv4Addr::new(10, 0, 0, 1);
+        let v6 = Ipv6Addr::LOCALHOST;
+        assert_eq!((v4,).to_key(), (IpAddr::V4(v4),).to_key());
+        let (back, port): (Ipv4Addr, u64) = (v4, 443u64).to_key().try_into()?;
+        assert_eq!((back, port), (v4, 443));
+        let (back,): (Ipv6Addr,) = (v6,).to_key().try_into()?;
+        assert_eq!(back, v6);
+        let err = <(Ipv6Addr,)>::try_from((v4,).to_key()).unwrap_err();
+        assert!(matches!(err, crate::KvError::WrongSegmentType { .. }));
+
+        for ip in &sorted {
+            let key = ("host", Some(*ip)).to_key();
+            let display = crate::display::to_display_string(key.as_bytes()).unwrap();
+            assert_eq!(
+                crate::display::parse_display_string_to_key(&display),
+                Some(key)
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn enum_segments_roundtrip_and_sort_by_variant() -> KvResult<()> {
+        use crate::{KeyCodec, KeyEnum, KvError, KvKey, LegacyCodec};
+
+        // Declared out of alphabetical order, to show that declaration order wins.
+        #[repr(u8)]
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Status {
+            Queued,
+            Running,
+            Done,
+        }
+
+        impl KeyEnum for Status {
+            fn to_discriminant(self) -> u8 {
+                self as u8
+            }
+
+            fn from_discriminant(d: u8) -> Option<Self> {
+                [Status::Queued, Status::Running, Status::Done]
+                    .get(d as usize)
+                    .copied()
+            }
+        }
+
+        let tuples = [
+            ("job", Status::Done, 1u64),
+            ("job", Status::Queued, 9),
+            ("job", Status::Running, 3),
+            ("job", Status::Queued, 2),
+        ];
+        let mut keys: Vec<KvKey> = tuples.iter().map(|t| t.to_key()).collect();
+        keys.sort();
+        let decoded = keys
+            .into_iter()
+            .map(<(String, Status, u64)>::try_from)
+            .collect::<KvResult<Vec<_>>>()?;
+        let order: Vec<(Status, u64)> = decoded.into_iter().map(|(_, s, n)| (s, n)).collect();
+        assert_eq!(
+            order,
+            [
+                (Status::Queued, 2),
+                (Status::Queued, 9),
+                (Status::Running, 3),
+                (Status::Done, 1),
+            ]
+        );
+
+        // A discriminant with no variant, or a plain integer, doesn't decode as the enum.
+        let mut unknown = KvKey::new();
+        LegacyCodec.encode_enum(7, &mut unknown.0);
+        assert!(matches!(
+            <(Status,)>::try_from(unknown),
+            Err(KvError::WrongSegmentType { got: "enum", .. })
+        ));
+        assert!(<(Status,)>::try_from((1u64,).to_key()).is_err());
+
+        let key = (Status::Running, Some(Status::Done)).to_key();
+        let display = crate::display::to_display_string(key.as_bytes()).unwrap();
+        assert_eq!(display, "1e:some(2e)");
+        assert_eq!(
+            crate::display::parse_display_string_to_key(&display),
+            Some(key)
+        );
+        Ok(())
+    }
+
     #[test]
     fn roundtrip_with_strings() -> KvResult<()> {
         let tup = (999u64, "potato", "apple", true);
@@ -171,6 +817,290 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn roundtrip_chars() -> KvResult<()> {
+        let tup = ('a', 'é', '🦀', 7u64, ':');
+        let key = tup.to_key();
+        let out: (char, char, char, u64, char) = key.try_into()?;
+        assert_eq!(tup, out);
+        Ok(())
+    }
+
+    #[test]
+    fn chars_sort_in_code_point_order() {
+        let mut chars = vec!['🦀', 'z', 'é', 'A', '\u{7f}', 'ß', '中'];
+        let mut keys: Vec<_> = chars.iter().map(|c| (*c, 1u64).to_key()).collect();
+        chars.sort();
+        keys.sort();
+        let decoded: Vec<char> = keys
+            .into_iter()
+            .map(|k| <(char, u64)>::try_from(k).unwrap().0)
+            .collect();
+        assert_eq!(decoded, chars);
+    }
+
+    #[test]
+    fn chars_display_roundtrip() {
+        use crate::keys::display::{parse_display_string_to_key, to_display_string};
+
+        let key = ('x', ':', '🦀', "y").to_key();
+        let display = to_display_string(&key.0).unwrap();
+        assert_eq!(display, "'x':'\\:':'🦀':\"y\"");
+        assert_eq!(parse_display_string_to_key(&display), Some(key));
+    }
+
+    #[test]
+    fn string_display_roundtrip() {
+        use crate::keys::display::{parse_display_string_to_key, to_display_string};
+
+        let tricky = [
+            "12345",
+            "-5",
+            "5u",
+            "-5i",
+            "7t",
+            "true",
+            "false",
+            "none",
+            "some(x)",
+            "'c'",
+            "a:b",
+            "a\\:b",
+            "say \"hi\"",
+            "back\\slash\\",
+            "",
+            ":",
+        ];
+        for s in tricky {
+            let key = (s, 1u64, s).to_key();
+            let display = to_display_string(&key.0).unwrap();
+            assert_eq!(
+                parse_display_string_to_key(&display),
+                Some(key),
+                "{display}"
+            );
+        }
+        for c in ['"', '\\', ':', '\''] {
+            let key = (c, "x").to_key();
+            let display = to_display_string(&key.0).unwrap();
+            assert_eq!(
+                parse_display_string_to_key(&display),
+                Some(key),
+                "{display}"
+            );
+        }
+
+        let display = to_display_string(&("12345", "a:\"b\"").to_key().0).unwrap();
+        assert_eq!(display, "\"12345\":\"a:\\\"b\\\"\"");
+        assert_eq!(parse_display_string_to_key(""), Some(crate::KvKey::new()));
+        assert_eq!(parse_display_string_to_key("\"open"), None);
+        assert_eq!(parse_display_string_to_key("\"a\"b"), None);
+
+        // Unquoted strings from older dumps still load, including ones that end in a digit
+        // and `e` but aren't an enum discriminant.
+        assert_eq!(
+            parse_display_string_to_key("users:a\\:b:1u"),
+            Some(("users", "a:b", 1u64).to_key())
+        );
+        for legacy in ["-5e", "300e", "1.5e", "v2e"] {
+            let key = parse_display_string_to_key(&format!("{legacy}:1u")).unwrap();
+            assert_eq!(key, (legacy, 1u64).to_key(), "{legacy}");
+            let display = to_display_string(&key.0).unwrap();
+            assert_eq!(parse_display_string_to_key(&display), Some(key));
+        }
+    }
+
+    #[test]
+    fn integer_display_roundtrip() {
+        use crate::keys::display::{parse_display_string_to_key, to_display_string};
+
+        let cases = [
+            (0u64.to_key(), "0u"),
+            (0i64.to_key(), "0i"),
+            (1u64.to_key(), "1u"),
+            (1i64.to_key(), "1i"),
+            ((-1i64).to_key(), "-1i"),
+            (u64::MAX.to_key(), "18446744073709551615u"),
+            (i64::MAX.to_key(), "9223372036854775807i"),
+            (i64::MIN.to_key(), "-9223372036854775808i"),
+            (
+                (0u64, 0i64, u64::MAX, i64::MIN).to_key(),
+                "0u:0i:18446744073709551615u:-9223372036854775808i",
+            ),
+        ];
+        for (key, want) in cases {
+            let display = to_display_string(&key.0).unwrap();
+            assert_eq!(display, want);
+            let parsed = parse_display_string_to_key(&display).unwrap();
+            assert_eq!(parsed.as_bytes(), key.as_bytes());
+        }
+        assert_ne!(0u64.to_key(), 0i64.to_key());
+
+        for bad in [
+            "-1u",
+            "18446744073709551616u",
+            "9223372036854775808i",
+            "-9223372036854775809i",
+        ] {
+            assert_eq!(parse_display_string_to_key(bad), None, "{bad}");
+        }
+    }
+
+    #[test]
+    fn roundtrip_options() -> KvResult<()> {
+        let some = (5u64, Some("tail".to_string()));
+        let out: (u64, Option<String>) = some.to_key().try_into()?;
+        assert_eq!(out, some);
+
+        let none = (5u64, None::<String>);
+        let out: (u64, Option<String>) = none.to_key().try_into()?;
+        assert_eq!(out, none);
+
+        let nested = (Some(Some(-3i64)), Some(None::<u64>), 1u64);
+        let out: (Option<Option<i64>>, Option<Option<u64>>, u64) = nested.to_key().try_into()?;
+        assert_eq!(out, nested);
+        Ok(())
+    }
+
+    #[test]
+    fn none_sorts_before_some() {
+        let mut keys = vec![
+            (1u64, Some(0u64)).to_key(),
+            (1u64, Some(u64::MAX)).to_key(),
+            (1u64, None::<u64>).to_key(),
+            (0u64, Some(9u64)).to_key(),
+            (2u64, None::<u64>).to_key(),
+        ];
+        keys.sort();
+        let decoded: Vec<(u64, Option<u64>)> =
+            keys.into_iter().map(|k| k.try_into().unwrap()).collect();
+        assert_eq!(
+            decoded,
+            vec![
+                (0, Some(9)),
+                (1, None),
+                (1, Some(0)),
+                (1, Some(u64::MAX)),
+                (2, None),
+            ]
+        );
+    }
+
+    #[test]
+    fn options_display_roundtrip() {
+        use crate::keys::display::{parse_display_string_to_key, to_display_string};
+
+        let key = (None::<u64>, Some("a:b"), Some(Some(7i64))).to_key();
+        let display = to_display_string(&key.0).unwrap();
+        assert_eq!(display, "none:some(\"a:b\"):some(some(7i))");
+        assert_eq!(parse_display_string_to_key(&display), Some(key));
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn timestamps_roundtrip_and_sort() -> KvResult<()> {
+        use crate::keys::display::{parse_display_string_to_key, to_display_string};
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let times: Vec<DateTime<Utc>> = vec![
+            Utc.with_ymd_and_hms(1969, 7, 20, 20, 17, 40).unwrap(),
+            DateTime::from_timestamp_nanos(-1),
+            Utc.with_ymd_and_hms(1700, 1, 1, 0, 0, 0).unwrap(),
+            DateTime::from_timestamp_nanos(0),
+            Utc.with_ymd_and_hms(2024, 2, 29, 12, 0, 0).unwrap() + chrono::Duration::nanoseconds(1),
+            DateTime::from_timestamp_nanos(1),
+        ];
+        let mut keys: Vec<KvKey> = times.iter().map(|t| (*t, "event").to_key()).collect();
+        keys.sort();
+        let decoded: Vec<DateTime<Utc>> = keys
+            .iter()
+            .map(|k| <(DateTime<Utc>, String)>::try_from(k.clone()).map(|(t, _)| t))
+            .collect::<KvResult<_>>()?;
+        let mut sorted = times.clone();
+        sorted.sort();
+        assert_eq!(decoded, sorted);
+
+        let display = to_display_string(&keys[1].0).unwrap();
+        assert_eq!(display, "-14182940000000000t:\"event\"");
+        assert_eq!(
+            parse_display_string_to_key(&display).as_ref(),
+            Some(&keys[1])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn bytes_and_hex_roundtrip() -> KvResult<()> {
+        use crate::KvKey;
+
+        let key = (1u64, "é", true).to_key();
+        assert_eq!(KvKey::from_bytes(key.as_bytes().to_vec()), key);
+        let hex = key.to_hex();
+        assert!(hex.starts_with("010000000000000001"));
+        assert_eq!(KvKey::from_hex(&hex)?, key);
+        assert_eq!(KvKey::from_hex(&hex.to_uppercase())?, key);
+        assert!(KvKey::from_hex("abc").is_err());
+        assert!(KvKey::from_hex("zz").is_err());
+        assert!(KvKey::from_hex("é0").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn raw_text_forms_roundtrip_exactly() -> KvResult<()> {
+        use crate::KvKey;
+
+        let keys = [
+            KvKey::new(),
+            KvKey::from_bytes(vec![0]),
+            KvKey::from_bytes(vec![0xff, 0xfe]),
+            KvKey::from_bytes(vec![0x00, 0x80, 0xc3, 0x28, 0xff]),
+            ("nul\0inside", 0u64, -1i64).to_key(),
+            (1u64, "é", true).to_key(),
+        ];
+        for key in keys {
+            assert_eq!(KvKey::from_hex(&key.to_hex())?, key);
+            let b64 = key.to_base64url();
+            assert!(!b64.contains(['+', '/', '=']));
+            assert_eq!(KvKey::from_base64url(&b64)?, key);
+        }
+
+        assert_eq!(
+            KvKey::from_bytes(b"foobar".to_vec()).to_base64url(),
+            "Zm9vYmFy"
+        );
+        assert_eq!(KvKey::from_bytes(vec![0xfb, 0xff]).to_base64url(), "-_8");
+        assert!(KvKey::from_base64url("Zm9vY").is_err());
+        assert!(KvKey::from_base64url("Zm9v=").is_err());
+        assert!(KvKey::from_base64url("Zm9vYh").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn join_matches_full_tuple() -> KvResult<()> {
+        let parent = ("org", 3u64).to_key();
+        let children = [
+            (
+                parent.join(&("team", true)),
+                ("org", 3u64, "team", true).to_key(),
+            ),
+            (parent.join(&(-1i64,)), ("org", 3u64, -1i64).to_key()),
+            (parent.join(&parent), ("org", 3u64, "org", 3u64).to_key()),
+        ];
+        for (joined, direct) in children {
+            assert_eq!(joined, direct);
+        }
+        // Joining leaves the parent untouched.
+        assert_eq!(parent, ("org", 3u64).to_key());
+
+        let mut built = crate::KvKey::new();
+        built.push_segment(&("org",));
+        built.push_segment(&3u64);
+        built.push_segment(&'x');
+        let out: (String, u64, char) = built.try_into()?;
+        assert_eq!(out, ("org".to_string(), 3, 'x'));
+        Ok(())
+    }
+
     #[test]
     fn roundtrip_false_bool() -> KvResult<()> {
         let tup = (0u64, false, "z");
@@ -179,4 +1109,45 @@ mod tests {
         assert_eq!((tup.0, tup.1, tup.2.to_owned()), out);
         Ok(())
     }
+
+    #[test]
+    fn in_range_is_half_open() {
+        let start = (1u64, 5i64).to_key();
+        let end = (1u64, 9i64).to_key();
+        assert!(start.in_range(Some(&start), Some(&end)));
+        assert!((1u64, 8i64).to_key().in_range(Some(&start), Some(&end)));
+        assert!(!end.in_range(Some(&start), Some(&end)));
+        assert!(!(1u64, 4i64).to_key().in_range(Some(&start), Some(&end)));
+        assert!(end.in_range(Some(&start), None));
+        assert!((0u64,).to_key().in_range(None, Some(&end)));
+        assert!(crate::KvKey::new().in_range(None, None));
+        // Children of the start key are in range; children of the end key are not.
+        assert!(
+            (1u64, 5i64, true)
+                .to_key()
+                .in_range(Some(&start), Some(&end))
+        );
+        assert!(
+            !(1u64, 9i64, true)
+                .to_key()
+                .in_range(Some(&start), Some(&end))
+        );
+    }
+
+    #[test]
+    fn common_prefix_len_counts_shared_bytes() {
+        let users = ("users",).to_key();
+        let alice = ("users", "alice").to_key();
+        let alina = ("users", "alina").to_key();
+        // Segment tag, 8-byte length and "ali".
+        assert_eq!(
+            alice.common_prefix_len(&alina),
+            users.as_bytes().len() + 1 + 8 + 3
+        );
+        assert_eq!(users.common_prefix_len(&alice), users.as_bytes().len());
+        assert_eq!(alice.common_prefix_len(&users), users.as_bytes().len());
+        assert_eq!(alice.common_prefix_len(&alice), alice.as_bytes().len());
+        assert_eq!(alice.common_prefix_len(&(7u64,).to_key()), 0);
+        assert_eq!(crate::KvKey::new().common_prefix_len(&alice), 0);
+    }
 }
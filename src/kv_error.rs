@@ -3,13 +3,43 @@ use std::error::Error;
 #[derive(Debug)]
 pub enum KvError {
     KeyDecodeError(String),
-    InvalidSelector,
+    /// A key had a different number of segments than the type it was decoded into.
+    WrongSegmentCount {
+        expected: usize,
+        found: usize,
+    },
+    /// The segment at `index` of a key didn't have the type it was decoded as.
+    WrongSegmentType {
+        index: usize,
+        expected: &'static str,
+        got: &'static str,
+    },
+    /// A list query's selectors contradict each other; the message says how.
+    InvalidSelector(String),
     ValEncodeError(bincode::error::EncodeError),
     ValDecodeError(bincode::error::DecodeError),
     ValDowncastError(String),
+    /// An encoded value exceeded the limit set with [`crate::Kv::with_max_value_size`].
+    ValueTooLarge {
+        size: usize,
+        max: usize,
+    },
+    /// An encoded key exceeded the limit set with [`crate::Kv::with_max_key_len`].
+    KeyTooLong {
+        len: usize,
+        max: usize,
+    },
+    /// A stored value had a format version this build can't read.
+    UnsupportedValueVersion(u8),
     Other(String),
     #[cfg(feature = "sqlite")]
     SqliteError(rusqlite::Error),
+    #[cfg(feature = "redis")]
+    RedisError(redis::RedisError),
+    #[cfg(feature = "sled")]
+    SledError(sled::Error),
+    #[cfg(feature = "redb")]
+    RedbError(redb::Error),
 }
 
 pub type KvResult<T> = Result<T, KvError>;
@@ -18,19 +48,44 @@ impl std::fmt::Display for KvError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             KvError::KeyDecodeError(str) => write!(f, "Error decoding key: {str}"),
-            KvError::InvalidSelector => write!(
+            KvError::WrongSegmentCount { expected, found } => write!(
                 f,
-                "Invalid selector provided - Provide any one or two of start, end, prefix, not all"
+                "Error decoding key: expected {expected} segments, found {found}"
             ),
+            KvError::WrongSegmentType {
+                index,
+                expected,
+                got,
+            } => write!(
+                f,
+                "Error decoding key: expected segment {index} to be {expected}, got {got}"
+            ),
+            KvError::InvalidSelector(msg) => write!(f, "Invalid selector: {msg}"),
             KvError::ValEncodeError(encode_error) => {
                 write!(f, "Error encoding value with bincode: {encode_error}")
             }
             KvError::ValDecodeError(decode_error) => {
                 write!(f, "Error decoding value with bincode: {decode_error}")
             }
+            KvError::UnsupportedValueVersion(version) => {
+                write!(f, "Unsupported stored value format version {version:#04x}")
+            }
             KvError::Other(str) => write!(f, "Error during kv op: {str}"),
+            #[cfg(feature = "sqlite")]
             KvError::SqliteError(error) => write!(f, "rusqlite error: {error}"),
+            #[cfg(feature = "redis")]
+            KvError::RedisError(error) => write!(f, "redis error: {error}"),
+            #[cfg(feature = "sled")]
+            KvError::SledError(error) => write!(f, "sled error: {error}"),
+            #[cfg(feature = "redb")]
+            KvError::RedbError(error) => write!(f, "redb error: {error}"),
             KvError::ValDowncastError(s) => write!(f, "Error converting to KvValue: {s}"),
+            KvError::ValueTooLarge { size, max } => {
+                write!(f, "Value is {size} bytes, over the {max} byte limit")
+            }
+            KvError::KeyTooLong { len, max } => {
+                write!(f, "Key is {len} bytes, over the {max} byte limit")
+            }
         }
     }
 }
@@ -47,4 +102,26 @@ impl From<std::cell::BorrowMutError> for KvError {
     }
 }
 
-impl Error for KvError {}
+impl<T> From<std::sync::PoisonError<T>> for KvError {
+    fn from(value: std::sync::PoisonError<T>) -> Self {
+        Self::Other(value.to_string())
+    }
+}
+
+impl Error for KvError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            KvError::ValEncodeError(e) => Some(e),
+            KvError::ValDecodeError(e) => Some(e),
+            #[cfg(feature = "sqlite")]
+            KvError::SqliteError(e) => Some(e),
+            #[cfg(feature = "redis")]
+            KvError::RedisError(e) => Some(e),
+            #[cfg(feature = "sled")]
+            KvError::SledError(e) => Some(e),
+            #[cfg(feature = "redb")]
+            KvError::RedbError(e) => Some(e),
+            _ => None,
+        }
+    }
+}
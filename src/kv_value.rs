@@ -1,12 +1,34 @@
 use serde_json::{Map as JsonMap, Number, Value as JsonValue};
-use std::collections::BTreeMap;
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
 
-use crate::KvError;
+use crate::keys::{base64url_decode, base64url_encode};
+use crate::{JsonBinary, KvError, KvResult};
 
 /// Any type which can be stored as a value in the key-value store.
 ///
 /// Supports null, bool, i64, f64, String, arrays, objects, and binary blobs.
-#[derive(Debug, Clone, PartialEq, PartialOrd, bincode::Encode, bincode::Decode)]
+///
+/// # Ordering and equality
+///
+/// Values have a total order, so mixed collections can be sorted deterministically:
+///
+/// - Variants rank `Null < Bool < numbers < String < Array < Object < Binary`.
+/// - Numbers (`I64` and `F64`) compare by mathematical value, exactly, so `I64(2) < F64(2.5)`.
+///   When the values are equal the integer sorts first, so `I64(5) < F64(5.0)`.
+/// - Floats compare with [`f64::total_cmp`]: `-0.0 < 0.0`, and NaN sorts after every other
+///   number and is equal to itself.
+/// - Strings, binary blobs, arrays and objects compare lexicographically.
+///
+/// Equality agrees with this order, so `I64(5) != F64(5.0)` and `F64(NAN) == F64(NAN)`.
+///
+/// ```rust
+/// use stupid_simple_kv::KvValue;
+/// let mut values = vec![KvValue::from("a"), KvValue::F64(1.5), KvValue::Null, KvValue::I64(2)];
+/// values.sort();
+/// assert_eq!(values, vec![KvValue::Null, KvValue::F64(1.5), KvValue::I64(2), KvValue::from("a")]);
+/// ```
+#[derive(Debug, Clone, bincode::Encode, bincode::Decode)]
 pub enum KvValue {
     Null,
     Bool(bool),
@@ -18,6 +40,260 @@ pub enum KvValue {
     Binary(Vec<u8>),
 }
 
+impl KvValue {
+    /// Encode this value into the byte format handed to backends.
+    pub(crate) fn encode(&self) -> KvResult<Vec<u8>> {
+        bincode::encode_to_vec(self, bincode::config::standard()).map_err(KvError::ValEncodeError)
+    }
+
+    /// A rough estimate of how many bytes this value occupies once stored.
+    ///
+    /// Counts one byte per type tag, 8 bytes per number and per length prefix, and the raw
+    /// length of strings, binary blobs and object keys, recursing into arrays and objects.
+    /// The real encoding packs small numbers and lengths more tightly, so this usually
+    /// overestimates, which makes it a safe figure for enforcing size limits.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::KvValue;
+    /// assert_eq!(KvValue::from("abc").size_hint(), 1 + 8 + 3);
+    /// ```
+    pub fn size_hint(&self) -> usize {
+        const TAG: usize = 1;
+        const WORD: usize = 8;
+        TAG + match self {
+            KvValue::Null => 0,
+            KvValue::Bool(_) => 1,
+            KvValue::I64(_) | KvValue::F64(_) => WORD,
+            KvValue::String(s) => WORD + s.len(),
+            KvValue::Binary(bytes) => WORD + bytes.len(),
+            KvValue::Array(items) => WORD + items.iter().map(KvValue::size_hint).sum::<usize>(),
+            KvValue::Object(fields) => {
+                WORD + fields
+                    .iter()
+                    .map(|(k, v)| WORD + k.len() + v.size_hint())
+                    .sum::<usize>()
+            }
+        }
+    }
+
+    /// A short lowercase name for this value's variant, such as `"string"` or `"i64"`.
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            KvValue::Null => "null",
+            KvValue::Bool(_) => "bool",
+            KvValue::I64(_) => "i64",
+            KvValue::F64(_) => "f64",
+            KvValue::String(_) => "string",
+            KvValue::Array(_) => "array",
+            KvValue::Object(_) => "object",
+            KvValue::Binary(_) => "binary",
+        }
+    }
+
+    /// The string, if this is a [`KvValue::String`].
+    ///
+    /// The `as_*` accessors borrow instead of consuming the value like `TryFrom` does, and
+    /// return `None` for any other variant.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::KvValue;
+    /// let value = KvValue::from("hello");
+    /// assert_eq!(value.as_str(), Some("hello"));
+    /// assert_eq!(value.as_i64(), None);
+    /// ```
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            KvValue::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// The integer, if this is a [`KvValue::I64`].
+    pub fn as_i64(&self) -> Option<i64> {
+        match self {
+            KvValue::I64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The integer, if this is a non-negative [`KvValue::I64`].
+    pub fn as_u64(&self) -> Option<u64> {
+        self.as_i64().and_then(|n| u64::try_from(n).ok())
+    }
+
+    /// The float, if this is a [`KvValue::F64`].
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            KvValue::F64(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// The boolean, if this is a [`KvValue::Bool`].
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            KvValue::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    /// The elements, if this is a [`KvValue::Array`].
+    pub fn as_array(&self) -> Option<&[KvValue]> {
+        match self {
+            KvValue::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// The fields, if this is a [`KvValue::Object`].
+    pub fn as_object(&self) -> Option<&BTreeMap<String, KvValue>> {
+        match self {
+            KvValue::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// This number as an `i64`, converting from [`KvValue::F64`] if it is a whole number in
+    /// range.
+    ///
+    /// Unlike the strict `TryFrom` impls, the `to_*` conversions accept either numeric
+    /// variant, which helps with values loaded from JSON where `5` and `5.0` are
+    /// interchangeable. They fail with [`KvError::ValDowncastError`] for non-numbers and for
+    /// numbers that don't fit.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::KvValue;
+    /// assert_eq!(KvValue::F64(5.0).to_i64().unwrap(), 5);
+    /// assert!(KvValue::F64(5.5).to_i64().is_err());
+    /// assert!(KvValue::I64(-1).to_u64().is_err());
+    /// ```
+    pub fn to_i64(&self) -> KvResult<i64> {
+        match self {
+            KvValue::I64(n) => Ok(*n),
+            KvValue::F64(f) if f.fract() == 0.0 && (-I64_LIMIT..I64_LIMIT).contains(f) => {
+                Ok(*f as i64)
+            }
+            _ => Err(self.coercion_error("i64")),
+        }
+    }
+
+    /// This number as a `u64`, converting from either numeric variant if it is a whole,
+    /// non-negative number in range. See [`Self::to_i64`].
+    pub fn to_u64(&self) -> KvResult<u64> {
+        match self {
+            KvValue::I64(n) => u64::try_from(*n).map_err(|_| self.coercion_error("u64")),
+            KvValue::F64(f) if f.fract() == 0.0 && (0.0..2.0 * I64_LIMIT).contains(f) => {
+                Ok(*f as u64)
+            }
+            _ => Err(self.coercion_error("u64")),
+        }
+    }
+
+    /// This number as an `f64`, converting from [`KvValue::I64`]. Integers beyond 2^53
+    /// round to the nearest float. See [`Self::to_i64`].
+    pub fn to_f64(&self) -> KvResult<f64> {
+        match self {
+            KvValue::I64(n) => Ok(*n as f64),
+            KvValue::F64(f) => Ok(*f),
+            _ => Err(self.coercion_error("f64")),
+        }
+    }
+
+    fn coercion_error(&self, target: &str) -> KvError {
+        KvError::ValDowncastError(format!("Cannot convert {self:?} to {target}"))
+    }
+
+    /// The blob, if this is a [`KvValue::Binary`].
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match self {
+            KvValue::Binary(bytes) => Some(bytes),
+            _ => None,
+        }
+    }
+
+    /// Decode a value previously produced by [`KvValue::encode`].
+    pub(crate) fn decode(bytes: &[u8]) -> KvResult<Self> {
+        let (decoded, _) =
+            bincode::decode_from_slice::<KvValue, _>(bytes, bincode::config::standard())
+                .map_err(KvError::ValDecodeError)?;
+        Ok(decoded)
+    }
+}
+
+impl KvValue {
+    /// Position of this value's variant in the cross-type order; numbers share a rank.
+    fn type_rank(&self) -> u8 {
+        match self {
+            KvValue::Null => 0,
+            KvValue::Bool(_) => 1,
+            KvValue::I64(_) | KvValue::F64(_) => 2,
+            KvValue::String(_) => 3,
+            KvValue::Array(_) => 4,
+            KvValue::Object(_) => 5,
+            KvValue::Binary(_) => 6,
+        }
+    }
+}
+
+/// Compare floats by [`f64::total_cmp`], except that every NaN sorts after every number.
+fn cmp_f64(a: f64, b: f64) -> Ordering {
+    a.is_nan().cmp(&b.is_nan()).then(a.total_cmp(&b))
+}
+
+/// 2^63, the first float past `i64::MAX`; everything below it truncates into range.
+const I64_LIMIT: f64 = 9_223_372_036_854_775_808.0;
+
+/// Compare an integer with a float by exact mathematical value.
+fn cmp_i64_f64(i: i64, f: f64) -> Ordering {
+    if f.is_nan() || f >= I64_LIMIT {
+        return Ordering::Less;
+    }
+    if f < -I64_LIMIT {
+        return Ordering::Greater;
+    }
+    let whole = f.trunc();
+    // Equal integer parts: a negative fraction makes the float smaller. Otherwise the float
+    // is larger or mathematically equal, and integers sort first on ties.
+    let tie = if f < whole {
+        Ordering::Greater
+    } else {
+        Ordering::Less
+    };
+    i.cmp(&(whole as i64)).then(tie)
+}
+
+impl Ord for KvValue {
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (KvValue::Null, KvValue::Null) => Ordering::Equal,
+            (KvValue::Bool(a), KvValue::Bool(b)) => a.cmp(b),
+            (KvValue::I64(a), KvValue::I64(b)) => a.cmp(b),
+            (KvValue::F64(a), KvValue::F64(b)) => cmp_f64(*a, *b),
+            (KvValue::I64(a), KvValue::F64(b)) => cmp_i64_f64(*a, *b),
+            (KvValue::F64(a), KvValue::I64(b)) => cmp_i64_f64(*b, *a).reverse(),
+            (KvValue::String(a), KvValue::String(b)) => a.cmp(b),
+            (KvValue::Array(a), KvValue::Array(b)) => a.cmp(b),
+            (KvValue::Object(a), KvValue::Object(b)) => a.cmp(b),
+            (KvValue::Binary(a), KvValue::Binary(b)) => a.cmp(b),
+            _ => self.type_rank().cmp(&other.type_rank()),
+        }
+    }
+}
+
+impl PartialOrd for KvValue {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for KvValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for KvValue {}
+
 impl From<()> for KvValue {
     fn from(_: ()) -> Self {
         KvValue::Null
@@ -54,9 +330,11 @@ impl From<&str> for KvValue {
     }
 }
 
-impl From<Vec<KvValue>> for KvValue {
-    fn from(value: Vec<KvValue>) -> Self {
-        KvValue::Array(value)
+/// Builds an [`KvValue::Array`], converting each element. `Vec<u8>` is the exception: it
+/// becomes [`KvValue::Binary`].
+impl<T: Into<KvValue>> From<Vec<T>> for KvValue {
+    fn from(value: Vec<T>) -> Self {
+        KvValue::Array(value.into_iter().map(Into::into).collect())
     }
 }
 
@@ -66,12 +344,35 @@ impl From<BTreeMap<String, KvValue>> for KvValue {
     }
 }
 
+impl<V: Into<KvValue>> From<HashMap<String, V>> for KvValue {
+    fn from(value: HashMap<String, V>) -> Self {
+        KvValue::Object(value.into_iter().map(|(k, v)| (k, v.into())).collect())
+    }
+}
+
 impl From<Vec<u8>> for KvValue {
     fn from(value: Vec<u8>) -> Self {
         KvValue::Binary(value)
     }
 }
 
+/// Builds an array of [`KvValue::I64`]s, failing if any number is above `i64::MAX`.
+impl TryFrom<Vec<u64>> for KvValue {
+    type Error = KvError;
+
+    fn try_from(value: Vec<u64>) -> Result<Self, Self::Error> {
+        value
+            .into_iter()
+            .map(|n| {
+                i64::try_from(n)
+                    .map(KvValue::I64)
+                    .map_err(|_| KvError::ValDowncastError(format!("{n} is out of range for i64")))
+            })
+            .collect::<KvResult<_>>()
+            .map(KvValue::Array)
+    }
+}
+
 impl From<&JsonValue> for KvValue {
     fn from(value: &JsonValue) -> Self {
         match value {
@@ -89,27 +390,34 @@ impl From<&JsonValue> for KvValue {
             JsonValue::String(s) => KvValue::String(s.clone()),
             JsonValue::Array(arr) => KvValue::Array(arr.iter().map(KvValue::from).collect()),
             JsonValue::Object(obj) => {
-                // Check for exact binary tag
-                if obj.len() == 2
-                    && obj.get("__sskv_bin_value") == Some(&JsonValue::Bool(true))
-                    && obj.contains_key("bytes")
+                // Check for a tagged non-finite float
+                if obj.len() == 1
+                    && let Some(JsonValue::String(text)) = obj.get("__sskv_f64_value")
+                    && let Ok(f) = text.parse::<f64>()
+                    && !f.is_finite()
                 {
-                    if let JsonValue::Array(arr) = &obj["bytes"] {
-                        let maybe_bytes: Option<Vec<u8>> =
-                            arr.iter()
-                                .map(|v| {
-                                    if let JsonValue::Number(n) = v {
-                                        n.as_u64().and_then(|u| {
-                                            if u <= 255 { Some(u as u8) } else { None }
-                                        })
-                                    } else {
-                                        None
-                                    }
-                                })
-                                .collect();
-                        if let Some(bytes) = maybe_bytes {
-                            return KvValue::Binary(bytes);
-                        }
+                    return KvValue::F64(f);
+                }
+
+                // Check for exact binary tag, in either of its forms
+                if obj.len() == 2 && obj.get("__sskv_bin_value") == Some(&JsonValue::Bool(true)) {
+                    let maybe_bytes = match (obj.get("bytes"), obj.get("base64url")) {
+                        (Some(JsonValue::Array(arr)), _) => arr
+                            .iter()
+                            .map(|v| {
+                                if let JsonValue::Number(n) = v {
+                                    n.as_u64()
+                                        .and_then(|u| if u <= 255 { Some(u as u8) } else { None })
+                                } else {
+                                    None
+                                }
+                            })
+                            .collect(),
+                        (_, Some(JsonValue::String(text))) => base64url_decode(text),
+                        _ => None,
+                    };
+                    if let Some(bytes) = maybe_bytes {
+                        return KvValue::Binary(bytes);
                     }
                 }
 
@@ -124,43 +432,85 @@ impl From<&JsonValue> for KvValue {
     }
 }
 
-// From<&KvValue> for JsonValue
-impl From<&KvValue> for JsonValue {
-    fn from(val: &KvValue) -> Self {
-        match val {
+impl KvValue {
+    /// Convert to JSON, writing binary blobs (including nested ones) in the `binary` form.
+    ///
+    /// Both forms read back as [`KvValue::Binary`] through `From<&serde_json::Value>`.
+    /// NaN and the infinities, which JSON can't represent, are written as
+    /// `{"__sskv_f64_value": "NaN"}` (or `"inf"`, `"-inf"`) and read back as `F64`.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{JsonBinary, KvValue};
+    /// let json = KvValue::Binary(vec![0, 1, 2]).to_json(JsonBinary::Base64);
+    /// assert_eq!(json, serde_json::json!({ "__sskv_bin_value": true, "base64url": "AAEC" }));
+    /// assert_eq!(KvValue::from(&json), KvValue::Binary(vec![0, 1, 2]));
+    /// ```
+    pub fn to_json(&self, binary: JsonBinary) -> JsonValue {
+        match self {
             KvValue::Null => JsonValue::Null,
             KvValue::Bool(b) => JsonValue::Bool(*b),
             KvValue::I64(n) => JsonValue::Number(Number::from(*n)),
+            // JSON has no NaN or infinities, so those are tagged with their Rust spelling.
             KvValue::F64(f) => Number::from_f64(*f)
                 .map(JsonValue::Number)
-                .unwrap_or(JsonValue::Null),
+                .unwrap_or_else(|| {
+                    let mut map = JsonMap::new();
+                    map.insert(
+                        "__sskv_f64_value".to_string(),
+                        JsonValue::String(f.to_string()),
+                    );
+                    JsonValue::Object(map)
+                }),
             KvValue::String(s) => JsonValue::String(s.clone()),
-            KvValue::Array(arr) => JsonValue::Array(arr.iter().map(JsonValue::from).collect()),
+            KvValue::Array(arr) => {
+                JsonValue::Array(arr.iter().map(|v| v.to_json(binary)).collect())
+            }
             KvValue::Object(obj) => {
                 let map: JsonMap<String, JsonValue> = obj
                     .iter()
-                    .map(|(k, v)| (k.clone(), JsonValue::from(v)))
+                    .map(|(k, v)| (k.clone(), v.to_json(binary)))
                     .collect();
                 JsonValue::Object(map)
             }
             KvValue::Binary(bytes) => {
                 let mut map = JsonMap::new();
                 map.insert("__sskv_bin_value".to_string(), JsonValue::Bool(true));
-                map.insert(
-                    "bytes".to_string(),
-                    JsonValue::Array(
-                        bytes
-                            .iter()
-                            .map(|b| JsonValue::Number(Number::from(*b)))
-                            .collect(),
+                match binary {
+                    JsonBinary::Base64 => map.insert(
+                        "base64url".to_string(),
+                        JsonValue::String(base64url_encode(bytes)),
+                    ),
+                    JsonBinary::ByteArray => map.insert(
+                        "bytes".to_string(),
+                        JsonValue::Array(
+                            bytes
+                                .iter()
+                                .map(|b| JsonValue::Number(Number::from(*b)))
+                                .collect(),
+                        ),
                     ),
-                );
+                };
                 JsonValue::Object(map)
             }
         }
     }
 }
 
+/// Writes binary blobs as [`JsonBinary::ByteArray`], the form [`crate::JsonCodec`] stores.
+impl From<&KvValue> for JsonValue {
+    fn from(val: &KvValue) -> Self {
+        val.to_json(JsonBinary::ByteArray)
+    }
+}
+
+/// Timestamps are stored as RFC 3339 strings in UTC, e.g. `2024-05-01T12:00:00.5Z`.
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for KvValue {
+    fn from(value: chrono::DateTime<chrono::Utc>) -> Self {
+        KvValue::String(value.to_rfc3339_opts(chrono::SecondsFormat::AutoSi, true))
+    }
+}
+
 impl TryFrom<KvValue> for () {
     type Error = KvError;
 
@@ -200,6 +550,29 @@ impl TryFrom<KvValue> for i64 {
     }
 }
 
+/// Narrowing conversions from [`KvValue::I64`], failing if the number doesn't fit.
+macro_rules! impl_try_from_kv_value_for_int {
+    ($($int:ty),+) => {
+        $(
+            impl TryFrom<KvValue> for $int {
+                type Error = KvError;
+
+                fn try_from(value: KvValue) -> Result<Self, Self::Error> {
+                    let n = i64::try_from(value)?;
+                    <$int>::try_from(n).map_err(|_| {
+                        KvError::ValDowncastError(format!(
+                            "{n} is out of range for {}",
+                            stringify!($int)
+                        ))
+                    })
+                }
+            }
+        )+
+    };
+}
+
+impl_try_from_kv_value_for_int!(u32, i32, u16, i16, u8, i8);
+
 impl TryFrom<KvValue> for f64 {
     type Error = KvError;
 
@@ -239,6 +612,19 @@ impl TryFrom<KvValue> for Vec<KvValue> {
     }
 }
 
+impl TryFrom<KvValue> for BTreeMap<String, KvValue> {
+    type Error = KvError;
+
+    fn try_from(value: KvValue) -> Result<Self, Self::Error> {
+        match value {
+            KvValue::Object(fields) => Ok(fields),
+            _ => Err(KvError::ValDowncastError(format!(
+                "Expected Object, got {value:?}"
+            ))),
+        }
+    }
+}
+
 impl TryFrom<KvValue> for Vec<u8> {
     type Error = KvError;
 
@@ -251,3 +637,19 @@ impl TryFrom<KvValue> for Vec<u8> {
         }
     }
 }
+
+#[cfg(feature = "chrono")]
+impl TryFrom<KvValue> for chrono::DateTime<chrono::Utc> {
+    type Error = KvError;
+
+    fn try_from(value: KvValue) -> Result<Self, Self::Error> {
+        match &value {
+            KvValue::String(s) => chrono::DateTime::parse_from_rfc3339(s)
+                .map(|dt| dt.with_timezone(&chrono::Utc))
+                .map_err(|e| KvError::ValDowncastError(format!("Invalid timestamp {s:?}: {e}"))),
+            _ => Err(KvError::ValDowncastError(format!(
+                "Expected an RFC 3339 timestamp String, got {value:?}"
+            ))),
+        }
+    }
+}
@@ -4,11 +4,15 @@
 //!
 //! ## Features
 //!
-//! - **Order-preserving tuple-style keys**: Compose keys using `u64`, `i64`, `bool`, `String`, tuples, or your own struct if it implements [`IntoKey`].
+//! - **Order-preserving tuple-style keys**: Compose keys using `u64`, `i64`, `bool`, `char`, `String`, `Option`s and tuples of those (plus `DateTime<Utc>` with the `chrono` feature and `IpAddr`s with `net`), or your own struct if it implements [`IntoKey`].
 //! - **Pluggable design**: Swap between memory or SQLite backends, or define your own by implementing [`KvBackend`].
-//! - **Automatic value serialization**: Store any serde-serializable value as a [`KvValue`].
+//!   Wrap a slow backend in [`CachedBackend`] for an LRU read cache.
+//!   Mirror writes onto a second backend during a migration with [`TeeBackend`].
+//! - **Automatic value serialization**: Store any serde-serializable value as a [`KvValue`], encoded by a pluggable [`ValueCodec`].
 //! - **List/query API**: Filter or range-scan with [`KvListBuilder`].
-//! - **Easy JSON import/export**: Dump or restore the store's contents for debugging or migration.
+//! - **Easy JSON and CSV import/export**: Dump or restore the store's contents for debugging, migration, or spreadsheets.
+//! - **Change notifications**: With the `watch` feature, [`Kv::subscribe`] delivers an event for every write.
+//! - **Thread-safe**: [`Kv`] is `Send + Sync`, with an [`RwLockMemoryBackend`] for read-heavy concurrent use.
 //! - **Typed errors** and strict Rust interface.
 //!
 //! ## Quickstart
@@ -59,31 +63,116 @@
 //! ```
 
 mod backends;
+mod csv;
+mod dir_dump;
+mod entry;
 mod keys;
 mod kv_error;
 mod kv_value;
 mod list_builder;
+mod namespace;
+mod stats;
+#[cfg(any(test, feature = "test-util"))]
+pub mod testing;
 mod tests;
+mod trace;
+mod transaction;
+mod typed_kv;
+mod value_codec;
+#[cfg(feature = "watch")]
+mod watch;
 
-use std::cell::RefCell;
-use std::rc::Rc;
+use std::collections::BTreeMap;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::sync::Arc;
 
-pub use crate::backends::{KvBackend, memory_backend::MemoryBackend};
+pub use crate::backends::{
+    KvBackend, KvRangeIter, cached_backend::CachedBackend, memory_backend::MemoryBackend,
+    rwlock_memory_backend::RwLockMemoryBackend, tee_backend::TeeBackend,
+};
+use crate::backends::{PAGE_SIZE, PagedRange, SharedBackend};
+pub use crate::entry::Entry;
 pub use crate::keys::{KvKey, display};
 pub use crate::kv_error::{KvError, KvResult};
 pub use crate::kv_value::KvValue;
-pub use crate::list_builder::KvListBuilder;
-pub use keys::IntoKey;
-use keys::display::{parse_display_string_to_key, to_display_string};
+pub use crate::list_builder::{KvIter, KvListBuilder};
+pub use crate::namespace::Namespace;
+pub use crate::stats::KvStats;
+use crate::trace::OpTimer;
+pub use crate::transaction::Txn;
+pub use crate::typed_kv::TypedKv;
+pub use crate::value_codec::{BincodeCodec, JsonCodec, ValueCodec};
+use crate::value_codec::{VALUE_VERSION, decode_stored, encode_stored, is_versioned};
+#[cfg(feature = "watch")]
+pub use crate::watch::{KvEvent, KvEventKind};
+use keys::display::{parse_display_string_with, to_display_string_with};
+use keys::{CollatingCodec, KeyCodecExt};
+pub use keys::{DenoKvCodec, FromKvKey, IntoKey, KeyCodec, KeyDecoder, KeyEnum, LegacyCodec};
 
 #[cfg(feature = "sqlite")]
-pub use crate::backends::sqlite_backend::SqliteBackend;
+pub use crate::backends::sqlite_backend::{SqliteBackend, SqliteOptions, SqliteSynchronous};
+
+#[cfg(feature = "redb")]
+pub use crate::backends::redb_backend::RedbBackend;
+#[cfg(feature = "redis")]
+pub use crate::backends::redis_backend::RedisBackend;
+#[cfg(feature = "sled")]
+pub use crate::backends::sled_backend::SledBackend;
+
+/// Parsed entries and skipped `(display key, error)` pairs from a lenient JSON import.
+type JsonEntriesLenient = (Vec<(KvKey, KvValue)>, Vec<(String, KvError)>);
+
+/// Entries bucketed by a decoded key segment, as returned by [`Kv::grouped_by_first`].
+pub type EntryGroups<K> = BTreeMap<K, Vec<(KvKey, KvValue)>>;
+
+/// How [`Kv::import_json_str`] treats keys already in the store.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImportMode {
+    /// Clear the store before importing.
+    Replace,
+    /// Overlay the import: imported keys overwrite existing ones, other keys are kept.
+    Merge,
+}
+
+/// One difference between two stores, as reported by [`Kv::diff`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum KvDiff {
+    /// The key is only in the other store.
+    Added(KvKey, KvValue),
+    /// The key is only in this store.
+    Removed(KvKey, KvValue),
+    /// The key is in both, holding `old` here and `new` in the other store.
+    Changed(KvKey, KvValue, KvValue),
+}
+
+/// How JSON dumps write [`KvValue::Binary`] values. Restoring accepts either form.
+///
+/// Both are an object tagged with `"__sskv_bin_value": true`, so they can't be mistaken
+/// for a stored object.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum JsonBinary {
+    /// `{"__sskv_bin_value": true, "base64url": "AAEC"}`: the bytes as unpadded URL-safe
+    /// base64, about 1.33 characters per byte.
+    #[default]
+    Base64,
+    /// `{"__sskv_bin_value": true, "bytes": [0, 1, 2]}`: one JSON number per byte, as
+    /// written by older versions.
+    ByteArray,
+}
 
 /// Main key-value store abstraction.
 ///
 /// Holds a boxed backend and exposes get/set/delete/query APIs.
 /// Instantiate with [`Kv::new`], and use [`KvListBuilder`] for advanced listing/filtering.
 ///
+/// `Kv` is `Send + Sync`: put it in an [`Arc`] to read from several threads, and hand
+/// writer threads a clone or a [`Namespace`]. Clones and namespaces share this store's
+/// lock on the backend, so operations documented as running under a single write lock
+/// (like [`Kv::update`] or [`Kv::delete_if`]) are atomic with respect to each other.
+/// A second `Kv` built over a clone of the same backend has a lock of its own: the two
+/// handles are *not* atomic with respect to each other.
+///
 /// # Example
 /// ```rust
 /// use stupid_simple_kv::{Kv, MemoryBackend, IntoKey};
@@ -93,8 +182,16 @@ pub use crate::backends::sqlite_backend::SqliteBackend;
 /// assert_eq!(out, Some("bar".into()));
 /// ```
 ///
+#[derive(Clone)]
 pub struct Kv {
-    backend: Rc<RefCell<Box<dyn KvBackend>>>,
+    backend: SharedBackend,
+    codec: Arc<dyn ValueCodec>,
+    key_codec: Arc<dyn KeyCodec + Send + Sync>,
+    max_value_size: Option<usize>,
+    max_key_len: Option<usize>,
+    json_binary: JsonBinary,
+    #[cfg(feature = "watch")]
+    subscribers: Arc<watch::Subscribers>,
 }
 
 impl Kv {
@@ -106,32 +203,230 @@ impl Kv {
     /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
     /// ```
     pub fn new(backend: Box<dyn KvBackend>) -> Self {
-        let backend = Rc::new(RefCell::new(backend));
-        Self { backend }
+        Self::with_codec(backend, BincodeCodec)
+    }
+
+    /// Create a new [`Kv`] that encodes values with `codec` instead of the default
+    /// [`BincodeCodec`].
+    ///
+    /// The codec is shared with namespaces and typed views created from this `Kv`. Opening an
+    /// existing store with a different codec than it was written with will fail to decode.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{JsonCodec, Kv, MemoryBackend};
+    /// let mut kv = Kv::with_codec(Box::new(MemoryBackend::new()), JsonCodec);
+    /// kv.set(&("a",), "x".into()).unwrap();
+    /// assert_eq!(kv.get(&("a",)).unwrap(), Some("x".into()));
+    /// ```
+    pub fn with_codec(backend: Box<dyn KvBackend>, codec: impl ValueCodec + 'static) -> Self {
+        let backend = SharedBackend::new(backend);
+        Self {
+            backend,
+            codec: Arc::new(codec),
+            key_codec: Arc::new(LegacyCodec),
+            max_value_size: None,
+            max_key_len: None,
+            json_binary: JsonBinary::default(),
+            #[cfg(feature = "watch")]
+            subscribers: Arc::default(),
+        }
+    }
+
+    /// Reject writes whose encoded value is larger than `max` bytes.
+    ///
+    /// Oversized writes fail with [`KvError::ValueTooLarge`] and leave the store untouched.
+    /// The limit also applies to namespaces and typed views created from this `Kv`
+    /// afterwards. Use [`KvValue::size_hint`] to check a value up front.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, KvError, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_max_value_size(16);
+    /// kv.set(&("small",), "ok".into()).unwrap();
+    /// let err = kv.set(&("big",), "x".repeat(100).into()).unwrap_err();
+    /// assert!(matches!(err, KvError::ValueTooLarge { .. }));
+    /// ```
+    pub fn with_max_value_size(mut self, max: usize) -> Self {
+        self.max_value_size = Some(max);
+        self
+    }
+
+    /// Reject keys whose encoded form is longer than `max` bytes.
+    ///
+    /// Reads, writes and deletes of an over-long key fail with [`KvError::KeyTooLong`]
+    /// before the backend is touched. Prefixes and range bounds given to listing methods
+    /// aren't checked. Like [`Self::with_max_value_size`], the limit carries over to
+    /// namespaces and typed views created afterwards.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, KvError, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_max_key_len(32);
+    /// kv.set(&("short",), 1i64.into()).unwrap();
+    /// let err = kv.set(&("x".repeat(100),), 1i64.into()).unwrap_err();
+    /// assert!(matches!(err, KvError::KeyTooLong { .. }));
+    /// ```
+    pub fn with_max_key_len(mut self, max: usize) -> Self {
+        self.max_key_len = Some(max);
+        self
+    }
+
+    /// Choose how JSON dumps ([`Self::dump_json`], [`Self::dump_ndjson`] and friends) write
+    /// binary values. Defaults to the compact [`JsonBinary::Base64`]; restoring reads
+    /// either form regardless of this setting.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{JsonBinary, Kv, KvValue, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_json_binary(JsonBinary::ByteArray);
+    /// kv.set(&("blob",), KvValue::Binary(vec![1, 2])).unwrap();
+    /// assert_eq!(
+    ///     kv.dump_json().unwrap(),
+    ///     r#"{"\"blob\"":{"__sskv_bin_value":true,"bytes":[1,2]}}"#
+    /// );
+    /// ```
+    pub fn with_json_binary(mut self, binary: JsonBinary) -> Self {
+        self.json_binary = binary;
+        self
+    }
+
+    /// Order string key segments by `collate(s)` instead of by their raw bytes, e.g.
+    /// `str::to_lowercase` for case-insensitive ordering.
+    ///
+    /// Each string segment is stored as its collation key followed by the original, so
+    /// keys sort bytewise by the collated form (ties broken by the original) and still
+    /// decode back to the exact strings written. Lookups match the original string:
+    /// `("Bob",)` and `("bob",)` remain distinct keys. Prefix scans match a trailing string
+    /// segment by its collated form, so [`KvListBuilder::prefix`] with `("bob",)` lists
+    /// both; earlier segments of the prefix must match exactly.
+    ///
+    /// Keys are encoded differently from the default, so pick the collation when creating
+    /// a store and keep it. [`Self::scan_prefix`] decodes with the right codec; otherwise
+    /// decode listed keys with [`KvKey::decode_with`] and [`Self::key_codec`], since
+    /// `TryFrom<KvKey>` assumes the default encoding. Every key argument to this `Kv` and
+    /// its namespaces is encoded for you, but a [`KvKey`] built elsewhere with
+    /// [`IntoKey::to_key`] is used as-is.
+    ///
+    /// Dumps with display-string keys (JSON, NDJSON, MessagePack, CSV) convert keys to the
+    /// default encoding, so they read like any other dump. The `from_*` constructors restore
+    /// them into a default store; to restore into a collated one, create it first and use
+    /// [`Self::import_json_str`] or [`Self::load_ndjson`]. [`Self::dump_dir`] names files
+    /// after the stored bytes instead, so restore it with [`Self::from_dir`] followed by the
+    /// same `with_string_collation`.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, KvValue, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_string_collation(str::to_lowercase);
+    /// for name in ["Bobby", "alice", "Carol"] {
+    ///     kv.set(&("users", name), KvValue::Null).unwrap();
+    /// }
+    /// let names: Vec<String> = kv
+    ///     .list()
+    ///     .prefix(&("users",))
+    ///     .entries()
+    ///     .unwrap()
+    ///     .iter()
+    ///     .map(|(key, _)| key.decode_with::<(String, String)>(kv.key_codec()).unwrap().1)
+    ///     .collect();
+    /// assert_eq!(names, ["alice", "Bobby", "Carol"]);
+    /// ```
+    pub fn with_string_collation(
+        mut self,
+        collate: impl Fn(&str) -> String + Send + Sync + 'static,
+    ) -> Self {
+        self.key_codec = Arc::new(CollatingCodec { collate });
+        self
+    }
+
+    /// The key codec this store encodes key arguments with; [`LegacyCodec`] unless
+    /// [`Self::with_string_collation`] was used.
+    pub fn key_codec(&self) -> &dyn KeyCodec {
+        &*self.key_codec
+    }
+
+    fn key(&self, key: &dyn IntoKey) -> KvKey {
+        key.to_key_with(&*self.key_codec)
+    }
+
+    /// Encode `prefix` as the bound of a prefix scan (see [`KeyCodec::prefix_len`]),
+    /// along with the number of segments it spans.
+    fn prefix_key(&self, prefix: &dyn IntoKey) -> (KvKey, usize) {
+        let mut key = self.key(prefix);
+        let depth = KeyDecoder::with_codec(&key.0, &*self.key_codec)
+            .remaining_segments()
+            .unwrap_or(0);
+        key.0.truncate(self.key_codec.prefix_len(&key.0));
+        (key, depth)
+    }
+
+    /// The bytes of `key` after its first `depth` segments.
+    fn skip_segments<'k>(&self, key: &'k KvKey, depth: usize) -> KvResult<&'k [u8]> {
+        let mut rest = &key.0[..];
+        for _ in 0..depth {
+            self.key_codec.skip_segment(&mut rest).ok_or_else(|| {
+                KvError::KeyDecodeError(format!("Key {key:?} has a corrupt segment"))
+            })?;
+        }
+        Ok(rest)
+    }
+
+    /// Encode a key for a point read or write, enforcing the configured length limit.
+    fn checked_key(&self, key: &dyn IntoKey) -> KvResult<KvKey> {
+        let key = self.key(key);
+        match self.max_key_len {
+            Some(max) if key.0.len() > max => Err(KvError::KeyTooLong {
+                len: key.0.len(),
+                max,
+            }),
+            _ => Ok(key),
+        }
+    }
+
+    /// Encode `value`, enforcing the configured size limit.
+    fn encode_value(&self, value: &KvValue) -> KvResult<Vec<u8>> {
+        let bytes = encode_stored(&*self.codec, value)?;
+        match self.max_value_size {
+            Some(max) if bytes.len() > max => Err(KvError::ValueTooLarge {
+                size: bytes.len(),
+                max,
+            }),
+            _ => Ok(bytes),
+        }
     }
 
     /// Retrieve the value for a given key. Returns `Ok(Some(KvValue))` if present, `Ok(None)` if not present.
     ///
+    /// Like the other single-key methods, `get` takes the key by value or by reference.
+    ///
     /// Example:
     /// ```rust
     /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue, IntoKey};
     /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
     /// let val = kv.get(&(42u64, "x")).unwrap();
+    /// let same = kv.get((42u64, "x")).unwrap();
     /// ```
-    pub fn get(&self, key: &dyn IntoKey) -> KvResult<Option<KvValue>> {
-        let key = key.to_key();
-        let pairs = self
-            .backend
-            .try_borrow()?
-            .get_range(Some(key.clone()), key.successor())?;
-        if pairs.is_empty() {
-            Ok(None)
-        } else {
-            let (decoded, _) =
-                bincode::decode_from_slice::<KvValue, _>(&pairs[0].1, bincode::config::standard())
-                    .map_err(KvError::ValDecodeError)?;
-            Ok(Some(decoded))
-        }
+    pub fn get(&self, key: impl IntoKey) -> KvResult<Option<KvValue>> {
+        let key = self.checked_key(&key)?;
+        let timer = OpTimer::start("get", key.0.len());
+        let bytes = self.backend.read().get(&key)?;
+        timer.finish(bytes.as_ref().map(Vec::len));
+        bytes.map(|b| decode_stored(&*self.codec, &b)).transpose()
+    }
+
+    /// Whether a value is stored at `key`, without decoding it.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// assert!(kv.contains_key(&("a",)).unwrap());
+    /// assert!(!kv.contains_key(&("b",)).unwrap());
+    /// ```
+    pub fn contains_key(&self, key: impl IntoKey) -> KvResult<bool> {
+        self.backend.read().contains_key(&self.checked_key(&key)?)
     }
 
     /// Set the value for a given key, overwriting it if present.
@@ -142,8 +437,26 @@ impl Kv {
     /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
     /// kv.set(&(7u64, true, "foo"), 123i64.into()).unwrap();
     /// ```
-    pub fn set(&mut self, key: &dyn IntoKey, value: KvValue) -> KvResult<()> {
-        self.set_optional(key, Some(value))
+    pub fn set(&mut self, key: impl IntoKey, value: KvValue) -> KvResult<()> {
+        self.set_optional(&key, Some(value))
+    }
+
+    /// Set the value for a given key and return the value it replaced, like
+    /// [`HashMap::insert`](std::collections::HashMap::insert).
+    ///
+    /// The old value is read and the new one written under a single write lock, so no clone
+    /// of this `Kv` can change the key in between.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// assert_eq!(kv.replace(&("mode",), "fast".into()).unwrap(), None);
+    /// let old = kv.replace(&("mode",), "safe".into()).unwrap();
+    /// assert_eq!(old, Some("fast".into()));
+    /// ```
+    pub fn replace(&mut self, key: impl IntoKey, value: KvValue) -> KvResult<Option<KvValue>> {
+        self.update_with(&key, |previous| Ok((Some(value), previous)))
     }
 
     pub(crate) fn set_optional(
@@ -151,55 +464,902 @@ impl Kv {
         key: &dyn IntoKey,
         value: Option<KvValue>,
     ) -> KvResult<()> {
-        let key = key.to_key();
-        if let Some(v) = value {
-            let encoded = bincode::encode_to_vec(v, bincode::config::standard())
-                .map_err(KvError::ValEncodeError)?;
-            self.backend.try_borrow_mut()?.set(key, Some(encoded))
+        let key = self.checked_key(key)?;
+        let encoded = value.map(|v| self.encode_value(&v)).transpose()?;
+        let present = encoded.is_some();
+        let timer = OpTimer::start(if present { "set" } else { "delete" }, key.0.len());
+        let value_size = encoded.as_ref().map(Vec::len);
+        // A `None` value removes the key completely!
+        self.backend.write().set(key.clone(), encoded)?;
+        timer.finish(value_size);
+        self.notify(&key, present);
+        Ok(())
+    }
+
+    #[cfg(feature = "watch")]
+    fn notify(&self, key: &KvKey, present: bool) {
+        let kind = if present {
+            KvEventKind::Set
         } else {
-            // Remove the key completely!
-            self.backend.try_borrow_mut()?.set(key, None)
+            KvEventKind::Delete
+        };
+        self.subscribers.publish(key, kind);
+    }
+
+    #[cfg(not(feature = "watch"))]
+    fn notify(&self, _key: &KvKey, _present: bool) {}
+
+    /// Subscribe to change notifications for every key in the store.
+    ///
+    /// Each successful `set` or `delete` (including batch writes and writes made through a
+    /// [`Namespace`] of this store) sends a [`KvEvent`] to the returned receiver.
+    /// Dropping the receiver unsubscribes.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvEventKind};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let events = kv.subscribe();
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// assert_eq!(events.try_recv().unwrap().kind, KvEventKind::Set);
+    /// ```
+    #[cfg(feature = "watch")]
+    pub fn subscribe(&self) -> std::sync::mpsc::Receiver<KvEvent> {
+        self.subscribers.subscribe(None)
+    }
+
+    /// Subscribe to change notifications for keys under `prefix` only.
+    #[cfg(feature = "watch")]
+    pub fn subscribe_prefix(&self, prefix: &dyn IntoKey) -> std::sync::mpsc::Receiver<KvEvent> {
+        self.subscribers.subscribe(Some(self.prefix_key(prefix).0))
+    }
+
+    /// Set many keys at once.
+    ///
+    /// Backends that support transactions (such as SQLite) apply the whole batch atomically,
+    /// which is much faster than calling [`Self::set`] in a loop for bulk loads.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set_batch((0..100i64).map(|i| ((1u64, i), KvValue::I64(i)))).unwrap();
+    /// ```
+    pub fn set_batch<K: IntoKey>(
+        &mut self,
+        items: impl IntoIterator<Item = (K, KvValue)>,
+    ) -> KvResult<()> {
+        let encoded = items
+            .into_iter()
+            .map(|(key, value)| Ok((self.checked_key(&key)?, Some(self.encode_value(&value)?))))
+            .collect::<KvResult<Vec<_>>>()?;
+        #[cfg(feature = "watch")]
+        let keys: Vec<KvKey> = encoded.iter().map(|(k, _)| k.clone()).collect();
+        let timer = OpTimer::start_range("set_batch")
+            .values(encoded.iter().map(|(_, v)| v.as_ref().map_or(0, Vec::len)));
+        self.backend.write().set_batch(encoded)?;
+        timer.succeed();
+        #[cfg(feature = "watch")]
+        for key in &keys {
+            self.notify(key, true);
         }
+        Ok(())
     }
 
     /// Delete the value for a given key. Returns the key and previous value if present.
     ///
+    /// The value is removed with a single [`KvBackend::take`] and then decoded, under one
+    /// write lock. If it fails to decode, the raw bytes are written back and the error is
+    /// returned, so the entry is left in place. Use [`Self::remove`]
+    /// when the previous value isn't needed.
+    ///
     /// Example:
     /// ```rust
     /// use stupid_simple_kv::{Kv, MemoryBackend, IntoKey};
     /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
     /// let maybe_pair = kv.delete(&(3u64, false));
     /// ```
-    pub fn delete(&mut self, key: &dyn IntoKey) -> KvResult<Option<(KvKey, KvValue)>> {
-        let made = key.to_key();
-        let val = self.get(key)?;
-        if let Some(val) = val {
-            self.set_optional(key, None)?;
-            Ok(Some((made, val)))
-        } else {
-            Ok(None)
+    pub fn delete(&mut self, key: impl IntoKey) -> KvResult<Option<(KvKey, KvValue)>> {
+        let key = self.checked_key(&key)?;
+        let timer = OpTimer::start("delete", key.0.len());
+        let val = {
+            let mut backend = self.backend.write();
+            let Some(bytes) = backend.take(&key)? else {
+                timer.finish(None);
+                return Ok(None);
+            };
+            let val = match decode_stored(&*self.codec, &bytes) {
+                Ok(val) => val,
+                Err(e) => {
+                    backend.set(key, Some(bytes))?;
+                    return Err(e);
+                }
+            };
+            timer.finish(Some(bytes.len()));
+            val
+        };
+        self.notify(&key, false);
+        Ok(Some((key, val)))
+    }
+
+    /// Delete the value for a given key without reading it. Returns whether it was present.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// assert!(kv.remove(&("a",)).unwrap());
+    /// assert!(!kv.remove(&("a",)).unwrap());
+    /// ```
+    pub fn remove(&mut self, key: impl IntoKey) -> KvResult<bool> {
+        let key = self.checked_key(&key)?;
+        let timer = OpTimer::start("delete", key.0.len());
+        let existed = self.backend.write().delete(&key)?;
+        timer.finish(None);
+        if existed {
+            self.notify(&key, false);
+        }
+        Ok(existed)
+    }
+
+    /// Delete `key` only if its current value equals `expected`. Returns whether it was
+    /// deleted; a missing key or a different value leaves the store untouched.
+    ///
+    /// The read and delete happen under a single write lock on the backend, so a value
+    /// changed in between through a clone of this `Kv` is never removed.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("lock",), "worker-1".into()).unwrap();
+    /// assert!(!kv.delete_if(&("lock",), &"worker-2".into()).unwrap());
+    /// assert!(kv.delete_if(&("lock",), &"worker-1".into()).unwrap());
+    /// assert_eq!(kv.get(&("lock",)).unwrap(), None);
+    /// ```
+    pub fn delete_if(&mut self, key: impl IntoKey, expected: &KvValue) -> KvResult<bool> {
+        let key = self.checked_key(&key)?;
+        {
+            let mut backend = self.backend.write();
+            let Some(bytes) = backend.get(&key)? else {
+                return Ok(false);
+            };
+            if decode_stored(&*self.codec, &bytes)? != *expected {
+                return Ok(false);
+            }
+            backend.delete(&key)?;
+        }
+        self.notify(&key, false);
+        Ok(true)
+    }
+
+    /// The number of entries in the store.
+    ///
+    /// This asks the backend for a count (e.g. `COUNT(*)` in SQLite) instead of reading and
+    /// decoding every entry.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// assert_eq!(kv.len().unwrap(), 1);
+    /// ```
+    pub fn len(&self) -> KvResult<usize> {
+        self.backend.read().len()
+    }
+
+    /// Returns `true` if the store holds no entries.
+    pub fn is_empty(&self) -> KvResult<bool> {
+        self.backend.read().is_empty()
+    }
+
+    /// Remove every key from the store.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// kv.clear().unwrap();
+    /// assert!(kv.entries().unwrap().is_empty());
+    /// ```
+    pub fn clear(&mut self) -> KvResult<()> {
+        self.backend.write().clear()
+    }
+
+    /// Delete every key under `prefix`, returning how many were removed.
+    ///
+    /// Like [`Self::clear`] scoped to one logical table. All the deletes are applied with a
+    /// single [`KvBackend::set_batch`] under one write lock, so clones of this `Kv` see
+    /// either the whole prefix or none of it.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, KvValue, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("sessions", 1u64), KvValue::Null).unwrap();
+    /// kv.set(&("sessions", 2u64), KvValue::Null).unwrap();
+    /// kv.set(&("users", 1u64), KvValue::Null).unwrap();
+    /// assert_eq!(kv.truncate_prefix(&("sessions",)).unwrap(), 2);
+    /// assert_eq!(kv.len().unwrap(), 1);
+    /// ```
+    pub fn truncate_prefix(&mut self, prefix: &dyn IntoKey) -> KvResult<usize> {
+        let (start, end) = self.prefix_bounds(Some(prefix));
+        let keys = {
+            let mut backend = self.backend.write();
+            let keys = backend
+                .get_range_iter(start, end)?
+                .map(|pair| pair.map(|(key, _)| key))
+                .collect::<KvResult<Vec<KvKey>>>()?;
+            backend.set_batch(keys.iter().map(|key| (key.clone(), None)).collect())?;
+            keys
+        };
+        for key in &keys {
+            self.notify(key, false);
+        }
+        Ok(keys.len())
+    }
+
+    /// Force every write made so far onto durable storage, e.g. after a large batch.
+    ///
+    /// What this means depends on the backend: SQLite checkpoints its write-ahead log, and
+    /// in-memory backends do nothing.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// kv.flush().unwrap();
+    /// ```
+    pub fn flush(&mut self) -> KvResult<()> {
+        self.backend.write().flush()
+    }
+
+    /// Summarize the store's contents: entry count, value bytes, key lengths, and how many
+    /// values there are of each type. Meant for diagnostics and dashboards.
+    ///
+    /// This reads and decodes every entry in a single streaming pass.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// kv.set(&("b",), "x".into()).unwrap();
+    /// let stats = kv.stats().unwrap();
+    /// assert_eq!(stats.entries, 2);
+    /// assert_eq!(stats.value_types["string"], 1);
+    /// ```
+    pub fn stats(&self) -> KvResult<KvStats> {
+        let backend = self.backend.read();
+        let mut stats = KvStats::default();
+        for entry in backend.get_range_iter(None, None)? {
+            let (key, bytes) = entry?;
+            let value = decode_stored(&*self.codec, &bytes)?;
+            stats.record(&key, bytes.len(), &value);
+        }
+        Ok(stats)
+    }
+
+    /// Copy every entry into `new_backend`, then start using it in place of the current one.
+    ///
+    /// Entries are streamed from the old backend and written in batches, so the whole store is
+    /// never held in memory at once. Namespaces and typed views created from this `Kv` follow
+    /// it to the new backend. If copying fails, the current backend stays in use.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// kv.migrate_to(Box::new(MemoryBackend::new())).unwrap();
+    /// assert_eq!(kv.get(&("a",)).unwrap(), Some(1i64.into()));
+    /// ```
+    pub fn migrate_to(&mut self, mut new_backend: Box<dyn KvBackend>) -> KvResult<()> {
+        let mut backend = self.backend.write();
+        let mut batch = Vec::with_capacity(PAGE_SIZE);
+        for entry in backend.get_range_iter(None, None)? {
+            let (key, bytes) = entry?;
+            batch.push((key, Some(bytes)));
+            if batch.len() == PAGE_SIZE {
+                new_backend.set_batch(std::mem::take(&mut batch))?;
+            }
+        }
+        if !batch.is_empty() {
+            new_backend.set_batch(batch)?;
+        }
+        *backend = new_backend;
+        Ok(())
+    }
+
+    /// Add the format version header to every value stored without one, returning how many
+    /// were rewritten.
+    ///
+    /// Values written by versions of this crate that predate the header are still read
+    /// transparently, so this is only needed to bring a store fully up to date (e.g. before
+    /// handing its backend to a tool that expects the header). Values are rewritten a page at
+    /// a time under a single write lock, without being decoded.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// assert_eq!(kv.upgrade_value_headers().unwrap(), 0);
+    /// ```
+    pub fn upgrade_value_headers(&mut self) -> KvResult<usize> {
+        let mut backend = self.backend.write();
+        let mut start = None;
+        let mut upgraded = 0;
+        loop {
+            let page = backend.get_range_page(start, None, 0, Some(PAGE_SIZE))?;
+            let Some((last, _)) = page.last() else {
+                break;
+            };
+            start = Some(last.immediate_successor());
+            let done = page.len() < PAGE_SIZE;
+            let batch: Vec<_> = page
+                .into_iter()
+                .filter(|(_, bytes)| bytes.first().is_none_or(|&b| !is_versioned(b)))
+                .map(|(key, bytes)| {
+                    let mut headed = Vec::with_capacity(bytes.len() + 1);
+                    headed.push(VALUE_VERSION);
+                    headed.extend_from_slice(&bytes);
+                    (key, Some(headed))
+                })
+                .collect();
+            upgraded += batch.len();
+            if !batch.is_empty() {
+                backend.set_batch(batch)?;
+            }
+            if done {
+                break;
+            }
+        }
+        Ok(upgraded)
+    }
+
+    /// Return the value stored at `key`, or store and return `f()` if there is none.
+    ///
+    /// The lookup and the insert happen under a single write lock on the backend, so no
+    /// clone of this `Kv` can change the entry in between and concurrent callers through
+    /// them never both insert; `f` only runs when the key is absent. A separate `Kv` over
+    /// the same backend is not covered (see [`Kv`]).
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let v = kv.get_or_insert_with(&("visits",), || KvValue::I64(0)).unwrap();
+    /// assert_eq!(v, KvValue::I64(0));
+    /// ```
+    pub fn get_or_insert_with(
+        &mut self,
+        key: &dyn IntoKey,
+        f: impl FnOnce() -> KvValue,
+    ) -> KvResult<KvValue> {
+        let key = self.checked_key(key)?;
+        let value = {
+            let mut backend = self.backend.write();
+            if let Some(bytes) = backend.get(&key)? {
+                return decode_stored(&*self.codec, &bytes);
+            }
+            let value = f();
+            backend.set(key.clone(), Some(self.encode_value(&value)?))?;
+            value
+        };
+        self.notify(&key, true);
+        Ok(value)
+    }
+
+    /// Get an [`Entry`] for `key`, for insert-or-modify edits in the style of
+    /// [`HashMap::entry`](std::collections::HashMap::entry).
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let v = kv.entry(&("greeting",)).or_insert("hello".into()).unwrap();
+    /// assert_eq!(v, "hello".into());
+    /// ```
+    pub fn entry(&mut self, key: impl IntoKey) -> Entry<'_> {
+        let key = self.key(&key);
+        Entry::new(self, key)
+    }
+
+    /// Replace the value at `key` with `f(current)`, deleting the key if `f` returns `None`.
+    ///
+    /// The read and the write happen under a single write lock on the backend, so no clone
+    /// of this `Kv` can change the value in between; a separate `Kv` over the same backend
+    /// can (see [`Kv`]). This is the building block for counters and other
+    /// read-modify-write edits.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let incr = |v: Option<KvValue>| match v {
+    ///     Some(KvValue::I64(n)) => Some(KvValue::I64(n + 1)),
+    ///     _ => Some(KvValue::I64(1)),
+    /// };
+    /// kv.update(&("hits",), incr).unwrap();
+    /// kv.update(&("hits",), incr).unwrap();
+    /// assert_eq!(kv.get(&("hits",)).unwrap(), Some(KvValue::I64(2)));
+    /// ```
+    pub fn update(
+        &mut self,
+        key: &dyn IntoKey,
+        f: impl FnOnce(Option<KvValue>) -> Option<KvValue>,
+    ) -> KvResult<()> {
+        self.update_with(key, |current| Ok((f(current), ())))
+    }
+
+    /// Append `item` to the array stored at `key`, creating a one-element array if the key is
+    /// absent. Returns the new length.
+    ///
+    /// Runs under a single write lock like [`Self::update`].
+    ///
+    /// # Errors
+    /// Fails with [`KvError::ValDowncastError`], leaving the value untouched, if the key holds
+    /// something other than an array.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.array_push(&("log",), "started".into()).unwrap();
+    /// assert_eq!(kv.array_push(&("log",), "stopped".into()).unwrap(), 2);
+    /// ```
+    pub fn array_push(&mut self, key: &dyn IntoKey, item: KvValue) -> KvResult<usize> {
+        self.update_with(key, |current| {
+            let mut items = match current {
+                None => Vec::new(),
+                Some(KvValue::Array(items)) => items,
+                Some(other) => {
+                    return Err(KvError::ValDowncastError(format!(
+                        "Expected Array, got {other:?}"
+                    )));
+                }
+            };
+            items.push(item);
+            let len = items.len();
+            Ok((Some(KvValue::Array(items)), len))
+        })
+    }
+
+    /// Set `field` on the object stored at `key`, creating an empty object first if the key
+    /// is absent. Returns the field's previous value.
+    ///
+    /// Runs under a single write lock like [`Self::update`].
+    ///
+    /// # Errors
+    /// Fails with [`KvError::ValDowncastError`], leaving the value untouched, if the key holds
+    /// something other than an object.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.object_set(&("user", 1u64), "name", "alice".into()).unwrap();
+    /// let old = kv.object_set(&("user", 1u64), "name", "bob".into()).unwrap();
+    /// assert_eq!(old, Some("alice".into()));
+    /// ```
+    pub fn object_set(
+        &mut self,
+        key: &dyn IntoKey,
+        field: &str,
+        value: KvValue,
+    ) -> KvResult<Option<KvValue>> {
+        self.update_with(key, |current| {
+            let mut fields = Self::object_fields(current)?.unwrap_or_default();
+            let previous = fields.insert(field.to_owned(), value);
+            Ok((Some(KvValue::Object(fields)), previous))
+        })
+    }
+
+    /// Remove `field` from the object stored at `key`, returning its value. An absent key is
+    /// left absent.
+    ///
+    /// Runs under a single write lock like [`Self::update`], and fails like
+    /// [`Self::object_set`] if the key holds something other than an object.
+    pub fn object_remove(&mut self, key: &dyn IntoKey, field: &str) -> KvResult<Option<KvValue>> {
+        self.update_with(key, |current| {
+            let Some(mut fields) = Self::object_fields(current)? else {
+                return Ok((None, None));
+            };
+            let removed = fields.remove(field);
+            Ok((Some(KvValue::Object(fields)), removed))
+        })
+    }
+
+    fn object_fields(value: Option<KvValue>) -> KvResult<Option<BTreeMap<String, KvValue>>> {
+        value
+            .map(|value| match value {
+                KvValue::Object(fields) => Ok(fields),
+                other => Err(KvError::ValDowncastError(format!(
+                    "Expected Object, got {other:?}"
+                ))),
+            })
+            .transpose()
+    }
+
+    /// Like [`Self::update`], but `f` may fail (leaving the key untouched) and returns an
+    /// extra result alongside the new value. Atomic only with respect to clones of this
+    /// `Kv`, like `update`.
+    fn update_with<T>(
+        &mut self,
+        key: &dyn IntoKey,
+        f: impl FnOnce(Option<KvValue>) -> KvResult<(Option<KvValue>, T)>,
+    ) -> KvResult<T> {
+        let key = self.checked_key(key)?;
+        let (changed, present, out) = {
+            let mut backend = self.backend.write();
+            let current = backend
+                .get(&key)?
+                .map(|b| decode_stored(&*self.codec, &b))
+                .transpose()?;
+            let existed = current.is_some();
+            let (next, out) = f(current)?;
+            let encoded = next.map(|v| self.encode_value(&v)).transpose()?;
+            let present = encoded.is_some();
+            // Deleting a key that was never there is a no-op.
+            let changed = existed || present;
+            if changed {
+                backend.set(key.clone(), encoded)?;
+            }
+            (changed, present, out)
+        };
+        if changed {
+            self.notify(&key, present);
+        }
+        Ok(out)
+    }
+
+    /// Copy the value stored at `from` to `to`, overwriting any existing value at `to`.
+    /// Returns `false` (and writes nothing) if `from` does not exist.
+    ///
+    /// The read and write happen under a single write lock on the backend, so no clone of
+    /// this `Kv` can change `from` in between.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("template",), "defaults".into()).unwrap();
+    /// assert!(kv.copy(&("template",), &("user", 1u64)).unwrap());
+    /// assert_eq!(kv.get(&("user", 1u64)).unwrap(), Some("defaults".into()));
+    /// ```
+    pub fn copy(&mut self, from: &dyn IntoKey, to: &dyn IntoKey) -> KvResult<bool> {
+        let (from, to) = (self.checked_key(from)?, self.checked_key(to)?);
+        {
+            let mut backend = self.backend.write();
+            let Some(bytes) = backend.get(&from)? else {
+                return Ok(false);
+            };
+            backend.set(to.clone(), Some(bytes))?;
         }
+        self.notify(&to, true);
+        Ok(true)
     }
 
-    /// List all entries in the keyspace.
+    /// Run `f` as one atomic transaction.
+    ///
+    /// `f` reads and writes through a [`Txn`], which sees its own writes before they are
+    /// committed. If `f` returns `Ok`, every buffered write is applied with a single
+    /// [`KvBackend::set_batch`] (one SQL transaction on the SQLite backend); if it returns
+    /// `Err`, nothing is written and the error is passed on. The backend is write-locked
+    /// while `f` runs, so keep it short.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, KvError, KvValue, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("alice",), KvValue::I64(10)).unwrap();
+    /// kv.transaction(|txn| {
+    ///     let Some(KvValue::I64(balance)) = txn.get(&("alice",))? else {
+    ///         return Err(KvError::Other("no account".into()));
+    ///     };
+    ///     txn.set(&("alice",), KvValue::I64(balance - 3))?;
+    ///     txn.set(&("bob",), KvValue::I64(3))
+    /// })
+    /// .unwrap();
+    /// assert_eq!(kv.get(&("bob",)).unwrap(), Some(KvValue::I64(3)));
+    /// ```
+    pub fn transaction<T>(&mut self, f: impl FnOnce(&mut Txn) -> KvResult<T>) -> KvResult<T> {
+        let (out, keys) = {
+            let timer = OpTimer::start_range("transaction");
+            let mut backend = self.backend.write();
+            let mut txn = Txn::new(self, &mut **backend);
+            let out = f(&mut txn)?;
+            let writes = txn.into_writes();
+            let keys: Vec<(KvKey, bool)> = writes
+                .iter()
+                .map(|(key, value)| (key.clone(), value.is_some()))
+                .collect();
+            let timer = timer.values(writes.iter().map(|(_, v)| v.as_ref().map_or(0, Vec::len)));
+            backend.set_batch(writes)?;
+            timer.succeed();
+            (out, keys)
+        };
+        for (key, present) in &keys {
+            self.notify(key, *present);
+        }
+        Ok(out)
+    }
+
+    /// List all entries in the keyspace, in ascending key order.
     /// Usually, you should use [`Self::list`] with filters for efficient selects.
     ///
+    /// Keys compare by their encoded bytes, so the order is the one [`KvKey`]'s `Ord`
+    /// gives. Every backend must return ranges in that order; see [`KvBackend`].
+    ///
     /// Example:
     /// ```rust
     /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue, IntoKey};
     /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
     /// let all = kv.entries().unwrap();
     /// ```
-    pub fn entries(&mut self) -> KvResult<Vec<(KvKey, KvValue)>> {
-        KvListBuilder {
-            backend: self.backend.clone(),
-            start: None,
-            end: None,
-            prefix: None,
-        }
+    pub fn entries(&self) -> KvResult<Vec<(KvKey, KvValue)>> {
+        KvListBuilder::new(
+            self.backend.clone(),
+            self.codec.clone(),
+            self.key_codec.clone(),
+        )
         .entries()
     }
 
+    /// Every key in the store decoded into `K` with this store's key codec, in ascending key
+    /// order. Keys are read a page at a time and values are dropped without being decoded.
+    ///
+    /// The first key that doesn't decode as `K` fails the whole call.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&(2u64, "b"), KvValue::Null).unwrap();
+    /// kv.set(&(1u64, "a"), KvValue::Null).unwrap();
+    /// let keys: Vec<(u64, String)> = kv.keys_as().unwrap();
+    /// assert_eq!(keys, vec![(1, "a".to_string()), (2, "b".to_string())]);
+    /// ```
+    pub fn keys_as<K: for<'a> FromKvKey<'a>>(&self) -> KvResult<Vec<K>> {
+        PagedRange::new(None, None, |start, end, limit| {
+            self.backend
+                .read()
+                .get_range_page(start, end, 0, Some(limit))
+        })
+        .map(|pair| pair?.0.decode_with(&*self.key_codec))
+        .collect()
+    }
+
+    /// Lazily iterate over every entry in the keyspace, in key order.
+    ///
+    /// Unlike [`Self::entries`], nothing is collected: entries are streamed from the backend
+    /// a page at a time and decoded as they are yielded, so stores larger than memory can be
+    /// scanned and the loop can stop early.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), KvValue::I64(1)).unwrap();
+    /// for row in kv.entries_iter() {
+    ///     let (key, value) = row.unwrap();
+    /// }
+    /// ```
+    pub fn entries_iter(&self) -> KvIter {
+        KvIter::new(self.backend.clone(), self.codec.clone(), None, None)
+    }
+
+    /// Compare this store with `other`, listing every difference in key order.
+    ///
+    /// Both keyspaces are read side by side a page at a time, each page a separate
+    /// [`KvBackend::get_range_page`] call, in a single merge pass. Values are decoded and
+    /// then compared by variant and encoded bytes, so `I64(5)` against `F64(5.0)`, or
+    /// `F64(0.0)` against `F64(-0.0)`, counts as a change. Stores using different
+    /// [`ValueCodec`]s still compare by content.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{IntoKey, Kv, KvDiff, MemoryBackend};
+    /// let mut old = Kv::new(Box::new(MemoryBackend::new()));
+    /// let mut new = Kv::new(Box::new(MemoryBackend::new()));
+    /// old.set(&("a",), 1i64.into()).unwrap();
+    /// new.set(&("a",), 2i64.into()).unwrap();
+    /// assert_eq!(
+    ///     old.diff(&new).unwrap(),
+    ///     vec![KvDiff::Changed(("a",).to_key(), 1i64.into(), 2i64.into())]
+    /// );
+    /// ```
+    pub fn diff(&self, other: &Kv) -> KvResult<Vec<KvDiff>> {
+        use std::cmp::Ordering;
+
+        fn pages(kv: &Kv) -> impl Iterator<Item = KvResult<(KvKey, KvValue)>> + '_ {
+            PagedRange::new(None, None, |start, end, limit| {
+                kv.backend.read().get_range_page(start, end, 0, Some(limit))
+            })
+            .map(|pair| {
+                let (key, bytes) = pair?;
+                Ok((key, decode_stored(&*kv.codec, &bytes)?))
+            })
+        }
+
+        let (mut ours, mut theirs) = (pages(self), pages(other));
+        let mut a = ours.next().transpose()?;
+        let mut b = theirs.next().transpose()?;
+        let mut diffs = Vec::new();
+        loop {
+            match (a.take(), b.take()) {
+                (None, None) => break,
+                (Some((key, value)), None) => {
+                    diffs.push(KvDiff::Removed(key, value));
+                    a = ours.next().transpose()?;
+                }
+                (None, Some((key, value))) => {
+                    diffs.push(KvDiff::Added(key, value));
+                    b = theirs.next().transpose()?;
+                }
+                (Some(x), Some(y)) => match x.0.cmp(&y.0) {
+                    Ordering::Less => {
+                        diffs.push(KvDiff::Removed(x.0, x.1));
+                        (a, b) = (ours.next().transpose()?, Some(y));
+                    }
+                    Ordering::Greater => {
+                        diffs.push(KvDiff::Added(y.0, y.1));
+                        (a, b) = (Some(x), theirs.next().transpose()?);
+                    }
+                    Ordering::Equal => {
+                        if x.1.encode()? != y.1.encode()? {
+                            diffs.push(KvDiff::Changed(x.0, x.1, y.1));
+                        }
+                        (a, b) = (ours.next().transpose()?, theirs.next().transpose()?);
+                    }
+                },
+            }
+        }
+        Ok(diffs)
+    }
+
+    /// Hand every entry to `f` in key order, `chunk_size` entries at a time.
+    ///
+    /// Each chunk is a separate page read that resumes after the last key of the previous
+    /// one (see [`KvListBuilder::after`]), so only one chunk is held in memory and the
+    /// backend isn't locked while `f` runs. Entries written behind the cursor during the
+    /// scan are not seen. The first error from the backend, decoding, or `f` stops the scan
+    /// and is returned.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set_batch((0..25i64).map(|i| ((i,), KvValue::I64(i)))).unwrap();
+    /// let mut sizes = Vec::new();
+    /// kv.for_each_chunk(10, |chunk| {
+    ///     sizes.push(chunk.len());
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// assert_eq!(sizes, [10, 10, 5]);
+    /// ```
+    pub fn for_each_chunk(
+        &self,
+        chunk_size: usize,
+        mut f: impl FnMut(&[(KvKey, KvValue)]) -> KvResult<()>,
+    ) -> KvResult<()> {
+        if chunk_size == 0 {
+            return Err(KvError::Other("chunk_size must be at least 1".into()));
+        }
+        let mut cursor: Option<KvKey> = None;
+        loop {
+            let mut list = self.list();
+            list.limit(chunk_size);
+            if let Some(after) = &cursor {
+                list.after(after);
+            }
+            let chunk = list.entries()?;
+            let Some((last, _)) = chunk.last() else {
+                return Ok(());
+            };
+            cursor = Some(last.clone());
+            f(&chunk)?;
+            if chunk.len() < chunk_size {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Return the entry with the smallest key, optionally restricted to keys under `prefix`.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&(1u64, 5i64), KvValue::I64(5)).unwrap();
+    /// kv.set(&(1u64, 2i64), KvValue::I64(2)).unwrap();
+    /// let (_, oldest) = kv.first(Some(&(1u64,))).unwrap().unwrap();
+    /// assert_eq!(oldest, KvValue::I64(2));
+    /// ```
+    pub fn first(&self, prefix: Option<&dyn IntoKey>) -> KvResult<Option<(KvKey, KvValue)>> {
+        let (start, end) = self.prefix_bounds(prefix);
+        let pair = self.backend.read().first(start, end)?;
+        pair.map(|(k, v)| Ok((k, decode_stored(&*self.codec, &v)?)))
+            .transpose()
+    }
+
+    /// Return the entry with the largest key, optionally restricted to keys under `prefix`.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&(1u64, 5i64), KvValue::I64(5)).unwrap();
+    /// kv.set(&(1u64, 2i64), KvValue::I64(2)).unwrap();
+    /// let (_, latest) = kv.last(Some(&(1u64,))).unwrap().unwrap();
+    /// assert_eq!(latest, KvValue::I64(5));
+    /// ```
+    pub fn last(&self, prefix: Option<&dyn IntoKey>) -> KvResult<Option<(KvKey, KvValue)>> {
+        let (start, end) = self.prefix_bounds(prefix);
+        let pair = self.backend.read().last(start, end)?;
+        pair.map(|(k, v)| Ok((k, decode_stored(&*self.codec, &v)?)))
+            .transpose()
+    }
+
+    /// List the distinct keys one segment longer than `prefix` that have entries at or
+    /// below them, like listing the subdirectories of a directory.
+    ///
+    /// Each child is found with a single [`KvBackend::first`] lookup, and its subtree is
+    /// then skipped, so the cost grows with the number of children rather than the number
+    /// of entries. An entry stored at exactly `prefix` is not a child and is ignored.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue, IntoKey};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("users", 1u64, "name"), KvValue::Null).unwrap();
+    /// kv.set(&("users", 1u64, "email"), KvValue::Null).unwrap();
+    /// kv.set(&("users", 2u64, "name"), KvValue::Null).unwrap();
+    /// let ids = kv.list_prefixes(&("users",)).unwrap();
+    /// assert_eq!(ids, vec![("users", 1u64).to_key(), ("users", 2u64).to_key()]);
+    /// ```
+    pub fn list_prefixes(&self, prefix: &dyn IntoKey) -> KvResult<Vec<KvKey>> {
+        let (prefix, depth) = self.prefix_key(prefix);
+        let end = prefix.successor();
+        let backend = self.backend.read();
+        let mut children = Vec::new();
+        let mut next = Some(prefix.clone());
+        while let Some(start) = next.take() {
+            let Some((key, _)) = backend.first(Some(start), end.clone())? else {
+                break;
+            };
+            let mut rest = self.skip_segments(&key, depth)?;
+            if rest.is_empty() {
+                next = Some(key.immediate_successor());
+                continue;
+            }
+            self.key_codec.skip_segment(&mut rest).ok_or_else(|| {
+                KvError::KeyDecodeError(format!("Key {key:?} has a corrupt segment"))
+            })?;
+            let child = KvKey(key.0[..key.0.len() - rest.len()].to_vec());
+            next = child.successor();
+            children.push(child);
+        }
+        Ok(children)
+    }
+
+    fn prefix_bounds(&self, prefix: Option<&dyn IntoKey>) -> (Option<KvKey>, Option<KvKey>) {
+        match prefix {
+            Some(prefix) => {
+                let (prefix, _) = self.prefix_key(prefix);
+                let end = prefix.successor();
+                (Some(prefix), end)
+            }
+            None => (None, None),
+        }
+    }
+
     /// Build a query for scanning/filtering the key-value space.
     /// Use methods like [`KvListBuilder::prefix`], [`KvListBuilder::start`], [`KvListBuilder::end`] for range scans.
     ///
@@ -211,18 +1371,107 @@ impl Kv {
     /// let results = kv.list().prefix(&(1u64,)).entries().unwrap();
     /// ```
     pub fn list(&self) -> KvListBuilder {
-        KvListBuilder::new(self.backend.clone())
+        KvListBuilder::new(
+            self.backend.clone(),
+            self.codec.clone(),
+            self.key_codec.clone(),
+        )
     }
 
-    /// Dump all keys and values as a pretty, parseable JSON value.
-    /// Useful for debugging or migration. Keys are debug-formatted.
-    pub fn to_serde_json(&mut self) -> KvResult<serde_json::Value> {
+    /// List every entry under `prefix`, decoding each full key into `K` with this store's
+    /// key codec.
+    ///
+    /// Stops at the first key that doesn't decode as `K`, returning its error.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&(1u64, -5i64), KvValue::Null).unwrap();
+    /// let rows: Vec<((u64, i64), KvValue)> = kv.scan_prefix(&(1u64,)).unwrap();
+    /// assert_eq!(rows[0].0, (1, -5));
+    /// ```
+    pub fn scan_prefix<K: for<'a> FromKvKey<'a>>(
+        &self,
+        prefix: &dyn IntoKey,
+    ) -> KvResult<Vec<(K, KvValue)>> {
+        self.list()
+            .prefix(prefix)
+            .map(|(key, value)| Ok((key.decode_with(&*self.key_codec)?, value)))
+    }
+
+    /// List every entry under `prefix`, bucketed by the first key segment after the prefix.
+    ///
+    /// Keys come back sorted, so entries sharing a first segment are adjacent and grouping
+    /// takes a single pass. Each bucket keeps its entries in key order, with full keys. Fails
+    /// if a key under `prefix` has no further segments, or one that doesn't decode as `K`.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("tenants", 1u64, "a"), KvValue::Null).unwrap();
+    /// kv.set(&("tenants", 1u64, "b"), KvValue::Null).unwrap();
+    /// kv.set(&("tenants", 2u64, "a"), KvValue::Null).unwrap();
+    /// let groups = kv.grouped_by_first::<u64>(&("tenants",)).unwrap();
+    /// assert_eq!(groups[&1].len(), 2);
+    /// assert_eq!(groups[&2].len(), 1);
+    /// ```
+    pub fn grouped_by_first<K>(&self, prefix: &dyn IntoKey) -> KvResult<EntryGroups<K>>
+    where
+        K: for<'a> FromKvKey<'a> + Ord,
+    {
+        let (prefix, depth) = self.prefix_key(prefix);
+        let mut groups = EntryGroups::<K>::new();
+        let mut current: Option<(K, Vec<(KvKey, KvValue)>)> = None;
+        for entry in self.list().prefix(&prefix).iter()? {
+            let (key, value) = entry?;
+            let mut decoder =
+                KeyDecoder::with_codec(self.skip_segments(&key, depth)?, &*self.key_codec);
+            let group = K::from_kv_key(&mut decoder).ok_or_else(|| decoder.mismatch::<K>(0, 1))?;
+            match &mut current {
+                Some((current_group, rows)) if *current_group == group => rows.push((key, value)),
+                _ => {
+                    if let Some((done, rows)) = current.replace((group, vec![(key, value)])) {
+                        groups.entry(done).or_default().extend(rows);
+                    }
+                }
+            }
+        }
+        if let Some((group, rows)) = current {
+            groups.entry(group).or_default().extend(rows);
+        }
+        Ok(groups)
+    }
+
+    /// Get a [`Namespace`] view of this store, confined to keys under `prefix`.
+    ///
+    /// The namespace shares this store's backend; writes through it are immediately
+    /// visible here (with the prefix) and vice versa.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let mut metrics = kv.namespace(&("metrics",));
+    /// metrics.set(&("cpu",), KvValue::F64(0.5)).unwrap();
+    /// assert_eq!(kv.get(&("metrics", "cpu")).unwrap(), Some(KvValue::F64(0.5)));
+    /// ```
+    pub fn namespace(&self, prefix: &dyn IntoKey) -> Namespace {
+        Namespace::new(self.clone(), self.key(prefix))
+    }
+
+    /// Dump all keys and values as a parseable JSON value.
+    /// Useful for debugging or migration. Keys are written as display strings.
+    pub fn to_serde_json(&self) -> KvResult<serde_json::Value> {
+        self.entries_to_json(self.entries()?)
+    }
+
+    fn entries_to_json(&self, entries: Vec<(KvKey, KvValue)>) -> KvResult<serde_json::Value> {
         let mut map = serde_json::Map::new();
-        for (key, value) in self.entries()? {
-            let display = to_display_string(&key.0).ok_or(KvError::KeyDecodeError(format!(
-                "Invalid key {key:#?}.\nThis should never happen, please file a bug report."
-            )))?;
-            map.insert(display, serde_json::Value::from(&value));
+        for (key, value) in entries {
+            let display = to_display_string_with(&key, &*self.key_codec)?;
+            map.insert(display, value.to_json(self.json_binary));
         }
         Ok(serde_json::Value::Object(map))
     }
@@ -230,29 +1479,174 @@ impl Kv {
     /// Construct a new `Kv` from a serde-compatible JSON value (from [`to_serde_json`]).
     /// Fails if any key or value is incompatible.
     pub fn from_serde_json(backend: Box<dyn KvBackend>, json: serde_json::Value) -> KvResult<Self> {
-        if let Some(obj) = json.as_object() {
-            let mut kv = Self::new(backend);
-            for (display, value) in obj.iter() {
-                let key = parse_display_string_to_key(display).ok_or(KvError::KeyDecodeError(
-                    format!("Could not decode JSON key {display} to KvKey."),
-                ))?;
-                kv.set(&key, KvValue::from(value))?;
-            }
-            Ok(kv)
-        } else {
-            Err(KvError::Other(format!(
+        let mut kv = Self::new(backend);
+        kv.set_batch(Self::json_entries(&json, &LegacyCodec)?)?;
+        Ok(kv)
+    }
+
+    /// Like [`Self::from_serde_json`], but entries whose key can't be parsed are skipped
+    /// instead of failing the whole restore.
+    ///
+    /// Returns the new `Kv` along with each skipped display key and the reason it was
+    /// rejected. A top-level value that isn't an object is still an error.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let json = serde_json::json!({ "\"ok\"": 1, "\"broken": 2 });
+    /// let (kv, skipped) = Kv::from_serde_json_lenient(Box::new(MemoryBackend::new()), json).unwrap();
+    /// assert_eq!(kv.len().unwrap(), 1);
+    /// assert_eq!(skipped[0].0, "\"broken");
+    /// ```
+    pub fn from_serde_json_lenient(
+        backend: Box<dyn KvBackend>,
+        json: serde_json::Value,
+    ) -> KvResult<(Self, Vec<(String, KvError)>)> {
+        let mut kv = Self::new(backend);
+        let (entries, skipped) = Self::json_entries_lenient(&json, &LegacyCodec)?;
+        kv.set_batch(entries)?;
+        Ok((kv, skipped))
+    }
+
+    fn json_object(
+        json: &serde_json::Value,
+    ) -> KvResult<&serde_json::Map<String, serde_json::Value>> {
+        json.as_object().ok_or_else(|| {
+            KvError::Other(format!(
                 "Invalid JSON value while trying to make Kv from serde_json::Value: {json}"
-            )))
+            ))
+        })
+    }
+
+    fn json_entry(
+        display: &str,
+        value: &serde_json::Value,
+        key_codec: &dyn KeyCodec,
+    ) -> KvResult<(KvKey, KvValue)> {
+        let key = parse_display_string_with(display, key_codec).ok_or(KvError::KeyDecodeError(
+            format!("Could not decode JSON key {display} to KvKey."),
+        ))?;
+        Ok((key, KvValue::from(value)))
+    }
+
+    fn json_entries(
+        json: &serde_json::Value,
+        key_codec: &dyn KeyCodec,
+    ) -> KvResult<Vec<(KvKey, KvValue)>> {
+        Self::json_object(json)?
+            .iter()
+            .map(|(display, value)| Self::json_entry(display, value, key_codec))
+            .collect()
+    }
+
+    fn json_entries_lenient(
+        json: &serde_json::Value,
+        key_codec: &dyn KeyCodec,
+    ) -> KvResult<JsonEntriesLenient> {
+        let mut entries = Vec::new();
+        let mut skipped = Vec::new();
+        for (display, value) in Self::json_object(json)? {
+            match Self::json_entry(display, value, key_codec) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => skipped.push((display.clone(), e)),
+            }
         }
+        Ok((entries, skipped))
     }
 
-    /// Dump the entire database to a JSON string.
-    /// See [`from_json_string`] for restoring.
-    pub fn dump_json(&mut self) -> KvResult<String> {
+    /// Import a JSON dump (as written by [`Self::dump_json`]) into this store.
+    ///
+    /// With [`ImportMode::Replace`] the store is cleared first, so it ends up holding exactly
+    /// the dumped entries. With [`ImportMode::Merge`] the dump is layered on top: keys in
+    /// the dump overwrite existing ones and every other key is kept.
+    ///
+    /// The whole dump is parsed before anything is written, so a malformed dump leaves the
+    /// store untouched.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{ImportMode, Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("keep",), "old".into()).unwrap();
+    /// kv.import_json_str(r#"{"patch": "new"}"#, ImportMode::Merge).unwrap();
+    /// assert_eq!(kv.get(&("keep",)).unwrap(), Some("old".into()));
+    /// assert_eq!(kv.get(&("patch",)).unwrap(), Some("new".into()));
+    /// ```
+    pub fn import_json_str(&mut self, json: &str, mode: ImportMode) -> KvResult<()> {
+        let json: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| KvError::Other(format!("serde error parsing json: {e}")))?;
+        let entries = Self::json_entries(&json, &*self.key_codec)?;
+        if mode == ImportMode::Replace {
+            self.clear()?;
+        }
+        self.set_batch(entries)
+    }
+
+    /// Like [`Self::import_json_str`], but entries whose key can't be parsed are skipped and
+    /// returned alongside the reason, rather than aborting the import.
+    ///
+    /// With [`ImportMode::Replace`] the store is still cleared, as long as the dump itself is
+    /// valid JSON.
+    pub fn import_json_str_lenient(
+        &mut self,
+        json: &str,
+        mode: ImportMode,
+    ) -> KvResult<Vec<(String, KvError)>> {
+        let json: serde_json::Value = serde_json::from_str(json)
+            .map_err(|e| KvError::Other(format!("serde error parsing json: {e}")))?;
+        let (entries, skipped) = Self::json_entries_lenient(&json, &*self.key_codec)?;
+        if mode == ImportMode::Replace {
+            self.clear()?;
+        }
+        self.set_batch(entries)?;
+        Ok(skipped)
+    }
+
+    /// Dump the entire database to a compact JSON string.
+    /// See [`from_json_string`] for restoring, and [`Self::dump_json_pretty`] for a readable form.
+    pub fn dump_json(&self) -> KvResult<String> {
         let json = self.to_serde_json()?;
         Ok(json.to_string())
     }
 
+    /// Dump the entire database to an indented, human-readable JSON string.
+    ///
+    /// The content is the same as [`Self::dump_json`], so it restores the same way.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a",), 1i64.into()).unwrap();
+    /// let pretty = kv.dump_json_pretty().unwrap();
+    /// assert!(pretty.starts_with("{\n  "));
+    /// ```
+    pub fn dump_json_pretty(&self) -> KvResult<String> {
+        let json = self.to_serde_json()?;
+        serde_json::to_string_pretty(&json)
+            .map_err(|e| KvError::Other(format!("serde error writing json: {e}")))
+    }
+
+    /// Dump only the entries under `prefix` to a JSON string.
+    ///
+    /// Keys are written in full (prefix included) in the same format as [`Self::dump_json`],
+    /// so the result can be restored with [`Self::from_json_string`] or merged back into a
+    /// store with [`Self::import_json_str`].
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("users", 1u64), "alice".into()).unwrap();
+    /// kv.set(&("posts", 1u64), "hello".into()).unwrap();
+    /// let json = kv.dump_json_prefix(&("users",)).unwrap();
+    /// assert_eq!(json, r#"{"\"users\":1u":"alice"}"#);
+    /// ```
+    pub fn dump_json_prefix(&self, prefix: &dyn IntoKey) -> KvResult<String> {
+        let json = self.entries_to_json(self.list().prefix(prefix).entries()?)?;
+        Ok(json.to_string())
+    }
+
     /// Restore a `Kv` from a JSON string previously written by [`dump_json`].
     ///
     /// Example:
@@ -268,4 +1662,169 @@ impl Kv {
             .map_err(|e| KvError::Other(format!("serde error parsing json: {e}")))?;
         Self::from_serde_json(backend, serde_json::Value::Object(json))
     }
+
+    /// Stream the entire database to `w` as newline-delimited JSON.
+    ///
+    /// Each line is an object of the form `{"key": "<display string>", "value": <json>}`,
+    /// using the same key and value encoding as [`Self::dump_json`]. Entries are written as
+    /// the backend is scanned, so memory use stays flat no matter how large the store is.
+    /// See [`Self::load_ndjson`] for restoring.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("a", 1u64), "x".into()).unwrap();
+    /// let mut out = Vec::new();
+    /// kv.dump_ndjson(&mut out).unwrap();
+    /// assert_eq!(String::from_utf8(out).unwrap(), "{\"key\":\"\\\"a\\\":1u\",\"value\":\"x\"}\n");
+    /// ```
+    pub fn dump_ndjson<W: Write>(&self, mut w: W) -> KvResult<()> {
+        let backend = self.backend.read();
+        for item in backend.get_range_iter(None, None)? {
+            let (key, bytes) = item?;
+            let display = to_display_string_with(&key, &*self.key_codec)?;
+            let value = decode_stored(&*self.codec, &bytes)?;
+            let line =
+                serde_json::json!({ "key": display, "value": value.to_json(self.json_binary) });
+            writeln!(w, "{line}")
+                .map_err(|e| KvError::Other(format!("error writing ndjson: {e}")))?;
+        }
+        w.flush()
+            .map_err(|e| KvError::Other(format!("error writing ndjson: {e}")))
+    }
+
+    /// Load newline-delimited JSON written by [`Self::dump_ndjson`] into this store,
+    /// one line at a time. Existing keys are overwritten; blank lines are skipped.
+    pub fn load_ndjson<R: BufRead>(&mut self, r: R) -> KvResult<()> {
+        for (n, line) in r.lines().enumerate() {
+            let line = line.map_err(|e| KvError::Other(format!("error reading ndjson: {e}")))?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let mut entry: serde_json::Value = serde_json::from_str(&line).map_err(|e| {
+                KvError::Other(format!("serde error parsing ndjson line {}: {e}", n + 1))
+            })?;
+            let Some(display) = entry.get("key").and_then(|k| k.as_str()) else {
+                return Err(KvError::Other(format!(
+                    "ndjson line {} has no string \"key\" field",
+                    n + 1
+                )));
+            };
+            let key = parse_display_string_with(display, &*self.key_codec).ok_or(
+                KvError::KeyDecodeError(format!("Could not decode JSON key {display} to KvKey.")),
+            )?;
+            let value = KvValue::from(&entry["value"].take());
+            self.set(&key, value)?;
+        }
+        Ok(())
+    }
+
+    /// Dump the entire database as MessagePack.
+    ///
+    /// The dump has exactly the same shape as [`Self::to_serde_json`] (display-string keys,
+    /// tagged binary values), just in a compact binary encoding, so the two formats can be
+    /// converted into each other losslessly. See [`Self::from_msgpack`] for restoring.
+    #[cfg(feature = "msgpack")]
+    pub fn dump_msgpack(&self) -> KvResult<Vec<u8>> {
+        let json = self.to_serde_json()?;
+        rmp_serde::to_vec(&json).map_err(|e| KvError::Other(format!("error encoding msgpack: {e}")))
+    }
+
+    /// Restore a `Kv` from bytes previously written by [`Self::dump_msgpack`].
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("blob", 1u64), KvValue::Binary(vec![1, 2, 3])).unwrap();
+    /// let bytes = kv.dump_msgpack().unwrap();
+    /// let loaded = Kv::from_msgpack(Box::new(MemoryBackend::new()), &bytes).unwrap();
+    /// assert_eq!(loaded.get(&("blob", 1u64)).unwrap(), Some(KvValue::Binary(vec![1, 2, 3])));
+    /// ```
+    #[cfg(feature = "msgpack")]
+    pub fn from_msgpack(backend: Box<dyn KvBackend>, bytes: &[u8]) -> KvResult<Self> {
+        let json: serde_json::Value = rmp_serde::from_slice(bytes)
+            .map_err(|e| KvError::Other(format!("error parsing msgpack: {e}")))?;
+        Self::from_serde_json(backend, json)
+    }
+
+    /// Dump the entire database as CSV, one row per entry.
+    ///
+    /// Every value must be a [`KvValue::Object`]. The header is inferred as follows:
+    /// - The first column is always `key`, holding the display-string form of each key.
+    /// - The remaining columns are the union of all object field names, in sorted order.
+    /// - A field missing from an object is written as an empty cell.
+    ///
+    /// Cells hold plain strings as-is. Every other value (numbers, bools, null, arrays,
+    /// nested objects, binary blobs), and any string that would otherwise read back as one of
+    /// those or as empty, is JSON-encoded with the same tagging as [`Self::dump_json`].
+    /// See [`Self::from_csv_string`] for restoring.
+    ///
+    /// # Errors
+    /// Returns an error if any value is not an object.
+    pub fn dump_csv(&self) -> KvResult<String> {
+        csv::write_csv(&self.entries()?, &*self.key_codec)
+    }
+
+    /// Restore a `Kv` from CSV previously written by [`Self::dump_csv`].
+    ///
+    /// Empty cells are treated as absent fields, cells that parse as JSON are decoded as such,
+    /// and anything else is read as a string.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// let row = KvValue::Object([("name".to_string(), "alice".into())].into_iter().collect());
+    /// kv.set(&("users", 1u64), row.clone()).unwrap();
+    /// let csv = kv.dump_csv().unwrap();
+    /// assert_eq!(csv, "key,name\n\"\"\"users\"\":1u\",alice\n");
+    ///
+    /// let loaded = Kv::from_csv_string(Box::new(MemoryBackend::new()), csv).unwrap();
+    /// assert_eq!(loaded.get(&("users", 1u64)).unwrap(), Some(row));
+    /// ```
+    pub fn from_csv_string(backend: Box<dyn KvBackend>, csv: String) -> KvResult<Self> {
+        let mut kv = Self::new(backend);
+        kv.set_batch(csv::read_csv(&csv)?)?;
+        Ok(kv)
+    }
+
+    /// Dump the entire database into the directory `path`, one file per entry, for backups
+    /// that diff well and can live in git.
+    ///
+    /// Each file is named `key-`, the lowercase hex of its encoded key, then `.json` (the
+    /// empty key is `key-.json`), so any key bytes map to a unique, portable name (safe on
+    /// case-insensitive file systems too). Names that would pass 200 hex digits are split
+    /// into nested `key-` directories. Each file holds the value as indented JSON, in the
+    /// same format as [`Self::dump_json`].
+    ///
+    /// The directory is created if needed. Entry files left over from an earlier dump whose
+    /// keys no longer exist are removed; anything not named like an entry is left alone.
+    /// See [`Self::from_dir`] for restoring.
+    ///
+    /// Example:
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&("theme",), "dark".into()).unwrap();
+    /// let dir = tempfile::tempdir().unwrap();
+    /// kv.dump_dir(dir.path()).unwrap();
+    ///
+    /// let loaded = Kv::from_dir(Box::new(MemoryBackend::new()), dir.path()).unwrap();
+    /// assert_eq!(loaded.get(&("theme",)).unwrap(), Some("dark".into()));
+    /// ```
+    pub fn dump_dir(&self, path: &Path) -> KvResult<()> {
+        dir_dump::write_dir(path, &self.entries()?, self.json_binary)
+    }
+
+    /// Restore a `Kv` from a directory written by [`Self::dump_dir`].
+    ///
+    /// Files and subdirectories not named like entries, such as `.git` or `notes.json`,
+    /// are ignored.
+    pub fn from_dir(backend: Box<dyn KvBackend>, path: &Path) -> KvResult<Self> {
+        let mut kv = Self::new(backend);
+        kv.set_batch(dir_dump::read_dir(path)?)?;
+        Ok(kv)
+    }
 }
@@ -1,11 +1,22 @@
-use std::{cell::RefCell, rc::Rc};
+use std::collections::VecDeque;
+use std::sync::Arc;
 
-use crate::{IntoKey, KvBackend, KvKey, KvResult, KvValue};
+use crate::backends::{PAGE_SIZE, SharedBackend};
+use crate::display::to_display_string;
+use crate::trace::OpTimer;
+use crate::value_codec::decode_stored;
+use crate::{IntoKey, KeyCodec, KvError, KvKey, KvResult, KvValue, ValueCodec};
 
 /// Builder for flexible queries over a key/value backend.
 ///
 /// Use prefix, start, and end keys to define your query range, then call [`KvListBuilder::entries`].
 ///
+/// Selectors combine by intersection: with a prefix, `start` and `end` narrow the scan to a
+/// sub-range of the keys under it. `start` must lie within the prefix and `end` after its
+/// first key, though `end` may reach past the prefix, in which case it is clamped. A `start`
+/// after `end` is also rejected; both mistakes fail with [`KvError::InvalidSelector`]
+/// instead of quietly returning nothing.
+///
 /// # Examples
 ///
 /// ```rust
@@ -17,83 +28,422 @@ use crate::{IntoKey, KvBackend, KvKey, KvResult, KvValue};
 ///
 /// // Range scan from (99,2) up to (99,5)
 /// let result = kv.list().start(&(99u64, 2i64)).end(&(99u64, 5i64)).entries().unwrap();
+///
+/// // Within prefix (99, _), from (99,2) up to (99,5)
+/// let sub = kv.list().prefix(&(99u64,)).start(&(99u64, 2i64)).end(&(99u64, 5i64)).entries().unwrap();
+///
+/// // Fetch the next page of 10 entries after a previously seen key
+/// let page = kv.list().after(&(99u64, 2i64)).limit(10).entries().unwrap();
+///
+/// // Clone a configured query to run variants of it
+/// let mut users = kv.list();
+/// users.prefix(&("users",));
+/// let first_five = users.clone().limit(5).entries().unwrap();
+/// let all = users.entries().unwrap();
 /// ```
+#[derive(Clone)]
 pub struct KvListBuilder {
-    pub(crate) backend: Rc<RefCell<Box<dyn KvBackend>>>,
+    pub(crate) backend: SharedBackend,
+    pub(crate) codec: Arc<dyn ValueCodec>,
+    pub(crate) key_codec: Arc<dyn KeyCodec + Send + Sync>,
     pub(crate) prefix: Option<KvKey>,
     pub(crate) start: Option<KvKey>,
     pub(crate) end: Option<KvKey>,
+    pub(crate) after: Option<KvKey>,
+    pub(crate) limit: Option<usize>,
+    pub(crate) offset: usize,
+    pub(crate) namespace: Option<KvKey>,
 }
 
 impl KvListBuilder {
-    pub(crate) fn new(backend: Rc<RefCell<Box<dyn KvBackend>>>) -> Self {
+    pub(crate) fn new(
+        backend: SharedBackend,
+        codec: Arc<dyn ValueCodec>,
+        key_codec: Arc<dyn KeyCodec + Send + Sync>,
+    ) -> Self {
         Self {
             backend,
+            codec,
+            key_codec,
             prefix: None,
             start: None,
             end: None,
+            after: None,
+            limit: None,
+            offset: 0,
+            namespace: None,
+        }
+    }
+
+    /// Build a query confined to the keys under `namespace`.
+    ///
+    /// Keys passed to the selector methods are relative to the namespace, and the namespace
+    /// is stripped from returned keys.
+    pub(crate) fn scoped(
+        backend: SharedBackend,
+        codec: Arc<dyn ValueCodec>,
+        key_codec: Arc<dyn KeyCodec + Send + Sync>,
+        namespace: KvKey,
+    ) -> Self {
+        let mut builder = Self::new(backend, codec, key_codec);
+        builder.namespace = Some(namespace);
+        builder
+    }
+
+    fn scope(&self, key: &dyn IntoKey) -> KvKey {
+        match &self.namespace {
+            Some(namespace) => namespace.join_with(key, &*self.key_codec),
+            None => key.to_key_with(&*self.key_codec),
+        }
+    }
+
+    fn unscope(&self, key: KvKey) -> KvKey {
+        match &self.namespace {
+            Some(namespace) => KvKey(key.0[namespace.0.len()..].to_vec()),
+            None => key,
         }
     }
 
     /// Restrict results to the given key prefix.
+    ///
+    /// Under [`crate::Kv::with_string_collation`], a string as the prefix's last segment
+    /// matches every string with the same collation key, e.g. any casing of it.
     pub fn prefix(&mut self, prefix: &dyn IntoKey) -> &mut Self {
-        self.prefix = Some(prefix.to_key());
+        let mut prefix = self.scope(prefix);
+        // Only the caller's segments may be widened, never the namespace's.
+        let scoped = self.namespace.as_ref().map_or(0, |ns| ns.0.len());
+        prefix
+            .0
+            .truncate(scoped + self.key_codec.prefix_len(&prefix.0[scoped..]));
+        self.prefix = Some(prefix);
         self
     }
 
     /// Start listing at this key (inclusive).
     pub fn start(&mut self, start: &dyn IntoKey) -> &mut Self {
-        self.start = Some(start.to_key());
+        self.start = Some(self.scope(start));
         self
     }
 
     /// End listing at this key (exclusive).
     pub fn end(&mut self, end: &dyn IntoKey) -> &mut Self {
-        self.end = Some(end.to_key());
+        self.end = Some(self.scope(end));
+        self
+    }
+
+    /// End listing at this key (inclusive).
+    ///
+    /// The exclusive bound becomes `end.successor()`, so besides `end` itself, any key that
+    /// has `end` as a prefix is included too: `end_inclusive(&(1u64,))` takes in `(1, 5)`.
+    /// Combined with [`Self::prefix`] this lists from the prefix through `end` and its
+    /// children; combined with [`Self::start`] it gives a closed `[start, end]` range.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// for i in 0..5i64 {
+    ///     kv.set(&(1u64, i), KvValue::I64(i)).unwrap();
+    /// }
+    /// let closed = kv.list().start(&(1u64, 1i64)).end_inclusive(&(1u64, 3i64)).entries().unwrap();
+    /// assert_eq!(closed.len(), 3);
+    /// ```
+    pub fn end_inclusive(&mut self, end: &dyn IntoKey) -> &mut Self {
+        // A key of all 0xFF bytes has no successor; everything after it qualifies.
+        self.end = self.scope(end).successor();
         self
     }
 
+    /// Only list keys strictly greater than this one.
+    ///
+    /// This is meant for cursor-based pagination: pass the last key of the previous page
+    /// here to fetch the next one. It combines with the other selectors.
+    ///
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// for i in 0..25i64 {
+    ///     kv.set(&(1u64, i), KvValue::I64(i)).unwrap();
+    /// }
+    /// let first_page = kv.list().prefix(&(1u64,)).limit(10).entries().unwrap();
+    /// let cursor = first_page.last().unwrap().0.clone();
+    /// let second_page = kv.list().prefix(&(1u64,)).after(&cursor).limit(10).entries().unwrap();
+    /// assert_eq!(second_page[0].1, KvValue::I64(10));
+    /// ```
+    pub fn after(&mut self, key: &dyn IntoKey) -> &mut Self {
+        self.after = Some(self.scope(key));
+        self
+    }
+
+    /// Return at most `n` entries.
+    pub fn limit(&mut self, n: usize) -> &mut Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Skip the first `n` matching entries.
+    ///
+    /// Combined with [`Self::limit`] this gives classic `[offset, offset + limit)` windows.
+    /// Skipped entries are never decoded. For large result sets, prefer cursor-based
+    /// paging with [`Self::after`], since backends may still have to walk the skipped rows.
+    pub fn offset(&mut self, n: usize) -> &mut Self {
+        self.offset = n;
+        self
+    }
+
+    /// Resolve the configured selectors into a `[start, end)` range.
+    fn bounds(&self) -> KvResult<(Option<KvKey>, Option<KvKey>)> {
+        let (start, end) = self.selector_bounds()?;
+        let start = match (start, &self.after) {
+            (Some(start), Some(after)) => Some(start.max(after.immediate_successor())),
+            (None, Some(after)) => Some(after.immediate_successor()),
+            (start, None) => start,
+        };
+        let Some(namespace) = &self.namespace else {
+            return Ok((start, end));
+        };
+        // Clamp the range to the namespace.
+        Ok((
+            max_bound(start, Some(namespace.clone())),
+            min_bound(end, namespace.successor()),
+        ))
+    }
+
+    /// Intersect the prefix range with the `start`/`end` bounds, rejecting combinations
+    /// that can't match anything.
+    fn selector_bounds(&self) -> KvResult<(Option<KvKey>, Option<KvKey>)> {
+        let invalid = |msg: String| Err(KvError::InvalidSelector(msg));
+        if let (Some(start), Some(end)) = (&self.start, &self.end)
+            && start > end
+        {
+            return invalid(format!(
+                "start {} is after end {}",
+                describe(start),
+                describe(end)
+            ));
+        }
+        if let Some(prefix) = &self.prefix {
+            if let Some(start) = &self.start
+                && !start.starts_with(prefix)
+            {
+                return invalid(format!(
+                    "start {} is outside prefix {}",
+                    describe(start),
+                    describe(prefix)
+                ));
+            }
+            if let Some(end) = &self.end
+                && end <= prefix
+            {
+                return invalid(format!(
+                    "end {} is before prefix {}",
+                    describe(end),
+                    describe(prefix)
+                ));
+            }
+        }
+        let (prefix_start, prefix_end) = match &self.prefix {
+            Some(prefix) => (Some(prefix.clone()), prefix.successor()),
+            None => (None, None),
+        };
+        Ok((
+            max_bound(prefix_start, self.start.clone()),
+            min_bound(prefix_end, self.end.clone()),
+        ))
+    }
+
     /// Run the current query and return key-value pairs.
-    /// Returns all results matching the filter/prefix/bounds.
+    /// Returns all results matching the filter/prefix/bounds, in ascending key order.
     ///
     /// # Errors
     /// Returns an error if the combination of selectors is invalid, or if decoding fails.
     pub fn entries(&self) -> KvResult<Vec<(KvKey, KvValue)>> {
-        use crate::KvError;
-
-        // Disallow all three present.
-        if self.prefix.is_some() && self.start.is_some() && self.end.is_some() {
-            return Err(KvError::InvalidSelector);
-        }
-
-        let (range_start, range_end) =
-            match (self.prefix.clone(), self.start.clone(), self.end.clone()) {
-                (Some(prefix), None, None) => {
-                    let end = prefix.successor();
-                    (Some(prefix), end)
-                }
-                (None, Some(start), None) => (Some(start), None),
-                (None, None, Some(end)) => (None, Some(end)),
-                (Some(_prefix), Some(start), None) => (Some(start), None), // start wins
-                (Some(prefix), None, Some(end)) => (Some(prefix), Some(end)),
-                (None, Some(start), Some(end)) => (Some(start), Some(end)),
-                (None, None, None) => (None, None),
-                _ => return Err(KvError::InvalidSelector),
-            };
+        let (range_start, range_end) = self.bounds()?;
 
         // Fetch the range (unbounded if end is None)
-        let items = self
-            .backend
-            .try_borrow()?
-            .get_range(range_start, range_end)?;
+        let backend = self.backend.read();
+        let timer = OpTimer::start_range("get_range");
+        let items = if self.limit.is_some() || self.offset > 0 {
+            backend.get_range_page(range_start, range_end, self.offset, self.limit)?
+        } else {
+            backend.get_range(range_start, range_end)?
+        };
+        timer.finish_range(items.iter().map(|(_, v)| v.len()));
 
         let mut result = Vec::with_capacity(items.len());
         for (k, v) in items {
-            let (decoded, _consumed) =
-                bincode::decode_from_slice::<KvValue, _>(&v, bincode::config::standard())
-                    .map_err(KvError::ValDecodeError)?;
-            result.push((k, decoded));
+            // The range already excludes keys outside the prefix; this guards against a
+            // backend or encoding edge case that lets one through.
+            if let Some(prefix) = &self.prefix
+                && !k.starts_with(prefix)
+            {
+                continue;
+            }
+            result.push((self.unscope(k), decode_stored(&*self.codec, &v)?));
         }
         Ok(result)
     }
+
+    /// Run the current query lazily, decoding one entry at a time.
+    ///
+    /// Unlike [`KvListBuilder::entries`], results are never buffered all at once, so this is
+    /// suitable for scanning very large ranges.
+    ///
+    /// # Example
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// for i in 0..10i64 {
+    ///     kv.set(&(1u64, i), i.into()).unwrap();
+    /// }
+    /// for item in kv.list().prefix(&(1u64,)).iter().unwrap() {
+    ///     let (key, value) = item.unwrap();
+    /// }
+    /// ```
+    ///
+    /// # Errors
+    /// Returns an error if the combination of selectors is invalid. Backend and decoding
+    /// errors are yielded by the iterator.
+    pub fn iter(&self) -> KvResult<KvIter> {
+        let (start, end) = self.bounds()?;
+        let mut iter = KvIter::new(self.backend.clone(), self.codec.clone(), start, end);
+        iter.remaining = self.limit;
+        iter.skip = self.offset;
+        iter.strip = self.namespace.as_ref().map_or(0, |ns| ns.0.len());
+        iter.prefix = self.prefix.clone();
+        Ok(iter)
+    }
+
+    /// Run the query and convert each entry with `f`, collecting the results.
+    ///
+    /// Entries are decoded and converted one at a time; the first error, whether from the
+    /// backend, value decoding, or `f` itself, stops the scan and is returned.
+    ///
+    /// # Example
+    /// ```rust
+    /// use stupid_simple_kv::{Kv, MemoryBackend, KvValue};
+    /// let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+    /// kv.set(&(1u64, 7i64), KvValue::I64(49)).unwrap();
+    /// let squares = kv
+    ///     .list()
+    ///     .prefix(&(1u64,))
+    ///     .map(|(key, value)| {
+    ///         let (_, n): (u64, i64) = key.try_into()?;
+    ///         Ok((n, i64::try_from(value)?))
+    ///     })
+    ///     .unwrap();
+    /// assert_eq!(squares, vec![(7, 49)]);
+    /// ```
+    pub fn map<T>(&self, mut f: impl FnMut((KvKey, KvValue)) -> KvResult<T>) -> KvResult<Vec<T>> {
+        self.iter()?.map(|item| item.and_then(&mut f)).collect()
+    }
+}
+
+/// A key's display string for error messages, falling back to its raw bytes.
+fn describe(key: &KvKey) -> String {
+    to_display_string(&key.0).unwrap_or_else(|| format!("{key:?}"))
+}
+
+/// The tighter of two lower bounds, where `None` is unbounded.
+fn max_bound(a: Option<KvKey>, b: Option<KvKey>) -> Option<KvKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.max(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// The tighter of two upper bounds, where `None` is unbounded.
+fn min_bound(a: Option<KvKey>, b: Option<KvKey>) -> Option<KvKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(a.min(b)),
+        (a, b) => a.or(b),
+    }
+}
+
+/// Lazy iterator over decoded entries, created by [`KvListBuilder::iter`].
+///
+/// The backend is only locked while a page of results is being fetched, so the store
+/// can be written to in between calls to `next`.
+pub struct KvIter {
+    backend: SharedBackend,
+    codec: Arc<dyn ValueCodec>,
+    next_start: Option<KvKey>,
+    end: Option<KvKey>,
+    page: VecDeque<(KvKey, Vec<u8>)>,
+    remaining: Option<usize>,
+    skip: usize,
+    strip: usize,
+    prefix: Option<KvKey>,
+    done: bool,
+}
+
+impl KvIter {
+    pub(crate) fn new(
+        backend: SharedBackend,
+        codec: Arc<dyn ValueCodec>,
+        start: Option<KvKey>,
+        end: Option<KvKey>,
+    ) -> Self {
+        Self {
+            backend,
+            codec,
+            next_start: start,
+            end,
+            page: VecDeque::new(),
+            remaining: None,
+            skip: 0,
+            strip: 0,
+            prefix: None,
+            done: false,
+        }
+    }
+
+    fn fill_page(&mut self) -> KvResult<()> {
+        let backend = self.backend.read();
+        let timer = OpTimer::start_range("iter");
+        let items =
+            backend.get_range_page(self.next_start.take(), self.end.clone(), 0, Some(PAGE_SIZE))?;
+        timer.finish_range(items.iter().map(|(_, v)| v.len()));
+        self.page.extend(items);
+        if self.page.len() < PAGE_SIZE {
+            self.done = true;
+        }
+        self.next_start = self.page.back().map(|(k, _)| k.immediate_successor());
+        Ok(())
+    }
+}
+
+impl Iterator for KvIter {
+    type Item = KvResult<(KvKey, KvValue)>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == Some(0) {
+            return None;
+        }
+        let (mut key, bytes) = loop {
+            if self.page.is_empty()
+                && !self.done
+                && let Err(e) = self.fill_page()
+            {
+                self.done = true;
+                return Some(Err(e));
+            }
+            let pair = self.page.pop_front()?;
+            if let Some(prefix) = &self.prefix
+                && !pair.0.starts_with(prefix)
+            {
+                continue;
+            }
+            if self.skip == 0 {
+                break pair;
+            }
+            self.skip -= 1;
+        };
+        if let Some(remaining) = &mut self.remaining {
+            *remaining -= 1;
+        }
+        if self.strip > 0 {
+            key.0.drain(..self.strip);
+        }
+        Some(decode_stored(&*self.codec, &bytes).map(|value| (key, value)))
+    }
 }
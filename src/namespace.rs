@@ -0,0 +1,67 @@
+use crate::{IntoKey, Kv, KvKey, KvListBuilder, KvResult, KvValue};
+
+/// A view of a [`Kv`] confined to the keys under a fixed prefix.
+///
+/// Every key passed to a `Namespace` is automatically prefixed, and the prefix is stripped
+/// from keys it returns, so code working inside a namespace never sees (or forgets) it.
+/// Namespaces share the backend of the [`Kv`] they were created from.
+///
+/// Create one with [`Kv::namespace`].
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{Kv, MemoryBackend, KvValue, IntoKey};
+/// let kv = Kv::new(Box::new(MemoryBackend::new()));
+/// let mut users = kv.namespace(&("users",));
+/// users.set(&(1u64,), "alice".into()).unwrap();
+///
+/// assert_eq!(kv.get(&("users", 1u64)).unwrap(), Some("alice".into()));
+/// let (key, _) = users.list().entries().unwrap().remove(0);
+/// assert_eq!(key, (1u64,).to_key());
+/// ```
+pub struct Namespace {
+    kv: Kv,
+    prefix: KvKey,
+}
+
+impl Namespace {
+    pub(crate) fn new(kv: Kv, prefix: KvKey) -> Self {
+        Self { kv, prefix }
+    }
+
+    /// The prefix this namespace prepends to every key.
+    pub fn prefix(&self) -> &KvKey {
+        &self.prefix
+    }
+
+    fn key(&self, key: &dyn IntoKey) -> KvKey {
+        self.prefix.join_with(key, self.kv.key_codec())
+    }
+
+    /// Retrieve the value for a key within the namespace.
+    pub fn get(&self, key: impl IntoKey) -> KvResult<Option<KvValue>> {
+        self.kv.get(self.key(&key))
+    }
+
+    /// Set the value for a key within the namespace.
+    pub fn set(&mut self, key: impl IntoKey, value: KvValue) -> KvResult<()> {
+        self.kv.set(self.key(&key), value)
+    }
+
+    /// Delete a key within the namespace. Returns the (unprefixed) key and previous value if present.
+    pub fn delete(&mut self, key: impl IntoKey) -> KvResult<Option<(KvKey, KvValue)>> {
+        let deleted = self.kv.delete(self.key(&key))?;
+        Ok(deleted.map(|(_, value)| (key.to_key_with(self.kv.key_codec()), value)))
+    }
+
+    /// Build a query over the namespace. Selector keys are relative to the namespace,
+    /// and returned keys have the namespace prefix stripped.
+    pub fn list(&self) -> KvListBuilder {
+        KvListBuilder::scoped(
+            self.kv.backend.clone(),
+            self.kv.codec.clone(),
+            self.kv.key_codec.clone(),
+            self.prefix.clone(),
+        )
+    }
+}
@@ -0,0 +1,30 @@
+use std::collections::BTreeMap;
+
+use crate::{KvKey, KvValue};
+
+/// A size and shape summary of a store, as returned by [`crate::Kv::stats`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct KvStats {
+    /// Number of stored entries.
+    pub entries: usize,
+    /// Total bytes of stored values, as handed to the backend (so including encoding
+    /// overhead, but not any per-row overhead of the backend itself).
+    pub value_bytes: usize,
+    /// Length in bytes of the shortest encoded key, or `None` for an empty store.
+    pub min_key_len: Option<usize>,
+    /// Length in bytes of the longest encoded key, or `None` for an empty store.
+    pub max_key_len: Option<usize>,
+    /// Number of entries of each value type, keyed by [`KvValue::type_name`].
+    pub value_types: BTreeMap<&'static str, usize>,
+}
+
+impl KvStats {
+    pub(crate) fn record(&mut self, key: &KvKey, stored_len: usize, value: &KvValue) {
+        let key_len = key.as_bytes().len();
+        self.entries += 1;
+        self.value_bytes += stored_len;
+        self.min_key_len = Some(self.min_key_len.map_or(key_len, |min| min.min(key_len)));
+        self.max_key_len = Some(self.max_key_len.map_or(key_len, |max| max.max(key_len)));
+        *self.value_types.entry(value.type_name()).or_default() += 1;
+    }
+}
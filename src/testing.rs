@@ -0,0 +1,282 @@
+//! Helpers for testing code built on this crate, enabled by the `test-util` feature.
+//!
+//! Backend authors can check a [`KvBackend`] implementation against the semantics the rest
+//! of the crate relies on with [`run_backend_conformance`]:
+//!
+//! ```rust
+//! use stupid_simple_kv::{KvBackend, MemoryBackend};
+//!
+//! stupid_simple_kv::testing::run_backend_conformance(|| {
+//!     Box::new(MemoryBackend::new()) as Box<dyn KvBackend>
+//! });
+//! ```
+
+use crate::{IntoKey, KvBackend, KvKey, KvResult};
+
+/// Run every conformance check against fresh backends from `make`, panicking with a
+/// description of the first violated rule.
+///
+/// Each check gets its own backend, so `make` must return an empty one every time. The
+/// checks cover point reads and writes, deletes, sorted range reads (including the empty
+/// key), exclusive range ends, empty ranges, `clear`, and prefix scans next to keys that
+/// share leading bytes.
+pub fn run_backend_conformance(make: impl Fn() -> Box<dyn KvBackend>) {
+    let checks: [(&str, Check); 6] = [
+        ("set/get/delete", check_point_ops),
+        ("range ordering", check_ordering),
+        ("exclusive end", check_exclusive_end),
+        ("empty ranges", check_empty_ranges),
+        ("clear", check_clear),
+        ("prefix boundaries", check_prefix_boundaries),
+    ];
+    for (name, check) in checks {
+        let mut backend = make();
+        assert!(
+            backend.is_empty().expect("is_empty failed"),
+            "{name}: `make` must return an empty backend"
+        );
+        if let Err(e) = check(&mut *backend) {
+            panic!("{name}: backend returned an error: {e}");
+        }
+    }
+}
+
+type Check = fn(&mut dyn KvBackend) -> KvResult<()>;
+
+fn keys(pairs: Vec<(KvKey, Vec<u8>)>) -> Vec<KvKey> {
+    pairs.into_iter().map(|(key, _)| key).collect()
+}
+
+fn check_point_ops(backend: &mut dyn KvBackend) -> KvResult<()> {
+    let key = ("point", 1u64).to_key();
+    assert_eq!(backend.get(&key)?, None, "get of a missing key");
+    assert!(
+        !backend.contains_key(&key)?,
+        "contains_key of a missing key"
+    );
+
+    backend.set(key.clone(), Some(b"one".to_vec()))?;
+    assert_eq!(backend.get(&key)?, Some(b"one".to_vec()), "get after set");
+    assert!(backend.contains_key(&key)?, "contains_key after set");
+    backend.set(key.clone(), Some(b"two".to_vec()))?;
+    assert_eq!(backend.get(&key)?, Some(b"two".to_vec()), "set overwrites");
+    backend.set(key.clone(), Some(Vec::new()))?;
+    assert_eq!(
+        backend.get(&key)?,
+        Some(Vec::new()),
+        "empty values are stored"
+    );
+
+    // A key and its extensions are distinct.
+    assert_eq!(backend.get(&("point",).to_key())?, None, "get of a prefix");
+    assert_eq!(backend.len()?, 1, "len after overwrites");
+
+    assert!(backend.delete(&key)?, "delete reports a present key");
+    assert!(!backend.delete(&key)?, "delete reports a missing key");
+    assert_eq!(backend.get(&key)?, None, "get after delete");
+
+    backend.set(key.clone(), Some(b"three".to_vec()))?;
+    assert_eq!(
+        backend.take(&key)?,
+        Some(b"three".to_vec()),
+        "take returns the value"
+    );
+    assert_eq!(backend.take(&key)?, None, "take of a missing key");
+    backend.set(key.clone(), Some(b"four".to_vec()))?;
+    backend.set(key.clone(), None)?;
+    assert_eq!(backend.get(&key)?, None, "set to None deletes");
+    assert!(backend.is_empty()?, "is_empty after deleting everything");
+
+    backend.set_batch(vec![
+        (("batch", 1u64).to_key(), Some(b"a".to_vec())),
+        (("batch", 2u64).to_key(), Some(b"b".to_vec())),
+        (("batch", 1u64).to_key(), None),
+    ])?;
+    assert_eq!(
+        keys(backend.get_range(None, None)?),
+        [("batch", 2u64).to_key()],
+        "set_batch applies writes in order"
+    );
+    Ok(())
+}
+
+fn check_ordering(backend: &mut dyn KvBackend) -> KvResult<()> {
+    // Inserted out of order, with the empty key, keys of different lengths and shared
+    // prefixes.
+    let mut expected: Vec<KvKey> = [
+        vec![0x02],
+        vec![0x01, 0xFF],
+        vec![],
+        vec![0xFF],
+        vec![0x01],
+        vec![0x01, 0x00, 0x05],
+        vec![0x80, 0x00],
+        vec![0x01, 0x00],
+    ]
+    .into_iter()
+    .map(KvKey::from_bytes)
+    .chain((0..20i64).rev().map(|i| ("n", i * 7 - 50).to_key()))
+    .collect();
+    for (i, key) in expected.iter().enumerate() {
+        backend.set(key.clone(), Some(vec![i as u8]))?;
+    }
+    expected.sort();
+
+    assert_eq!(
+        keys(backend.get_range(None, None)?),
+        expected,
+        "get_range order"
+    );
+    let iterated = backend
+        .get_range_iter(None, None)?
+        .map(|pair| pair.map(|(key, _)| key))
+        .collect::<KvResult<Vec<_>>>()?;
+    assert_eq!(iterated, expected, "get_range_iter order");
+    assert_eq!(
+        keys(backend.get_range_page(None, None, 3, Some(5))?),
+        expected[3..8],
+        "get_range_page window"
+    );
+    assert_eq!(
+        keys(backend.get_range_page(None, None, 25, None)?),
+        expected[25..],
+        "get_range_page without a limit"
+    );
+    assert_eq!(
+        keys(backend.get_range(Some(expected[2].clone()), Some(expected[10].clone()))?),
+        expected[2..10],
+        "bounded get_range order"
+    );
+    let first = backend.first(None, None)?.map(|(key, _)| key);
+    let last = backend.last(None, None)?.map(|(key, _)| key);
+    assert_eq!(first.as_ref(), expected.first(), "first");
+    assert_eq!(last.as_ref(), expected.last(), "last");
+    assert_eq!(backend.len()?, expected.len(), "len");
+    Ok(())
+}
+
+fn check_exclusive_end(backend: &mut dyn KvBackend) -> KvResult<()> {
+    let all: Vec<KvKey> = (0..10u64).map(|i| ("e", i).to_key()).collect();
+    for key in &all {
+        backend.set(key.clone(), Some(Vec::new()))?;
+    }
+    let range = |start: usize, end: usize| (Some(all[start].clone()), Some(all[end].clone()));
+
+    let (start, end) = range(2, 5);
+    assert_eq!(
+        keys(backend.get_range(start.clone(), end.clone())?),
+        all[2..5],
+        "start is inclusive and end exclusive"
+    );
+    assert_eq!(
+        backend
+            .last(start.clone(), end.clone())?
+            .map(|(key, _)| key),
+        Some(all[4].clone()),
+        "last excludes the end key"
+    );
+    assert_eq!(
+        backend
+            .get_range_iter(start, end)?
+            .collect::<KvResult<Vec<_>>>()?
+            .len(),
+        3,
+        "get_range_iter honours the end"
+    );
+    assert_eq!(
+        keys(backend.get_range(None, Some(all[3].clone()))?),
+        all[..3],
+        "open start"
+    );
+    assert_eq!(
+        keys(backend.get_range(Some(all[7].clone()), None)?),
+        all[7..],
+        "open end"
+    );
+    Ok(())
+}
+
+fn check_empty_ranges(backend: &mut dyn KvBackend) -> KvResult<()> {
+    assert!(backend.get_range(None, None)?.is_empty(), "empty backend");
+    assert_eq!(
+        backend.first(None, None)?,
+        None,
+        "first of an empty backend"
+    );
+    assert_eq!(backend.last(None, None)?, None, "last of an empty backend");
+    assert_eq!(backend.len()?, 0, "len of an empty backend");
+
+    let (a, b) = (("r", 1u64).to_key(), ("r", 2u64).to_key());
+    backend.set(a.clone(), Some(Vec::new()))?;
+    backend.set(b.clone(), Some(Vec::new()))?;
+    let same = backend.get_range(Some(a.clone()), Some(a.clone()))?;
+    assert!(same.is_empty(), "start == end is empty");
+    let reversed = backend.get_range(Some(b.clone()), Some(a.clone()))?;
+    assert!(reversed.is_empty(), "start > end is empty");
+    let between = backend.get_range(Some(a.immediate_successor()), Some(b))?;
+    assert!(between.is_empty(), "a gap between keys is empty");
+    assert_eq!(
+        backend.first(Some(a.clone()), Some(a))?,
+        None,
+        "first of an empty range"
+    );
+    Ok(())
+}
+
+fn check_clear(backend: &mut dyn KvBackend) -> KvResult<()> {
+    for i in 0..5u64 {
+        backend.set(("c", i).to_key(), Some(Vec::new()))?;
+    }
+    backend.clear()?;
+    assert!(backend.is_empty()?, "is_empty after clear");
+    assert!(
+        backend.get_range(None, None)?.is_empty(),
+        "get_range after clear"
+    );
+    assert_eq!(backend.get(&("c", 0u64).to_key())?, None, "get after clear");
+
+    backend.set(("c", 9u64).to_key(), Some(Vec::new()))?;
+    assert_eq!(backend.len()?, 1, "writes after clear");
+    Ok(())
+}
+
+fn check_prefix_boundaries(backend: &mut dyn KvBackend) -> KvResult<()> {
+    let prefix = ("ab",).to_key();
+    let inside = [
+        prefix.clone(),
+        ("ab", "x").to_key(),
+        ("ab", 0u64).to_key(),
+        ("ab", u64::MAX).to_key(),
+    ];
+    let outside = [
+        ("a",).to_key(),
+        ("abc",).to_key(),
+        ("b",).to_key(),
+        KvKey::from_bytes(prefix.0[..prefix.0.len() - 1].to_vec()),
+        prefix.successor().expect("prefix has a successor"),
+    ];
+    for key in inside.iter().chain(&outside) {
+        backend.set(key.clone(), Some(Vec::new()))?;
+    }
+    let mut expected = inside.to_vec();
+    expected.sort();
+    assert_eq!(
+        keys(backend.get_range(Some(prefix.clone()), prefix.successor())?),
+        expected,
+        "prefix range holds exactly the keys under the prefix"
+    );
+
+    // An all-0xFF prefix has no successor; an open end must still stop at the prefix.
+    let top = KvKey::from_bytes(vec![0xFF, 0xFF]);
+    let below = KvKey::from_bytes(vec![0xFF, 0xFE, 0xFF]);
+    let under = KvKey::from_bytes(vec![0xFF, 0xFF, 0x00]);
+    for key in [&top, &below, &under] {
+        backend.set(key.clone(), Some(Vec::new()))?;
+    }
+    assert_eq!(
+        keys(backend.get_range(Some(top.clone()), None)?),
+        [top, under],
+        "open-ended range from an all-0xFF key"
+    );
+    Ok(())
+}
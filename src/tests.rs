@@ -1,8 +1,13 @@
 #[cfg(test)]
+// Most tests pass keys by reference, the form every key-taking method accepts.
+#[allow(clippy::needless_borrows_for_generic_args)]
 mod kv_integration_tests {
     #[cfg(feature = "sqlite")]
     use crate::SqliteBackend;
-    use crate::{Kv, KvResult, KvValue, MemoryBackend, keys::IntoKey};
+    use crate::{
+        ImportMode, JsonBinary, JsonCodec, Kv, KvBackend, KvDiff, KvError, KvKey, KvResult,
+        KvValue, MemoryBackend, ValueCodec, keys::IntoKey,
+    };
 
     #[test]
     fn set_and_get_single_value() -> KvResult<()> {
@@ -108,18 +113,2545 @@ mod kv_integration_tests {
         Ok(())
     }
 
+    #[test]
+    fn list_range_inclusive_end() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+
+        for i in 1..=5i64 {
+            kv.set(&(98u64, i), KvValue::I64(i))?;
+        }
+        kv.set(&(98u64, 3i64, "child"), KvValue::I64(33))?;
+
+        let values = |entries: Vec<(KvKey, KvValue)>| -> Vec<KvValue> {
+            entries.into_iter().map(|(_k, v)| v).collect()
+        };
+
+        let exclusive = kv
+            .list()
+            .start(&(98u64, 2i64))
+            .end(&(98u64, 3i64))
+            .entries()?;
+        assert_eq!(values(exclusive), vec![KvValue::I64(2)]);
+
+        // The boundary key and keys beneath it are included.
+        let inclusive = kv
+            .list()
+            .start(&(98u64, 2i64))
+            .end_inclusive(&(98u64, 3i64))
+            .entries()?;
+        assert_eq!(
+            values(inclusive),
+            vec![KvValue::I64(2), KvValue::I64(3), KvValue::I64(33)]
+        );
+
+        // An exact `[k, k]` range.
+        let exact = kv
+            .list()
+            .start(&(98u64, 5i64))
+            .end_inclusive(&(98u64, 5i64))
+            .entries()?;
+        assert_eq!(values(exact), vec![KvValue::I64(5)]);
+
+        let with_prefix = kv
+            .list()
+            .prefix(&(98u64,))
+            .end_inclusive(&(98u64, 2i64))
+            .entries()?;
+        assert_eq!(values(with_prefix), vec![KvValue::I64(1), KvValue::I64(2)]);
+        Ok(())
+    }
+
+    #[test]
+    fn scan_prefix_decodes_keys() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in [-2i64, 0, 3] {
+            kv.set(&(97u64, i), KvValue::I64(i * 10))?;
+        }
+        kv.set(&(96u64, 1i64), KvValue::Null)?;
+
+        let mut rows: Vec<((u64, i64), KvValue)> = kv.scan_prefix(&(97u64,))?;
+        rows.sort_by_key(|(key, _)| *key);
+        assert_eq!(
+            rows,
+            vec![
+                ((97, -2), KvValue::I64(-20)),
+                ((97, 0), KvValue::I64(0)),
+                ((97, 3), KvValue::I64(30)),
+            ]
+        );
+
+        kv.set(&(97u64, "not an int"), KvValue::Null)?;
+        let err = kv.scan_prefix::<(u64, i64)>(&(97u64,)).unwrap_err();
+        assert!(matches!(err, KvError::WrongSegmentType { index: 1, .. }));
+        Ok(())
+    }
+
+    #[test]
+    fn list_distinct_prefixes() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in 10..=12u64 {
+            kv.set(&(1u64, i), KvValue::I64(i as i64))?;
+        }
+        kv.set(&(2u64, "a"), KvValue::Null)?;
+        kv.set(&(2u64, "b", true), KvValue::Null)?;
+        kv.set(&(3u64,), KvValue::Null)?;
+
+        assert_eq!(
+            kv.list_prefixes(&KvKey::new())?,
+            vec![(1u64,).to_key(), (2u64,).to_key(), (3u64,).to_key()]
+        );
+        assert_eq!(
+            kv.list_prefixes(&(2u64,))?,
+            vec![(2u64, "a").to_key(), (2u64, "b").to_key()]
+        );
+        assert!(kv.list_prefixes(&(3u64,))?.is_empty());
+        assert!(kv.list_prefixes(&(4u64,))?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn dump_json_of_one_prefix() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        kv.set(&("users", 1u64), KvValue::from("alice"))?;
+        kv.set(&("users", 2u64), KvValue::Binary(vec![1, 2]))?;
+        kv.set(&("posts", 1u64), KvValue::from("hello"))?;
+        kv.set(&("usersx",), KvValue::Null)?;
+
+        let json = kv.dump_json_prefix(&("users",))?;
+        let restored = Kv::from_json_string(Box::new(MemoryBackend::new()), json)?;
+        assert_eq!(
+            restored.entries()?,
+            vec![
+                (("users", 1u64).to_key(), KvValue::from("alice")),
+                (("users", 2u64).to_key(), KvValue::Binary(vec![1, 2])),
+            ]
+        );
+
+        let mut other = Kv::new(Box::new(MemoryBackend::new()));
+        other.set(&("posts", 9u64), KvValue::Null)?;
+        other.import_json_str(&kv.dump_json_prefix(&("posts",))?, ImportMode::Merge)?;
+        assert_eq!(other.len()?, 2);
+        assert_eq!(other.get(&("posts", 1u64))?, Some(KvValue::from("hello")));
+        Ok(())
+    }
+
+    struct FixedIntCodec;
+
+    impl ValueCodec for FixedIntCodec {
+        fn id(&self) -> &'static str {
+            "bincode-fixed-be"
+        }
+
+        fn encode(&self, value: &KvValue) -> KvResult<Vec<u8>> {
+            let config = bincode::config::standard()
+                .with_fixed_int_encoding()
+                .with_big_endian();
+            bincode::encode_to_vec(value, config).map_err(KvError::ValEncodeError)
+        }
+
+        fn decode(&self, bytes: &[u8]) -> KvResult<KvValue> {
+            let config = bincode::config::standard()
+                .with_fixed_int_encoding()
+                .with_big_endian();
+            let (value, _) =
+                bincode::decode_from_slice(bytes, config).map_err(KvError::ValDecodeError)?;
+            Ok(value)
+        }
+    }
+
+    #[test]
+    fn custom_value_codecs_roundtrip() -> KvResult<()> {
+        let value = KvValue::Array(vec![
+            KvValue::I64(-3),
+            KvValue::F64(1.5),
+            KvValue::Binary(vec![0, 255]),
+            KvValue::from("text"),
+        ]);
+
+        let backend = MemoryBackend::new();
+        let mut kv = Kv::with_codec(Box::new(backend.clone()), FixedIntCodec);
+        kv.set(&("v",), value.clone())?;
+        assert_eq!(kv.get(&("v",))?, Some(value.clone()));
+        assert_eq!(kv.namespace(&("v",)).list().entries()?.len(), 1);
+        let raw = backend.get(&("v",).to_key())?.unwrap();
+        assert_eq!(FixedIntCodec.decode(&raw[1..])?, value);
+
+        let backend = MemoryBackend::new();
+        let mut kv = Kv::with_codec(Box::new(backend.clone()), JsonCodec);
+        kv.set(&("v",), value.clone())?;
+        assert_eq!(kv.entries()?, vec![(("v",).to_key(), value)]);
+        let raw = backend.get(&("v",).to_key())?.unwrap();
+        assert!(raw[1..].starts_with(b"[-3,1.5,"));
+        Ok(())
+    }
+
+    #[test]
+    fn first_and_last() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        assert!(kv.first(None)?.is_none());
+        assert!(kv.last(None)?.is_none());
+
+        for tenant in 1..=3u64 {
+            for i in 0..4i64 {
+                kv.set(&(tenant, i), KvValue::I64(tenant as i64 * 100 + i))?;
+            }
+        }
+
+        let (key, value) = kv.first(Some(&(2u64,)))?.unwrap();
+        assert_eq!(key, (2u64, 0i64).to_key());
+        assert_eq!(value, KvValue::I64(200));
+        let (key, value) = kv.last(Some(&(2u64,)))?.unwrap();
+        assert_eq!(key, (2u64, 3i64).to_key());
+        assert_eq!(value, KvValue::I64(203));
+
+        // Without a prefix, the whole keyspace is considered.
+        assert_eq!(kv.first(None)?.unwrap().1, KvValue::I64(100));
+        assert_eq!(kv.last(None)?.unwrap().1, KvValue::I64(303));
+
+        assert!(kv.first(Some(&(9u64,)))?.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn cursor_pagination() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in 0..25i64 {
+            kv.set(&(3u64, i), KvValue::I64(i))?;
+        }
+        kv.set(&(4u64, 0i64), KvValue::I64(-1))?;
+
+        let mut seen = Vec::new();
+        let mut cursor: Option<KvKey> = None;
+        let mut pages = 0;
+        loop {
+            let mut query = kv.list();
+            query.prefix(&(3u64,)).limit(10);
+            if let Some(cursor) = &cursor {
+                query.after(cursor);
+            }
+            let page = query.entries()?;
+            if page.is_empty() {
+                break;
+            }
+            pages += 1;
+            assert!(page.len() <= 10);
+            cursor = page.last().map(|(k, _)| k.clone());
+            seen.extend(page.into_iter().map(|(_, v)| i64::try_from(v).unwrap()));
+        }
+
+        assert_eq!(pages, 3);
+        assert_eq!(seen, (0..25).collect::<Vec<_>>());
+
+        // The lazy iterator honours the same cursor and limit.
+        let rest: Vec<_> = kv
+            .list()
+            .prefix(&(3u64,))
+            .after(&(3u64, 19i64))
+            .limit(3)
+            .iter()?
+            .collect::<KvResult<_>>()?;
+        let rest: Vec<_> = rest.into_iter().map(|(_, v)| v).collect();
+        assert_eq!(
+            rest,
+            vec![KvValue::I64(20), KvValue::I64(21), KvValue::I64(22)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn offset_windows() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in 0..12i64 {
+            kv.set(&(8u64, i), KvValue::I64(i))?;
+        }
+
+        let vals = |items: Vec<(KvKey, KvValue)>| -> Vec<KvValue> {
+            items.into_iter().map(|(_, v)| v).collect()
+        };
+
+        let zero = kv.list().prefix(&(8u64,)).offset(0).limit(2).entries()?;
+        assert_eq!(vals(zero), vec![KvValue::I64(0), KvValue::I64(1)]);
+
+        let window = kv.list().prefix(&(8u64,)).offset(10).limit(5).entries()?;
+        assert_eq!(vals(window), vec![KvValue::I64(10), KvValue::I64(11)]);
+
+        let past_end = kv.list().prefix(&(8u64,)).offset(12).limit(5).entries()?;
+        assert!(past_end.is_empty());
+        assert!(kv.list().prefix(&(8u64,)).offset(100).entries()?.is_empty());
+
+        let lazy: Vec<_> = kv
+            .list()
+            .prefix(&(8u64,))
+            .offset(4)
+            .limit(2)
+            .iter()?
+            .collect::<KvResult<_>>()?;
+        assert_eq!(vals(lazy), vec![KvValue::I64(4), KvValue::I64(5)]);
+        Ok(())
+    }
+
+    #[test]
+    fn map_entries_into_struct() -> KvResult<()> {
+        #[derive(Debug, PartialEq)]
+        struct Reading {
+            sensor: u64,
+            seq: i64,
+            value: i64,
+        }
+
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for seq in 0..3i64 {
+            kv.set(&(11u64, seq), KvValue::I64(seq * 10))?;
+        }
+        kv.set(&(12u64, 0i64), KvValue::String("not a number".into()))?;
+
+        let readings = kv.list().prefix(&(11u64,)).map(|(key, value)| {
+            let (sensor, seq): (u64, i64) = key.try_into()?;
+            Ok(Reading {
+                sensor,
+                seq,
+                value: value.try_into()?,
+            })
+        })?;
+        assert_eq!(readings.len(), 3);
+        assert_eq!(
+            readings[2],
+            Reading {
+                sensor: 11,
+                seq: 2,
+                value: 20
+            }
+        );
+
+        // A failed conversion short-circuits the whole pipeline.
+        let mut calls = 0;
+        let failed = kv.list().map(|(_, value)| {
+            calls += 1;
+            i64::try_from(value)
+        });
+        assert!(failed.is_err());
+        assert_eq!(calls, 4);
+        Ok(())
+    }
+
+    #[test]
+    fn namespaces_are_isolated() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let mut orders = kv.namespace(&("orders",));
+        let mut invoices = kv.namespace(&("invoices",));
+
+        for i in 0..3u64 {
+            orders.set(&(i,), KvValue::I64(i as i64))?;
+            invoices.set(&(i,), KvValue::I64(100 + i as i64))?;
+        }
+        kv.set(&("ordersx", 0u64), KvValue::Null)?;
+
+        assert_eq!(orders.get(&(1u64,))?, Some(KvValue::I64(1)));
+        assert_eq!(invoices.get(&(1u64,))?, Some(KvValue::I64(101)));
+        assert_eq!(kv.get(&("orders", 2u64))?, Some(KvValue::I64(2)));
+
+        let listed = orders.list().entries()?;
+        let keys: Vec<_> = listed.iter().map(|(k, _)| k.clone()).collect();
+        assert_eq!(
+            keys,
+            vec![(0u64,).to_key(), (1u64,).to_key(), (2u64,).to_key()]
+        );
+        let (first,): (u64,) = keys[0].clone().try_into()?;
+        assert_eq!(first, 0);
+
+        // Selectors are relative to the namespace and never escape it.
+        let tail = invoices.list().start(&(1u64,)).entries()?;
+        assert_eq!(tail.len(), 2);
+        assert_eq!(tail[0].0, (1u64,).to_key());
+        assert_eq!(invoices.list().end(&(1u64,)).entries()?.len(), 1);
+        let lazy: Vec<_> = orders.list().iter()?.collect::<KvResult<_>>()?;
+        assert_eq!(lazy, listed);
+
+        let deleted = orders.delete(&(0u64,))?;
+        assert_eq!(deleted, Some(((0u64,).to_key(), KvValue::I64(0))));
+        assert_eq!(orders.list().entries()?.len(), 2);
+        assert_eq!(invoices.list().entries()?.len(), 3);
+        assert_eq!(kv.entries()?.len(), 6);
+        Ok(())
+    }
+
+    #[cfg(feature = "watch")]
+    #[test]
+    fn watch_set_and_delete() -> KvResult<()> {
+        use crate::{KvEvent, KvEventKind};
+
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let all = kv.subscribe();
+        let users = kv.subscribe_prefix(&("users",));
+
+        let key = ("users", 1u64).to_key();
+        kv.set(&key, KvValue::Bool(true))?;
+        kv.set(&("groups", 1u64), KvValue::Bool(true))?;
+        kv.delete(&key)?;
+        // Deleting a missing key is not a change.
+        kv.delete(&key)?;
+
+        let events: Vec<KvEvent> = all.try_iter().collect();
+        assert_eq!(events.len(), 3);
+        assert_eq!(
+            events[0],
+            KvEvent {
+                key: key.clone(),
+                kind: KvEventKind::Set
+            }
+        );
+        assert_eq!(events[1].key, ("groups", 1u64).to_key());
+        assert_eq!(
+            events[2],
+            KvEvent {
+                key: key.clone(),
+                kind: KvEventKind::Delete
+            }
+        );
+
+        let filtered: Vec<KvEvent> = users.try_iter().collect();
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().all(|e| e.key == key));
+
+        // Dropped receivers are pruned and writes keep working.
+        drop(all);
+        let mut ns = kv.namespace(&("users",));
+        ns.set(&(2u64,), KvValue::Null)?;
+        assert_eq!(users.try_recv().unwrap().key, ("users", 2u64).to_key());
+        Ok(())
+    }
+
+    #[test]
+    fn copy_key() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let template = KvValue::Object(
+            [("role".to_string(), KvValue::String("member".into()))]
+                .into_iter()
+                .collect(),
+        );
+        kv.set(&("template",), template.clone())?;
+        kv.set(&("users", 1u64), KvValue::Null)?;
+
+        assert!(kv.copy(&("template",), &("users", 1u64))?);
+        assert_eq!(kv.get(&("template",))?, Some(template.clone()));
+        assert_eq!(kv.get(&("users", 1u64))?, Some(template));
+
+        assert!(!kv.copy(&("missing",), &("users", 2u64))?);
+        assert_eq!(kv.get(&("users", 2u64))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn csv_round_trip() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let rows = [
+            (1u64, "alice, \"the first\"", 31i64, true),
+            (2u64, "42", 27i64, false),
+            (3u64, "", 45i64, true),
+        ];
+        for (id, name, age, active) in rows {
+            let mut row = std::collections::BTreeMap::new();
+            row.insert("name".to_string(), KvValue::from(name));
+            row.insert("age".to_string(), KvValue::I64(age));
+            row.insert("active".to_string(), KvValue::Bool(active));
+            row.insert("avatar".to_string(), KvValue::Binary(vec![0, 1, 2]));
+            if id == 2 {
+                row.insert("score".to_string(), KvValue::F64(9.5));
+                row.insert("note".to_string(), KvValue::Null);
+            }
+            kv.set(&("users", id), KvValue::Object(row))?;
+        }
+
+        let csv = kv.dump_csv()?;
+        assert!(csv.starts_with("key,active,age,avatar,name,note,score\n"));
+
+        let loaded = Kv::from_csv_string(Box::new(MemoryBackend::new()), csv)?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+
+        kv.set(&("not", "an object"), KvValue::I64(1))?;
+        assert!(kv.dump_csv().is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack_matches_json_dump() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        kv.set(&(1u64, "str"), KvValue::String("hello: world".into()))?;
+        kv.set(&(2u64, -7i64), KvValue::F64(2.5))?;
+        kv.set(&(3u64, true), KvValue::Binary(vec![0, 255, 7]))?;
+        kv.set(
+            &(4u64,),
+            KvValue::Array(vec![KvValue::Null, KvValue::I64(-1), KvValue::Bool(false)]),
+        )?;
+
+        let json = kv.dump_json()?;
+        let msgpack = kv.dump_msgpack()?;
+        assert!(msgpack.len() < json.len());
+
+        let from_json = Kv::from_json_string(Box::new(MemoryBackend::new()), json)?;
+        let from_msgpack = Kv::from_msgpack(Box::new(MemoryBackend::new()), &msgpack)?;
+        assert_eq!(from_msgpack.entries()?, from_json.entries()?);
+        assert_eq!(from_msgpack.entries()?, kv.entries()?);
+
+        assert!(Kv::from_msgpack(Box::new(MemoryBackend::new()), &[0xc1]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn ndjson_round_trip() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in 0..3000u64 {
+            let value = match i % 3 {
+                0 => KvValue::I64(i as i64),
+                1 => KvValue::String(format!("line\nbreak {i}")),
+                _ => KvValue::Binary(i.to_be_bytes().to_vec()),
+            };
+            kv.set(&("row", i, i % 2 == 0), value)?;
+        }
+
+        let mut out = Vec::new();
+        kv.dump_ndjson(&mut out)?;
+        assert_eq!(out.iter().filter(|&&b| b == b'\n').count(), 3000);
+
+        let mut loaded = Kv::new(Box::new(MemoryBackend::new()));
+        loaded.load_ndjson(out.as_slice())?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+
+        assert!(loaded.load_ndjson("{\"value\":1}\n".as_bytes()).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn lenient_json_import_reports_bad_keys() -> KvResult<()> {
+        let mut source = Kv::new(Box::new(MemoryBackend::new()));
+        source.set(&("good", 1u64), KvValue::from("a"))?;
+        source.set(&("good", 2u64), KvValue::Binary(vec![7]))?;
+        let mut json: serde_json::Value = serde_json::from_str(&source.dump_json()?).unwrap();
+        json.as_object_mut()
+            .unwrap()
+            .insert("\"unterminated:1u".into(), serde_json::json!(3));
+
+        assert!(Kv::from_serde_json(Box::new(MemoryBackend::new()), json.clone()).is_err());
+
+        let (kv, skipped) =
+            Kv::from_serde_json_lenient(Box::new(MemoryBackend::new()), json.clone())?;
+        assert_eq!(kv.entries()?, source.entries()?);
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].0, "\"unterminated:1u");
+        assert!(matches!(skipped[0].1, KvError::KeyDecodeError(_)));
+
+        let mut target = Kv::new(Box::new(MemoryBackend::new()));
+        target.set(&("stale",), KvValue::Null)?;
+        let skipped = target.import_json_str_lenient(&json.to_string(), ImportMode::Replace)?;
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(target.entries()?, source.entries()?);
+
+        assert!(
+            target
+                .import_json_str_lenient("[1]", ImportMode::Merge)
+                .is_err()
+        );
+        assert_eq!(target.len()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn pretty_json_dump_roundtrips() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&("users", 1u64), KvValue::from("alice"))?;
+        kv.set(&("users", 2u64, true), KvValue::Binary(vec![0, 9]))?;
+        kv.set(
+            &('c', -4i64),
+            KvValue::Array(vec![KvValue::F64(0.5), KvValue::Null]),
+        )?;
+
+        let pretty = kv.dump_json_pretty()?;
+        assert!(pretty.contains("\n  "));
+        assert!(pretty.len() > kv.dump_json()?.len());
+
+        let loaded = Kv::from_json_string(Box::new(MemoryBackend::new()), pretty)?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+        Ok(())
+    }
+
+    #[test]
+    fn import_json_modes() -> KvResult<()> {
+        let mut source = Kv::new(Box::new(MemoryBackend::new()));
+        source.set(&("shared",), KvValue::I64(2))?;
+        source.set(&("new",), KvValue::I64(3))?;
+        let dump = source.dump_json()?;
+
+        let existing = |kv: &mut Kv| -> KvResult<()> {
+            kv.set(&("shared",), KvValue::I64(1))?;
+            kv.set(&("untouched",), KvValue::I64(1))
+        };
+
+        let mut merged = Kv::new(Box::new(MemoryBackend::new()));
+        existing(&mut merged)?;
+        merged.import_json_str(&dump, ImportMode::Merge)?;
+        assert_eq!(merged.get(&("shared",))?, Some(KvValue::I64(2)));
+        assert_eq!(merged.get(&("new",))?, Some(KvValue::I64(3)));
+        assert_eq!(merged.get(&("untouched",))?, Some(KvValue::I64(1)));
+
+        let mut replaced = Kv::new(Box::new(MemoryBackend::new()));
+        existing(&mut replaced)?;
+        replaced.import_json_str(&dump, ImportMode::Replace)?;
+        assert_eq!(replaced.get(&("untouched",))?, None);
+        assert_eq!(replaced.entries()?, source.entries()?);
+
+        // A malformed dump is rejected before the store is cleared.
+        assert!(
+            replaced
+                .import_json_str("[1, 2]", ImportMode::Replace)
+                .is_err()
+        );
+        assert!(replaced.import_json_str("{", ImportMode::Replace).is_err());
+        assert_eq!(replaced.entries()?, source.entries()?);
+        Ok(())
+    }
+
+    #[test]
+    fn decode_error_has_source() -> KvResult<()> {
+        use std::error::Error;
+
+        let backend = Box::new(MemoryBackend::new());
+        let kv = Kv::new(backend);
+        let key = ("corrupt",).to_key();
+        kv.backend.write().set(key.clone(), Some(vec![42]))?;
+
+        let err = kv.get(&key).unwrap_err();
+        assert!(matches!(err, crate::KvError::ValDecodeError(_)));
+        assert!(err.source().is_some());
+        assert!(
+            crate::KvError::InvalidSelector(String::new())
+                .source()
+                .is_none()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn values_carry_a_version_header() -> KvResult<()> {
+        let backend = MemoryBackend::new();
+        let mut kv = Kv::new(Box::new(backend.clone()));
+        let value = KvValue::from("tagged");
+        kv.set(&("new",), value.clone())?;
+
+        let raw = backend.get(&("new",).to_key())?.unwrap();
+        assert_eq!(raw[0], 0x81);
+        assert_eq!(raw[1..], value.encode()?);
+        assert_eq!(kv.get(&("new",))?, Some(value.clone()));
+
+        // Values written before the header existed still read back, and can be upgraded.
+        let mut raw_backend = backend.clone();
+        raw_backend.set(("old",).to_key(), Some(value.encode()?))?;
+        assert_eq!(kv.get(&("old",))?, Some(value.clone()));
+        assert_eq!(kv.upgrade_value_headers()?, 1);
+        assert_eq!(backend.get(&("old",).to_key())?, Some(raw));
+        assert_eq!(kv.upgrade_value_headers()?, 0);
+        assert_eq!(kv.entries()?.len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn unknown_value_version_is_rejected() -> KvResult<()> {
+        let mut backend = MemoryBackend::new();
+        let kv = Kv::new(Box::new(backend.clone()));
+        let mut forged = vec![0x82];
+        forged.extend(KvValue::Null.encode()?);
+        backend.set(("future",).to_key(), Some(forged))?;
+
+        assert!(matches!(
+            kv.get(&("future",)),
+            Err(KvError::UnsupportedValueVersion(0x82))
+        ));
+        assert!(matches!(
+            kv.entries(),
+            Err(KvError::UnsupportedValueVersion(0x82))
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn typed_kv_with_custom_value() -> KvResult<()> {
+        use crate::{KvError, TypedKv};
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Clone, PartialEq)]
+        struct Account {
+            owner: String,
+            balance: i64,
+        }
+
+        impl From<Account> for KvValue {
+            fn from(account: Account) -> Self {
+                let mut map = BTreeMap::new();
+                map.insert("owner".to_string(), KvValue::String(account.owner));
+                map.insert("balance".to_string(), KvValue::I64(account.balance));
+                KvValue::Object(map)
+            }
+        }
+
+        impl TryFrom<KvValue> for Account {
+            type Error = KvError;
+
+            fn try_from(value: KvValue) -> Result<Self, Self::Error> {
+                let KvValue::Object(mut map) = value else {
+                    return Err(KvError::ValDowncastError(format!(
+                        "expected an account object, got {value:?}"
+                    )));
+                };
+                let mut field = |name: &str| {
+                    map.remove(name).ok_or_else(|| {
+                        KvError::ValDowncastError(format!("account is missing `{name}`"))
+                    })
+                };
+                Ok(Account {
+                    owner: field("owner")?.try_into()?,
+                    balance: field("balance")?.try_into()?,
+                })
+            }
+        }
+
+        let mut accounts: TypedKv<(u64, String), Account> =
+            TypedKv::new(Box::new(MemoryBackend::new()));
+        let key = (1u64, "checking".to_string());
+        let account = Account {
+            owner: "alice".into(),
+            balance: 250,
+        };
+        accounts.set(&key, account.clone())?;
+        accounts.set(
+            &(2u64, "savings".to_string()),
+            Account {
+                owner: "bob".into(),
+                balance: -3,
+            },
+        )?;
+
+        assert_eq!(accounts.get(&key)?, Some(account.clone()));
+        assert_eq!(accounts.get(&(9u64, "none".to_string()))?, None);
+        let all = accounts.entries()?;
+        assert_eq!(all.len(), 2);
+        assert_eq!(all[0], (key.clone(), account.clone()));
+
+        assert_eq!(accounts.delete(&key)?, Some(account));
+        assert_eq!(accounts.get(&key)?, None);
+
+        // Values that are not accounts surface as conversion errors.
+        accounts
+            .kv
+            .set(&(3u64, "bad".to_string()), KvValue::I64(1))?;
+        assert!(matches!(
+            accounts.get(&(3u64, "bad".to_string())),
+            Err(KvError::ValDowncastError(_))
+        ));
+        Ok(())
+    }
+
+    #[cfg(feature = "chrono")]
+    #[test]
+    fn timestamp_range_scan() -> KvResult<()> {
+        use chrono::{DateTime, TimeZone, Utc};
+
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let day = |y, m, d| Utc.with_ymd_and_hms(y, m, d, 0, 0, 0).unwrap();
+        for (i, t) in [
+            day(1960, 1, 1),
+            day(1969, 12, 31),
+            day(1970, 1, 2),
+            day(2001, 9, 9),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            kv.set(&("events", t), KvValue::I64(i as i64))?;
+        }
+
+        let between = kv
+            .list()
+            .start(&("events", day(1965, 1, 1)))
+            .end(&("events", day(2000, 1, 1)))
+            .entries()?;
+        let times: Vec<DateTime<Utc>> = between
+            .into_iter()
+            .map(|(k, _)| <(String, DateTime<Utc>)>::try_from(k).map(|(_, t)| t))
+            .collect::<KvResult<_>>()?;
+        assert_eq!(times, vec![day(1969, 12, 31), day(1970, 1, 2)]);
+
+        let when = day(1955, 11, 5) + chrono::Duration::milliseconds(250);
+        kv.set(&("when",), when.into())?;
+        let value = kv.get(&("when",))?.unwrap();
+        assert_eq!(value, KvValue::String("1955-11-05T00:00:00.250Z".into()));
+        assert_eq!(DateTime::<Utc>::try_from(value)?, when);
+        Ok(())
+    }
+
+    #[test]
+    fn get_with_key_from_bytes() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let key = ("index", 5u64).to_key();
+        kv.set(&key, KvValue::I64(5))?;
+
+        // Pretend the key went through an external index and back.
+        let stored: Vec<u8> = key.as_bytes().to_vec();
+        let restored = KvKey::from_bytes(stored);
+        assert_eq!(kv.get(&restored)?, Some(KvValue::I64(5)));
+        Ok(())
+    }
+
     #[test]
     fn clear_backend() -> KvResult<()> {
         let backend = Box::new(MemoryBackend::new());
         let mut kv = Kv::new(backend);
 
-        for i in 0..5i64 {
-            let tup = (777u64, i);
-            kv.set(&tup, KvValue::I64(i))?;
+        for i in 0..5i64 {
+            let tup = (777u64, i);
+            kv.set(&tup, KvValue::I64(i))?;
+        }
+        kv.backend.write().clear()?;
+        let items = kv.entries()?;
+        assert_eq!(items.len(), 0);
+        Ok(())
+    }
+
+    #[test]
+    fn clear_store() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        for i in 0..5i64 {
+            kv.set(&(778u64, i), KvValue::I64(i))?;
+        }
+        kv.clear()?;
+        assert!(kv.entries()?.is_empty());
+
+        // The store is still usable afterwards.
+        kv.set(&(778u64, 0i64), KvValue::Null)?;
+        assert_eq!(kv.entries()?.len(), 1);
+        Ok(())
+    }
+
+    #[test]
+    fn len_and_is_empty() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        assert_eq!(kv.len()?, 0);
+        assert!(kv.is_empty()?);
+
+        for i in 0..4i64 {
+            kv.set(&(779u64, i), KvValue::I64(i))?;
+        }
+        kv.set(&(779u64, 0i64), KvValue::Null)?;
+        assert_eq!(kv.len()?, 4);
+
+        kv.delete(&(779u64, 1i64))?;
+        assert_eq!(kv.len()?, 3);
+        assert!(!kv.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn read_only_dumps_share_borrow() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        kv.set(&(780u64, "a"), KvValue::I64(1))?;
+        kv.set(&(780u64, "b"), KvValue::I64(2))?;
+
+        let reader = &kv;
+        let listing = reader.list();
+        let entries = kv.entries()?;
+        assert_eq!(entries, listing.entries()?);
+        assert_eq!(reader.get(&(780u64, "a"))?, Some(KvValue::I64(1)));
+
+        let json = kv.dump_json()?;
+        assert_eq!(
+            serde_json::to_string(&reader.to_serde_json()?).unwrap(),
+            json
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn size_hint_tracks_encoded_length() -> KvResult<()> {
+        let record = KvValue::Object(
+            [
+                (
+                    "name".to_string(),
+                    KvValue::from("a fairly ordinary user name"),
+                ),
+                ("bio".to_string(), KvValue::from("lorem ipsum ".repeat(20))),
+                ("age".to_string(), KvValue::I64(1 << 40)),
+                ("score".to_string(), KvValue::F64(0.5)),
+                ("avatar".to_string(), KvValue::Binary(vec![7; 300])),
+                (
+                    "tags".to_string(),
+                    KvValue::Array(vec!["a".into(), "bb".into(), KvValue::Null]),
+                ),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        for value in [
+            KvValue::from("x".repeat(1000)),
+            KvValue::Binary(vec![0; 4096]),
+            KvValue::I64(i64::MIN),
+            record,
+        ] {
+            let actual = value.encode()?.len();
+            let hint = value.size_hint();
+            assert!(hint >= actual * 9 / 10, "{hint} vs {actual}");
+            assert!(hint <= actual * 5 / 4 + 16, "{hint} vs {actual}");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn max_value_size_rejects_large_writes() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend).with_max_value_size(64);
+        kv.set(&(781u64, "small"), KvValue::from("fits"))?;
+
+        let big = KvValue::from("y".repeat(100));
+        let err = kv.set(&(781u64, "big"), big.clone()).unwrap_err();
+        assert!(matches!(err, KvError::ValueTooLarge { max: 64, .. }));
+        assert_eq!(kv.get(&(781u64, "big"))?, None);
+
+        let batch = vec![
+            ((781u64, "a"), KvValue::I64(1)),
+            ((781u64, "b"), big.clone()),
+        ];
+        assert!(kv.set_batch(batch).is_err());
+        assert_eq!(kv.get(&(781u64, "a"))?, None);
+
+        let mut ns = kv.namespace(&(781u64,));
+        assert!(ns.set(&("big",), big).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn max_key_len_rejects_long_keys() -> KvResult<()> {
+        let fits = (782u64, "k".repeat(20));
+        let limit = fits.to_key().0.len();
+        let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_max_key_len(limit);
+        kv.set(&fits, KvValue::I64(1))?;
+        assert_eq!(kv.get(&fits)?, Some(KvValue::I64(1)));
+
+        let long = (782u64, "k".repeat(21));
+        let err = kv.set(&long, KvValue::I64(2)).unwrap_err();
+        assert!(
+            matches!(err, KvError::KeyTooLong { max, len } if max == limit && len == limit + 1)
+        );
+        assert!(matches!(kv.get(&long), Err(KvError::KeyTooLong { .. })));
+        assert!(matches!(kv.delete(&long), Err(KvError::KeyTooLong { .. })));
+        assert_eq!(kv.len()?, 1);
+
+        let mut ns = kv.namespace(&(782u64,));
+        assert!(ns.set(("k".repeat(21),), KvValue::Null).is_err());
+        assert_eq!(kv.delete(&fits)?, Some((fits.to_key(), KvValue::I64(1))));
+        Ok(())
+    }
+
+    #[test]
+    fn values_from_collections() {
+        use std::collections::HashMap;
+
+        let strings = vec!["a".to_string(), "b".to_string()];
+        assert_eq!(
+            KvValue::from(strings),
+            KvValue::Array(vec![KvValue::from("a"), KvValue::from("b")])
+        );
+        assert_eq!(
+            KvValue::from(vec![1i64, -2]),
+            KvValue::Array(vec![KvValue::I64(1), KvValue::I64(-2)])
+        );
+        assert_eq!(
+            KvValue::from(vec![vec![true], vec![]]),
+            KvValue::Array(vec![
+                KvValue::Array(vec![KvValue::Bool(true)]),
+                KvValue::Array(vec![])
+            ])
+        );
+        assert_eq!(
+            KvValue::from(vec![KvValue::Null]),
+            KvValue::Array(vec![KvValue::Null])
+        );
+        // Bytes stay binary.
+        assert_eq!(KvValue::from(vec![1u8, 2]), KvValue::Binary(vec![1, 2]));
+        // Unsigned numbers are range-checked into I64.
+        assert_eq!(
+            KvValue::try_from(vec![0u64, 7]).unwrap(),
+            KvValue::Array(vec![KvValue::I64(0), KvValue::I64(7)])
+        );
+        assert!(matches!(
+            KvValue::try_from(vec![1u64, u64::MAX]),
+            Err(KvError::ValDowncastError(_))
+        ));
+
+        let map: HashMap<String, KvValue> =
+            [("n".to_string(), KvValue::I64(1))].into_iter().collect();
+        let KvValue::Object(fields) = KvValue::from(map) else {
+            panic!("expected an object");
+        };
+        assert_eq!(fields.get("n"), Some(&KvValue::I64(1)));
+        let scores: HashMap<String, f64> = [("x".to_string(), 0.5)].into_iter().collect();
+        assert_eq!(
+            KvValue::from(scores),
+            KvValue::Object([("x".to_string(), KvValue::F64(0.5))].into_iter().collect())
+        );
+    }
+
+    #[test]
+    fn downcast_objects_and_narrow_ints() -> KvResult<()> {
+        use std::collections::BTreeMap;
+
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let fields: BTreeMap<String, KvValue> =
+            [("id".to_string(), KvValue::I64(7))].into_iter().collect();
+        kv.set(&(782u64, "obj"), fields.clone().into())?;
+        kv.set(&(782u64, "big"), KvValue::I64(1 << 40))?;
+        kv.set(&(782u64, "neg"), KvValue::I64(-1))?;
+
+        let object: BTreeMap<String, KvValue> = kv.get(&(782u64, "obj"))?.unwrap().try_into()?;
+        assert_eq!(object, fields);
+        assert!(BTreeMap::<String, KvValue>::try_from(KvValue::Null).is_err());
+
+        let id: u32 = object["id"].clone().try_into()?;
+        assert_eq!(id, 7);
+        assert_eq!(i8::try_from(KvValue::I64(-128))?, i8::MIN);
+        assert_eq!(u16::try_from(KvValue::I64(65535))?, u16::MAX);
+
+        let big = kv.get(&(782u64, "big"))?.unwrap();
+        assert!(matches!(
+            u32::try_from(big.clone()),
+            Err(KvError::ValDowncastError(_))
+        ));
+        assert!(i32::try_from(big).is_err());
+        assert!(u8::try_from(kv.get(&(782u64, "neg"))?.unwrap()).is_err());
+        assert!(u32::try_from(KvValue::from("7")).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn share_across_threads() -> KvResult<()> {
+        use crate::RwLockMemoryBackend;
+        use std::sync::Arc;
+        use std::thread;
+
+        fn assert_send_sync<T: Send + Sync>() {}
+        assert_send_sync::<Kv>();
+
+        let backend = RwLockMemoryBackend::new();
+        let kv = Arc::new(Kv::new(Box::new(backend.clone())));
+        let mut writer_kv = Kv::new(Box::new(backend));
+        let mut ns = kv.namespace(&("ns",));
+
+        let writer = thread::spawn(move || -> KvResult<()> {
+            for i in 0..200i64 {
+                writer_kv.set(&(783u64, i), KvValue::I64(i))?;
+                ns.set(&(i,), KvValue::I64(-i))?;
+            }
+            Ok(())
+        });
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let kv = Arc::clone(&kv);
+                thread::spawn(move || -> KvResult<()> {
+                    for _ in 0..50 {
+                        for (_, value) in kv.list().prefix(&(783u64,)).entries()? {
+                            assert!(matches!(value, KvValue::I64(0..200)));
+                        }
+                    }
+                    Ok(())
+                })
+            })
+            .collect();
+
+        writer.join().unwrap()?;
+        for reader in readers {
+            reader.join().unwrap()?;
+        }
+        assert_eq!(kv.list().prefix(&(783u64,)).entries()?.len(), 200);
+        assert_eq!(kv.get(&("ns", 199i64))?, Some(KvValue::I64(-199)));
+        Ok(())
+    }
+
+    #[test]
+    fn fold_over_entries_iter() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        // More than one page of results.
+        kv.set_batch((1..=1000i64).map(|i| ((784u64, i), KvValue::I64(i))))?;
+
+        let mut sum = 0;
+        for row in kv.entries_iter() {
+            let (_, value) = row?;
+            sum += i64::try_from(value)?;
+        }
+        assert_eq!(sum, 500_500);
+
+        let first_big = kv
+            .entries_iter()
+            .map(|row| row.and_then(|(_, v)| i64::try_from(v)))
+            .find(|n| matches!(n, Ok(n) if *n > 300))
+            .transpose()?;
+        assert_eq!(first_big, Some(301));
+        Ok(())
+    }
+
+    #[test]
+    fn get_or_insert_with_runs_once() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let mut calls = 0;
+
+        let first = kv.get_or_insert_with(&(785u64, "k"), || {
+            calls += 1;
+            KvValue::I64(1)
+        })?;
+        assert_eq!(first, KvValue::I64(1));
+        assert_eq!(calls, 1);
+        assert_eq!(kv.get(&(785u64, "k"))?, Some(KvValue::I64(1)));
+
+        let second = kv.get_or_insert_with(&(785u64, "k"), || {
+            calls += 1;
+            KvValue::I64(2)
+        })?;
+        assert_eq!(second, KvValue::I64(1));
+        assert_eq!(calls, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn update_inserts_modifies_and_deletes() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let key = (786u64, "counter");
+        let incr = |current: Option<KvValue>| match current {
+            Some(KvValue::I64(n)) => Some(KvValue::I64(n + 1)),
+            _ => Some(KvValue::I64(1)),
+        };
+
+        kv.update(&key, incr)?;
+        assert_eq!(kv.get(&key)?, Some(KvValue::I64(1)));
+        kv.update(&key, incr)?;
+        assert_eq!(kv.get(&key)?, Some(KvValue::I64(2)));
+
+        kv.update(&key, |current| {
+            assert_eq!(current, Some(KvValue::I64(2)));
+            None
+        })?;
+        assert_eq!(kv.get(&key)?, None);
+
+        kv.update(&key, |current| {
+            assert_eq!(current, None);
+            None
+        })?;
+        assert!(kv.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn array_push_appends() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let key = (787u64, "log");
+
+        assert_eq!(kv.array_push(&key, KvValue::from("a"))?, 1);
+        assert_eq!(kv.array_push(&key, KvValue::I64(2))?, 2);
+        assert_eq!(
+            kv.get(&key)?,
+            Some(KvValue::Array(vec![KvValue::from("a"), KvValue::I64(2)]))
+        );
+
+        kv.set(&(787u64, "scalar"), KvValue::I64(1))?;
+        let err = kv
+            .array_push(&(787u64, "scalar"), KvValue::Null)
+            .unwrap_err();
+        assert!(matches!(err, KvError::ValDowncastError(_)));
+        assert_eq!(kv.get(&(787u64, "scalar"))?, Some(KvValue::I64(1)));
+        Ok(())
+    }
+
+    #[test]
+    fn object_field_helpers() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+        let key = (788u64, "user");
+
+        assert_eq!(kv.object_remove(&key, "name")?, None);
+        assert_eq!(kv.get(&key)?, None);
+
+        assert_eq!(kv.object_set(&key, "name", "alice".into())?, None);
+        assert_eq!(kv.object_set(&key, "age", KvValue::I64(30))?, None);
+        assert_eq!(
+            kv.object_set(&key, "name", "bob".into())?,
+            Some("alice".into())
+        );
+        let expected = KvValue::Object(
+            [
+                ("age".to_string(), KvValue::I64(30)),
+                ("name".to_string(), "bob".into()),
+            ]
+            .into_iter()
+            .collect(),
+        );
+        assert_eq!(kv.get(&key)?, Some(expected));
+
+        assert_eq!(kv.object_remove(&key, "age")?, Some(KvValue::I64(30)));
+        assert_eq!(kv.object_remove(&key, "age")?, None);
+        let KvValue::Object(fields) = kv.get(&key)?.unwrap() else {
+            panic!("expected an object");
+        };
+        assert_eq!(fields.keys().collect::<Vec<_>>(), ["name"]);
+
+        kv.set(&(788u64, "list"), KvValue::Array(vec![]))?;
+        for result in [
+            kv.object_set(&(788u64, "list"), "x", KvValue::Null),
+            kv.object_remove(&(788u64, "list"), "x"),
+        ] {
+            assert!(matches!(result, Err(KvError::ValDowncastError(_))));
+        }
+        assert_eq!(kv.get(&(788u64, "list"))?, Some(KvValue::Array(vec![])));
+        Ok(())
+    }
+
+    #[test]
+    fn borrowing_accessors_match_one_variant() {
+        use std::collections::BTreeMap;
+
+        let fields = BTreeMap::from([("k".to_string(), KvValue::Null)]);
+        let values = [
+            KvValue::Null,
+            KvValue::Bool(true),
+            KvValue::I64(-3),
+            KvValue::I64(7),
+            KvValue::F64(1.5),
+            KvValue::from("s"),
+            KvValue::Array(vec![KvValue::I64(1)]),
+            KvValue::Object(fields.clone()),
+            KvValue::Binary(vec![0, 1]),
+        ];
+        for value in &values {
+            let matched = [
+                value.as_bool().is_some(),
+                value.as_i64().is_some(),
+                value.as_f64().is_some(),
+                value.as_str().is_some(),
+                value.as_array().is_some(),
+                value.as_object().is_some(),
+                value.as_bytes().is_some(),
+            ];
+            let expected = usize::from(!matches!(value, KvValue::Null));
+            assert_eq!(
+                matched.iter().filter(|m| **m).count(),
+                expected,
+                "{value:?}"
+            );
+        }
+
+        assert_eq!(values[1].as_bool(), Some(true));
+        assert_eq!(values[2].as_i64(), Some(-3));
+        assert_eq!(values[2].as_u64(), None);
+        assert_eq!(values[3].as_u64(), Some(7));
+        assert_eq!(values[4].as_f64(), Some(1.5));
+        assert_eq!(values[4].as_i64(), None);
+        assert_eq!(values[5].as_str(), Some("s"));
+        assert_eq!(values[6].as_array(), Some(&[KvValue::I64(1)][..]));
+        assert_eq!(values[7].as_object(), Some(&fields));
+        assert_eq!(values[8].as_bytes(), Some(&[0u8, 1][..]));
+        assert_eq!(values[8].as_array(), None);
+        assert_eq!(values[5].as_bytes(), None);
+    }
+
+    #[test]
+    fn numeric_coercions_check_range() {
+        assert_eq!(KvValue::I64(-5).to_i64().unwrap(), -5);
+        assert_eq!(KvValue::F64(-5.0).to_i64().unwrap(), -5);
+        assert_eq!(KvValue::F64(i64::MIN as f64).to_i64().unwrap(), i64::MIN);
+        assert_eq!(KvValue::I64(5).to_u64().unwrap(), 5);
+        assert_eq!(
+            KvValue::F64(1.8e19).to_u64().unwrap(),
+            18_000_000_000_000_000_000
+        );
+        assert_eq!(KvValue::I64(3).to_f64().unwrap(), 3.0);
+        assert_eq!(KvValue::F64(0.25).to_f64().unwrap(), 0.25);
+
+        let overflow = KvValue::F64(9.3e18).to_i64();
+        assert!(matches!(overflow, Err(KvError::ValDowncastError(_))));
+        assert!(KvValue::F64(1.9e19).to_u64().is_err());
+        assert!(KvValue::I64(-1).to_u64().is_err());
+        assert!(KvValue::F64(2.5).to_i64().is_err());
+        assert!(KvValue::F64(f64::NAN).to_i64().is_err());
+        assert!(KvValue::F64(f64::INFINITY).to_u64().is_err());
+        assert!(KvValue::from("5").to_f64().is_err());
+
+        // The strict downcasts are unchanged.
+        assert!(i64::try_from(KvValue::F64(5.0)).is_err());
+        assert!(f64::try_from(KvValue::I64(5)).is_err());
+    }
+
+    #[test]
+    fn values_compare_numerically_across_types() {
+        use std::cmp::Ordering;
+
+        assert!(KvValue::I64(2) < KvValue::F64(2.5));
+        assert!(KvValue::F64(-2.5) < KvValue::I64(-2));
+        assert!(KvValue::I64(3) > KvValue::F64(2.999));
+        assert_eq!(KvValue::I64(5).cmp(&KvValue::F64(5.0)), Ordering::Less);
+        assert_ne!(KvValue::I64(5), KvValue::F64(5.0));
+        assert!(KvValue::I64(i64::MAX) < KvValue::F64(9.3e18));
+        assert!(KvValue::I64(i64::MIN) > KvValue::F64(f64::NEG_INFINITY));
+        assert!(KvValue::F64(-0.0) < KvValue::F64(0.0));
+        assert!(KvValue::F64(f64::INFINITY) < KvValue::F64(f64::NAN));
+        assert_eq!(KvValue::F64(f64::NAN), KvValue::F64(f64::NAN));
+    }
+
+    #[test]
+    fn mixed_values_sort_deterministically() {
+        let values = vec![
+            KvValue::Binary(vec![1]),
+            KvValue::from("b"),
+            KvValue::F64(5.0),
+            KvValue::Array(vec![KvValue::I64(1)]),
+            KvValue::I64(5),
+            KvValue::Bool(true),
+            KvValue::F64(f64::NAN),
+            KvValue::from("a"),
+            KvValue::I64(-1),
+            KvValue::Null,
+            KvValue::Object(Default::default()),
+            KvValue::Bool(false),
+        ];
+        let expected = vec![
+            KvValue::Null,
+            KvValue::Bool(false),
+            KvValue::Bool(true),
+            KvValue::I64(-1),
+            KvValue::I64(5),
+            KvValue::F64(5.0),
+            KvValue::F64(f64::NAN),
+            KvValue::from("a"),
+            KvValue::from("b"),
+            KvValue::Array(vec![KvValue::I64(1)]),
+            KvValue::Object(Default::default()),
+            KvValue::Binary(vec![1]),
+        ];
+
+        let mut sorted = values.clone();
+        sorted.sort();
+        assert_eq!(sorted, expected);
+        let mut reversed: Vec<_> = values.into_iter().rev().collect();
+        reversed.sort();
+        assert_eq!(reversed, expected);
+    }
+
+    #[test]
+    fn delete_returns_previous_value() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let value = KvValue::Array(vec![KvValue::from("x"), KvValue::Binary(vec![1, 2])]);
+        kv.set(&("doc", 1u64), value.clone())?;
+        kv.set(&("doc", 2u64), KvValue::I64(2))?;
+
+        assert_eq!(
+            kv.delete(&("doc", 1u64))?,
+            Some((("doc", 1u64).to_key(), value))
+        );
+        assert_eq!(kv.delete(&("doc", 1u64))?, None);
+        assert_eq!(kv.get(&("doc", 1u64))?, None);
+
+        assert!(kv.remove(&("doc", 2u64))?);
+        assert!(!kv.remove(&("doc", 2u64))?);
+        assert!(kv.is_empty()?);
+
+        // A value that doesn't decode is reported and left in place.
+        let corrupt = ("corrupt",).to_key();
+        kv.backend.write().set(corrupt.clone(), Some(vec![42]))?;
+        assert!(matches!(
+            kv.delete(&corrupt),
+            Err(KvError::ValDecodeError(_))
+        ));
+        assert_eq!(kv.backend.read().get(&corrupt)?, Some(vec![42]));
+        Ok(())
+    }
+
+    #[test]
+    fn prefix_composes_with_start_and_end() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        for a in 0..3u64 {
+            for b in 0..12i64 {
+                kv.set(&(a, b), KvValue::I64(b))?;
+            }
+        }
+        let values = |rows: Vec<(KvKey, KvValue)>| -> Vec<KvValue> {
+            rows.into_iter().map(|(_, v)| v).collect()
+        };
+
+        let sub = kv
+            .list()
+            .prefix(&(1u64,))
+            .start(&(1u64, 5i64))
+            .end(&(1u64, 9i64))
+            .entries()?;
+        assert_eq!(values(sub), (5..9).map(KvValue::I64).collect::<Vec<_>>());
+        assert!(
+            kv.list()
+                .prefix(&(1u64,))
+                .start(&(1u64, 5i64))
+                .entries()?
+                .iter()
+                .all(|(k, _)| k.starts_with(&(1u64,).to_key()))
+        );
+
+        // An end past the prefix is clamped to it.
+        let clamped = kv
+            .list()
+            .prefix(&(1u64,))
+            .start(&(1u64, 3i64))
+            .end(&(2u64, 4i64))
+            .entries()?;
+        assert_eq!(clamped.len(), 9);
+        assert_eq!(clamped[0].0, (1u64, 3i64).to_key());
+        let tail = kv.list().prefix(&(1u64,)).start(&(1u64, 10i64)).entries()?;
+        assert_eq!(values(tail), vec![KvValue::I64(10), KvValue::I64(11)]);
+        let head = kv.list().prefix(&(1u64,)).end(&(1u64, 2i64)).entries()?;
+        assert_eq!(values(head), vec![KvValue::I64(0), KvValue::I64(1)]);
+        Ok(())
+    }
+
+    #[test]
+    fn conflicting_selectors_are_rejected() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&(1u64, 5i64), KvValue::Null)?;
+
+        let err = kv
+            .list()
+            .start(&(1u64, 9i64))
+            .end(&(1u64, 2i64))
+            .entries()
+            .unwrap_err();
+        assert!(matches!(&err, KvError::InvalidSelector(msg) if msg.contains("after end")));
+
+        let err = kv
+            .list()
+            .prefix(&(1u64,))
+            .start(&(2u64, 0i64))
+            .entries()
+            .unwrap_err();
+        assert!(matches!(&err, KvError::InvalidSelector(msg) if msg.contains("outside prefix")));
+        assert!(kv.list().prefix(&(1u64,)).start(&(0u64,)).iter().is_err());
+
+        let err = kv
+            .list()
+            .prefix(&(1u64,))
+            .end(&(0u64,))
+            .entries()
+            .unwrap_err();
+        assert!(matches!(err, KvError::InvalidSelector(_)));
+
+        // Equal bounds are merely empty.
+        assert!(
+            kv.list()
+                .start(&(1u64,))
+                .end(&(1u64,))
+                .entries()?
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn group_entries_by_tenant() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&("tenant", 2u64, "b"), KvValue::I64(4))?;
+        kv.set(&("tenant", 1u64, "a"), KvValue::I64(1))?;
+        kv.set(&("tenant", 2u64, "a"), KvValue::I64(3))?;
+        kv.set(&("tenant", 1u64, "b"), KvValue::I64(2))?;
+        kv.set(&("other", 1u64), KvValue::Null)?;
+
+        let groups = kv.grouped_by_first::<u64>(&("tenant",))?;
+        assert_eq!(groups.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+        assert_eq!(
+            groups[&1],
+            vec![
+                (("tenant", 1u64, "a").to_key(), KvValue::I64(1)),
+                (("tenant", 1u64, "b").to_key(), KvValue::I64(2)),
+            ]
+        );
+        assert_eq!(
+            groups[&2],
+            vec![
+                (("tenant", 2u64, "a").to_key(), KvValue::I64(3)),
+                (("tenant", 2u64, "b").to_key(), KvValue::I64(4)),
+            ]
+        );
+
+        let by_table = kv.grouped_by_first::<String>(&KvKey::new())?;
+        assert_eq!(by_table["tenant"].len(), 4);
+        assert_eq!(by_table["other"].len(), 1);
+        assert!(matches!(
+            kv.grouped_by_first::<bool>(&("tenant",)),
+            Err(KvError::WrongSegmentType { .. })
+        ));
+
+        // A group whose keys aren't adjacent still collects all of them.
+        #[derive(PartialEq, Eq, PartialOrd, Ord)]
+        struct Parity(u64);
+        impl<'a> crate::FromKvKey<'a> for Parity {
+            fn from_kv_key(decoder: &mut crate::KeyDecoder<'a>) -> Option<Self> {
+                decoder.next_u64().map(|n| Parity(n % 2))
+            }
+        }
+        kv.set(&("tenant", 3u64), KvValue::I64(5))?;
+        let by_parity = kv.grouped_by_first::<Parity>(&("tenant",))?;
+        assert_eq!(by_parity[&Parity(1)].len(), 3);
+        assert_eq!(by_parity[&Parity(0)].len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn stats_summarize_contents() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        assert_eq!(kv.stats()?, crate::KvStats::default());
+
+        kv.set(&(1u64,), KvValue::I64(1))?;
+        kv.set(&(2u64,), KvValue::I64(2))?;
+        kv.set(&(3u64, "long key"), KvValue::from("s"))?;
+        kv.set(&(4u64,), KvValue::Binary(vec![0; 100]))?;
+        kv.set(&(5u64,), KvValue::Null)?;
+        kv.set(&(6u64,), KvValue::Array(vec![KvValue::F64(1.0)]))?;
+
+        let stats = kv.stats()?;
+        assert_eq!(stats.entries, 6);
+        assert_eq!(stats.min_key_len, Some((1u64,).to_key().as_bytes().len()));
+        assert_eq!(
+            stats.max_key_len,
+            Some((3u64, "long key").to_key().as_bytes().len())
+        );
+        assert!(stats.value_bytes > 100);
+        let histogram: Vec<_> = stats.value_types.into_iter().collect();
+        assert_eq!(
+            histogram,
+            vec![
+                ("array", 1),
+                ("binary", 1),
+                ("i64", 2),
+                ("null", 1),
+                ("string", 1)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn case_insensitive_prefix_scans() -> KvResult<()> {
+        let mut kv =
+            Kv::new(Box::new(MemoryBackend::new())).with_string_collation(str::to_lowercase);
+        for name in [
+            "carol", "Bob", "bo", "alice", "Alice", "al", "b", "ab", "ALBERT",
+        ] {
+            kv.set(&("users", name, 1u64), KvValue::String(name.into()))?;
+        }
+        kv.set(("users", "bob", 1u64), KvValue::String("bob".into()))?;
+        kv.set(&("Users", "dave", 1u64), KvValue::Null)?;
+
+        // Sorted by the lowercased name whatever its length, ties broken by the original.
+        // The prefix takes in "Users" too, which sorts before "users".
+        let rows: Vec<((String, String, u64), KvValue)> = kv.scan_prefix(&("users",))?;
+        let names: Vec<_> = rows.iter().map(|((_, name, _), _)| name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "dave", "ab", "al", "ALBERT", "Alice", "alice", "b", "bo", "Bob", "bob", "carol"
+            ]
+        );
+
+        // A trailing string in a prefix matches case-insensitively, and only whole segments.
+        let bobs: Vec<((String, String, u64), KvValue)> = kv.scan_prefix(&("users", "BOB"))?;
+        let bobs: Vec<_> = bobs.iter().map(|((_, name, _), _)| name.as_str()).collect();
+        assert_eq!(bobs, ["Bob", "bob"]);
+        assert_eq!(kv.list().prefix(&("users", "b")).entries()?.len(), 1);
+        assert_eq!(kv.list().prefix(&("USERS",)).entries()?.len(), 11);
+        // Earlier segments, and lookups, still match the exact original string.
+        assert!(kv.list().prefix(&("USERS", "bob")).entries()?.is_empty());
+        assert_eq!(kv.get(("users", "Bob", 1u64))?, Some("Bob".into()));
+        assert_eq!(kv.get(("users", "BOB", 1u64))?, None);
+
+        let children = kv.list_prefixes(&("users",))?;
+        assert_eq!(children.len(), 11);
+        let (_, first): (String, String) = children[1].decode_with(kv.key_codec())?;
+        assert_eq!(first, "ab");
+        let groups = kv.grouped_by_first::<u64>(&("users", "ALICE"))?;
+        assert_eq!(groups[&1].len(), 2);
+
+        let users = kv.namespace(&("users",));
+        let scoped = users.list().prefix(&("BOB",)).entries()?;
+        assert_eq!(scoped.len(), 2);
+        assert_eq!(users.get(&("carol", 1u64))?, Some("carol".into()));
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_commits_atomically() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&("stock", "apples"), KvValue::I64(5))?;
+
+        let left = kv.transaction(|txn| {
+            txn.set(&("stock", "apples"), KvValue::I64(4))?;
+            txn.set(&("orders", 1u64), "apples".into())?;
+            assert_eq!(txn.delete(&("missing",))?, None);
+            match txn.get(&("stock", "apples"))? {
+                Some(KvValue::I64(n)) => Ok(n),
+                other => Err(KvError::Other(format!("unexpected {other:?}"))),
+            }
+        })?;
+        assert_eq!(left, 4);
+        assert_eq!(kv.get(&("stock", "apples"))?, Some(KvValue::I64(4)));
+        assert_eq!(kv.get(&("orders", 1u64))?, Some("apples".into()));
+        assert_eq!(kv.len()?, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_rolls_back_on_error() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&("a",), KvValue::I64(1))?;
+
+        let out: KvResult<()> = kv.transaction(|txn| {
+            txn.set(&("a",), KvValue::I64(2))?;
+            txn.delete(&("a",))?;
+            txn.set(&("b",), KvValue::I64(3))?;
+            Err(KvError::Other("abort".into()))
+        });
+        assert!(matches!(out, Err(KvError::Other(msg)) if msg == "abort"));
+        assert_eq!(kv.get(&("a",))?, Some(KvValue::I64(1)));
+        assert_eq!(kv.get(&("b",))?, None);
+        Ok(())
+    }
+
+    #[test]
+    fn transaction_reads_its_own_writes() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(&("x",), KvValue::I64(1))?;
+
+        kv.transaction(|txn| {
+            txn.set(&("y",), KvValue::I64(2))?;
+            assert_eq!(txn.get(&("y",))?, Some(KvValue::I64(2)));
+            assert!(txn.contains_key(&("y",))?);
+
+            assert_eq!(txn.delete(&("x",))?, Some(KvValue::I64(1)));
+            assert_eq!(txn.get(&("x",))?, None);
+            assert!(!txn.contains_key(&("x",))?);
+
+            txn.set(&("x",), KvValue::I64(3))?;
+            assert_eq!(txn.get(&("x",))?, Some(KvValue::I64(3)));
+            Ok(())
+        })?;
+        assert_eq!(kv.get(&("x",))?, Some(KvValue::I64(3)));
+        assert_eq!(kv.get(&("y",))?, Some(KvValue::I64(2)));
+        Ok(())
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn sqlite_transaction_commit_and_rollback() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(SqliteBackend::in_memory()?));
+        kv.transaction(|txn| {
+            for i in 0..10u64 {
+                txn.set(&("row", i), KvValue::I64(i as i64))?;
+            }
+            Ok(())
+        })?;
+        assert_eq!(kv.len()?, 10);
+
+        let out: KvResult<()> = kv.transaction(|txn| {
+            txn.delete(&("row", 0u64))?;
+            Err(KvError::Other("abort".into()))
+        });
+        assert!(out.is_err());
+        assert_eq!(kv.len()?, 10);
+        Ok(())
+    }
+
+    #[test]
+    fn truncate_prefix_reports_count() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        for i in 0..7u64 {
+            kv.set(&("logs", i), KvValue::I64(i as i64))?;
+        }
+        kv.set(&("logs",), KvValue::Null)?;
+        kv.set(&("logsx", 0u64), KvValue::Null)?;
+        kv.set(&("users", 1u64), KvValue::Null)?;
+        let under = kv.list().prefix(&("logs",)).entries()?.len();
+        let before = kv.len()?;
+
+        let removed = kv.truncate_prefix(&("logs",))?;
+        assert_eq!(removed, under);
+        assert_eq!(removed, 8);
+        assert_eq!(kv.len()?, before - removed);
+        assert!(kv.list().prefix(&("logs",)).entries()?.is_empty());
+        assert!(kv.contains_key(&("logsx", 0u64))?);
+
+        assert_eq!(kv.truncate_prefix(&("logs",))?, 0);
+        Ok(())
+    }
+
+    #[test]
+    fn keys_by_value_or_reference() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+
+        // Owned tuples.
+        kv.set((1u64, "owned"), KvValue::I64(1))?;
+        assert_eq!(kv.get((1u64, "owned"))?, Some(KvValue::I64(1)));
+        assert!(kv.contains_key((1u64, "owned"))?);
+
+        // Borrowed tuples and keys, still usable afterwards.
+        let tuple = (2u64, String::from("borrowed"));
+        kv.set(&tuple, KvValue::I64(2))?;
+        let key = (3u64, "encoded").to_key();
+        kv.set(&key, KvValue::I64(3))?;
+        let dynamic: &dyn IntoKey = &(4u64, "dyn");
+        kv.set(dynamic, KvValue::I64(4))?;
+        assert_eq!(kv.get(&tuple)?, Some(KvValue::I64(2)));
+        assert_eq!(kv.get(&key)?, Some(KvValue::I64(3)));
+        assert_eq!(kv.get(dynamic)?, Some(KvValue::I64(4)));
+
+        assert!(kv.delete((1u64, "owned"))?.is_some());
+        assert!(kv.delete(&tuple)?.is_some());
+        assert_eq!(tuple.1, "borrowed");
+        assert!(kv.remove(key)?);
+        assert!(kv.remove(dynamic)?);
+        assert!(kv.is_empty()?);
+
+        let mut ns = kv.namespace(&("ns",));
+        ns.set((1u64,), KvValue::Null)?;
+        assert_eq!(ns.get((1u64,))?, Some(KvValue::Null));
+        assert!(ns.delete((1u64,))?.is_some());
+
+        // The same holds for entries, conditional deletes and transactions.
+        kv.entry((5u64,)).or_insert(KvValue::I64(5))?;
+        kv.transaction(|txn| {
+            assert!(txn.contains_key((5u64,))?);
+            txn.set((6u64,), KvValue::I64(6))?;
+            assert_eq!(txn.get((6u64,))?, Some(KvValue::I64(6)));
+            txn.delete((6u64,))
+        })?;
+        assert!(kv.delete_if((5u64,), &KvValue::I64(5))?);
+        assert!(kv.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn all_ff_prefix_scans_stay_in_prefix() -> KvResult<()> {
+        let backends: Vec<Box<dyn KvBackend>> = vec![
+            Box::new(MemoryBackend::new()),
+            #[cfg(feature = "sqlite")]
+            Box::new(SqliteBackend::in_memory()?),
+        ];
+        for backend in backends {
+            let mut kv = Kv::new(backend);
+            let prefix = KvKey::from_bytes(vec![0xFF, 0xFF]);
+            let inside = [
+                vec![0xFF, 0xFF],
+                vec![0xFF, 0xFF, 0x00],
+                vec![0xFF, 0xFF, 0xFF],
+            ];
+            let outside = [
+                vec![0xFF],
+                vec![0xFF, 0xFE, 0xFF],
+                vec![0xFE, 0xFF, 0xFF, 0x01],
+            ];
+            for bytes in inside.iter().chain(&outside) {
+                kv.set(KvKey::from_bytes(bytes.clone()), KvValue::Null)?;
+            }
+            assert_eq!(prefix.successor(), None);
+
+            let keys: Vec<Vec<u8>> = kv
+                .list()
+                .prefix(&prefix)
+                .entries()?
+                .into_iter()
+                .map(|(key, _)| key.0)
+                .collect();
+            assert_eq!(keys, inside);
+            assert_eq!(kv.list().prefix(&prefix).iter()?.count(), inside.len());
+            assert_eq!(kv.last(Some(&prefix))?.unwrap().0.0, inside[2]);
+
+            let ns = kv.namespace(&prefix);
+            assert_eq!(ns.list().entries()?.len(), inside.len());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn string_prefix_scans_match_only_the_prefix() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let keys = [
+            ("a",).to_key(),
+            ("ab",).to_key(),
+            ("ab", "x").to_key(),
+            ("ab", "xyz", 1u64).to_key(),
+            ("abc",).to_key(),
+            ("abd", "y").to_key(),
+            ("b",).to_key(),
+            ("",).to_key(),
+        ];
+        for key in &keys {
+            kv.set(key, KvValue::Null)?;
+        }
+
+        let expected = [keys[1].clone(), keys[2].clone(), keys[3].clone()];
+        let listed: Vec<KvKey> = kv
+            .list()
+            .prefix(&("ab",))
+            .entries()?
+            .into_iter()
+            .map(|(key, _)| key)
+            .collect();
+        assert_eq!(listed, expected);
+        let iterated = kv
+            .list()
+            .prefix(&("ab",))
+            .iter()?
+            .map(|entry| entry.map(|(key, _)| key))
+            .collect::<KvResult<Vec<_>>>()?;
+        assert_eq!(iterated, expected);
+        for key in &keys {
+            let under = kv.list().prefix(key).entries()?;
+            assert!(under.iter().all(|(k, _)| k.starts_with(key)));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_backends_pass_conformance_suite() {
+        use crate::testing::run_backend_conformance;
+        use crate::{CachedBackend, RwLockMemoryBackend};
+
+        run_backend_conformance(|| Box::new(MemoryBackend::new()));
+        run_backend_conformance(|| Box::new(RwLockMemoryBackend::new()));
+        run_backend_conformance(|| Box::new(CachedBackend::with_capacity(MemoryBackend::new(), 4)));
+        #[cfg(feature = "sqlite")]
+        run_backend_conformance(|| Box::new(SqliteBackend::in_memory().unwrap()));
+        #[cfg(feature = "sqlite")]
+        run_backend_conformance(|| Box::new(SqliteBackend::in_memory().unwrap().with_key_index()));
+    }
+
+    #[test]
+    fn entries_are_sorted_by_key() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        for i in [5u64, 1, 9, 3] {
+            kv.set(("k", i), KvValue::Null)?;
+            kv.set((i,), KvValue::Null)?;
+        }
+        let all: Vec<KvKey> = kv.entries()?.into_iter().map(|(k, _)| k).collect();
+        assert!(all.is_sorted());
+        let listed: Vec<KvKey> = kv
+            .list()
+            .prefix(&("k",))
+            .entries()?
+            .into_iter()
+            .map(|(k, _)| k)
+            .collect();
+        assert!(listed.is_sorted());
+        assert_eq!(listed.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn dump_through_shared_reference() -> KvResult<()> {
+        use std::sync::Arc;
+
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(("a", 1u64), "x".into())?;
+        kv.set(("b",), KvValue::I64(2))?;
+        let kv = Arc::new(kv);
+
+        let held: &Kv = &kv;
+        let first = held.get(("a", 1u64))?;
+        let dumps = (0..4)
+            .map(|_| {
+                let kv = Arc::clone(&kv);
+                std::thread::spawn(move || kv.dump_json())
+            })
+            .collect::<Vec<_>>();
+        let json = held.dump_json()?;
+        for dump in dumps {
+            assert_eq!(dump.join().unwrap()?, json);
+        }
+        assert_eq!(held.to_serde_json()?.as_object().unwrap().len(), 2);
+        assert!(held.dump_json_prefix(&("a",))?.contains("\"x\""));
+        assert_eq!(first, Some("x".into()));
+
+        let restored = Kv::from_json_string(Box::new(MemoryBackend::new()), json)?;
+        assert_eq!(restored.entries()?, kv.entries()?);
+        Ok(())
+    }
+
+    #[test]
+    fn for_each_chunk_sees_every_entry_once() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set_batch((0..250i64).map(|i| ((860u64, i), KvValue::I64(i))))?;
+
+        let mut sizes = Vec::new();
+        let mut seen = Vec::new();
+        kv.for_each_chunk(100, |chunk| {
+            sizes.push(chunk.len());
+            seen.extend(chunk.iter().cloned());
+            Ok(())
+        })?;
+        assert_eq!(sizes, [100, 100, 50]);
+        assert_eq!(seen, kv.entries()?);
+
+        let mut calls = 0;
+        let err = kv.for_each_chunk(100, |_| {
+            calls += 1;
+            Err(KvError::Other("stop".into()))
+        });
+        assert!(matches!(err, Err(KvError::Other(msg)) if msg == "stop"));
+        assert_eq!(calls, 1);
+        assert!(kv.for_each_chunk(0, |_| Ok(())).is_err());
+        Ok(())
+    }
+
+    #[cfg(feature = "net")]
+    #[test]
+    fn scan_an_address_block() -> KvResult<()> {
+        use std::net::{IpAddr, Ipv4Addr};
+
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let hosts: [IpAddr; 6] = [
+            "10.0.1.0".parse().unwrap(),
+            "10.0.0.200".parse().unwrap(),
+            "9.255.255.255".parse().unwrap(),
+            "10.0.0.3".parse().unwrap(),
+            "fd00::1".parse().unwrap(),
+            "10.0.0.0".parse().unwrap(),
+        ];
+        for ip in hosts {
+            kv.set(("hits", ip), KvValue::I64(1))?;
+        }
+
+        // 10.0.0.0/24
+        let block: Vec<IpAddr> = kv
+            .list()
+            .start(&("hits", Ipv4Addr::new(10, 0, 0, 0)))
+            .end(&("hits", Ipv4Addr::new(10, 0, 1, 0)))
+            .entries()?
+            .into_iter()
+            .map(|(key, _)| <(String, IpAddr)>::try_from(key).map(|(_, ip)| ip))
+            .collect::<KvResult<_>>()?;
+        let expected: Vec<IpAddr> = ["10.0.0.0", "10.0.0.3", "10.0.0.200"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+        assert_eq!(block, expected);
+        Ok(())
+    }
+
+    #[test]
+    fn replace_returns_previous_value() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        assert_eq!(kv.replace(("fresh",), KvValue::I64(1))?, None);
+        assert_eq!(kv.get(("fresh",))?, Some(KvValue::I64(1)));
+
+        kv.set(("user", 1u64), KvValue::String("alice".into()))?;
+        let old = kv.replace(("user", 1u64), KvValue::String("bob".into()))?;
+        assert_eq!(old, Some(KvValue::String("alice".into())));
+        assert_eq!(kv.get(("user", 1u64))?, Some(KvValue::String("bob".into())));
+        Ok(())
+    }
+
+    #[test]
+    fn json_binary_encodings_roundtrip() -> KvResult<()> {
+        let blob = KvValue::Binary((0..=255u8).cycle().take(4096).collect());
+        let nested = KvValue::Array(vec![KvValue::Binary(vec![]), KvValue::Binary(vec![0xff])]);
+        let mut dumps = Vec::new();
+        for binary in [JsonBinary::Base64, JsonBinary::ByteArray] {
+            let mut kv = Kv::new(Box::new(MemoryBackend::new())).with_json_binary(binary);
+            kv.set(("blob",), blob.clone())?;
+            kv.set(("nested",), nested.clone())?;
+            let json = kv.dump_json()?;
+
+            // The default restore path reads either form.
+            let loaded = Kv::from_json_string(Box::new(MemoryBackend::new()), json.clone())?;
+            assert_eq!(loaded.get(("blob",))?, Some(blob.clone()));
+            assert_eq!(loaded.get(("nested",))?, Some(nested.clone()));
+
+            let mut ndjson = Vec::new();
+            kv.dump_ndjson(&mut ndjson)?;
+            let mut streamed = Kv::new(Box::new(MemoryBackend::new()));
+            streamed.load_ndjson(ndjson.as_slice())?;
+            assert_eq!(streamed.entries()?, kv.entries()?);
+            dumps.push(json);
+        }
+        // One number per byte averages about 3.6 characters; base64 needs 1.33.
+        assert!(dumps[0].len() * 2 < dumps[1].len());
+        assert!(dumps[0].contains("\"base64url\""));
+        assert!(dumps[1].contains("\"bytes\""));
+        Ok(())
+    }
+
+    #[test]
+    fn non_finite_floats_survive_json_dumps() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(("nan",), KvValue::F64(f64::NAN))?;
+        kv.set(("inf",), KvValue::F64(f64::INFINITY))?;
+        kv.set(("-inf",), KvValue::F64(f64::NEG_INFINITY))?;
+        kv.set(("nested",), KvValue::Array(vec![KvValue::F64(f64::NAN)]))?;
+
+        let json = kv.dump_json()?;
+        assert!(json.contains(r#"{"__sskv_f64_value":"inf"}"#));
+        let loaded = Kv::from_json_string(Box::new(MemoryBackend::new()), json)?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+        assert!(
+            loaded
+                .get(("nan",))?
+                .and_then(|v| v.as_f64())
+                .unwrap()
+                .is_nan()
+        );
+        assert_eq!(
+            loaded.get(("-inf",))?,
+            Some(KvValue::F64(f64::NEG_INFINITY))
+        );
+
+        // JsonCodec stores values as JSON, so it relies on the same tagging.
+        let mut stored = Kv::with_codec(Box::new(MemoryBackend::new()), JsonCodec);
+        stored.set(("inf",), KvValue::F64(f64::INFINITY))?;
+        assert_eq!(stored.get(("inf",))?, Some(KvValue::F64(f64::INFINITY)));
+        for (f, text) in [
+            (f64::NAN, "NaN"),
+            (f64::INFINITY, "inf"),
+            (f64::NEG_INFINITY, "-inf"),
+        ] {
+            let bytes = JsonCodec.encode(&KvValue::F64(f))?;
+            let expected = format!(r#"{{"__sskv_f64_value":"{text}"}}"#);
+            assert_eq!(String::from_utf8(bytes.clone()).unwrap(), expected);
+            let back = JsonCodec.decode(&bytes)?.as_f64().unwrap();
+            assert_eq!(back.to_bits(), f.to_bits());
+        }
+
+        // Only an exact tag with a non-finite value is treated as a float.
+        let lookalike = serde_json::json!({ "__sskv_f64_value": "1.5" });
+        assert!(matches!(KvValue::from(&lookalike), KvValue::Object(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn cloned_list_builders_are_independent() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set_batch((0..10u64).map(|i| ((1u64, i), KvValue::I64(i as i64))))?;
+        kv.set((2u64, 0u64), KvValue::Null)?;
+
+        let mut base = kv.list();
+        base.prefix(&(1u64,));
+        let first_three = base.clone().limit(3).entries()?;
+        let first_seven = base.clone().limit(7).entries()?;
+        assert_eq!(first_three.len(), 3);
+        assert_eq!(first_seven.len(), 7);
+        assert_eq!(first_three[..], first_seven[..3]);
+        // The original is untouched by its clones' limits.
+        assert_eq!(base.entries()?.len(), 10);
+        Ok(())
+    }
+
+    #[test]
+    fn entry_or_insert_only_fills_absent_keys() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        assert_eq!(
+            kv.entry(&("a",)).or_insert(KvValue::I64(1))?,
+            KvValue::I64(1)
+        );
+        assert_eq!(
+            kv.entry(&("a",)).or_insert(KvValue::I64(2))?,
+            KvValue::I64(1)
+        );
+        assert_eq!(kv.get(&("a",))?, Some(KvValue::I64(1)));
+
+        let mut called = false;
+        let v = kv.entry(&("a",)).or_insert_with(|| {
+            called = true;
+            KvValue::Null
+        })?;
+        assert_eq!(v, KvValue::I64(1));
+        assert!(!called);
+        Ok(())
+    }
+
+    #[test]
+    fn entry_and_modify_chains_into_or_insert() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let bump = |kv: &mut Kv| {
+            kv.entry(&("n",))
+                .and_modify(|v| *v = KvValue::I64(v.as_i64().unwrap() + 1))
+                .and_modify(|v| *v = KvValue::I64(v.as_i64().unwrap() * 10))
+                .or_insert(KvValue::I64(0))
+        };
+        // Absent: the default is stored and the modifiers don't run.
+        assert_eq!(bump(&mut kv)?, KvValue::I64(0));
+        // Present: modifiers run in order.
+        assert_eq!(bump(&mut kv)?, KvValue::I64(10));
+        assert_eq!(bump(&mut kv)?, KvValue::I64(110));
+        assert_eq!(kv.get(("n",))?, Some(KvValue::I64(110)));
+
+        // `modify` applies the edits without inserting anything for absent keys.
+        let double = |v: &mut KvValue| *v = KvValue::I64(v.as_i64().unwrap() * 2);
+        assert_eq!(
+            kv.entry(&("n",)).and_modify(double).modify()?,
+            Some(KvValue::I64(220))
+        );
+        assert_eq!(kv.entry(&("m",)).and_modify(double).modify()?, None);
+        assert_eq!(kv.get(("m",))?, None);
+
+        let mut limited = Kv::new(Box::new(MemoryBackend::new())).with_max_key_len(4);
+        let err = limited.entry(&("too long",)).or_insert(KvValue::Null);
+        assert!(matches!(err, Err(KvError::KeyTooLong { .. })));
+        Ok(())
+    }
+
+    #[cfg(feature = "tracing")]
+    #[test]
+    fn operations_emit_tracing_events() -> KvResult<()> {
+        use std::collections::BTreeMap;
+        use std::sync::{Arc, Mutex};
+        use tracing::field::{Field, Visit};
+        use tracing::span::{Attributes, Id, Record};
+        use tracing::{Event, Metadata, Subscriber};
+
+        #[derive(Default)]
+        struct Fields(BTreeMap<&'static str, String>);
+
+        impl Visit for Fields {
+            fn record_u64(&mut self, field: &Field, value: u64) {
+                self.0.insert(field.name(), value.to_string());
+            }
+
+            fn record_str(&mut self, field: &Field, value: &str) {
+                self.0.insert(field.name(), value.to_string());
+            }
+
+            fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+                self.0.insert(field.name(), format!("{value:?}"));
+            }
+        }
+
+        /// Keeps the fields of every event.
+        struct Capture(Arc<Mutex<Vec<BTreeMap<&'static str, String>>>>);
+
+        impl Subscriber for Capture {
+            fn enabled(&self, _: &Metadata<'_>) -> bool {
+                true
+            }
+            fn new_span(&self, _: &Attributes<'_>) -> Id {
+                Id::from_u64(1)
+            }
+            fn record(&self, _: &Id, _: &Record<'_>) {}
+            fn record_follows_from(&self, _: &Id, _: &Id) {}
+            fn event(&self, event: &Event<'_>) {
+                let mut fields = Fields::default();
+                event.record(&mut fields);
+                self.0.lock().unwrap().push(fields.0);
+            }
+            fn enter(&self, _: &Id) {}
+            fn exit(&self, _: &Id) {}
+        }
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let key = ("user", 1u64);
+        tracing::subscriber::with_default(Capture(events.clone()), || -> KvResult<()> {
+            kv.set(key, KvValue::String("alice".into()))?;
+            kv.get(key)?;
+            kv.list().entries()?;
+            kv.remove(key)?;
+            kv.set_batch([(("a",), KvValue::Null), (("b",), KvValue::I64(1))])?;
+            kv.entries_iter().collect::<KvResult<Vec<_>>>()?;
+            let failed: KvResult<()> = kv.transaction(|txn| {
+                txn.set(&("c",), KvValue::Null)?;
+                Err(KvError::Other("rolled back".into()))
+            });
+            assert!(failed.is_err());
+            Ok(())
+        })?;
+
+        let events = events.lock().unwrap();
+        let ops: Vec<&str> = events.iter().map(|e| e["op"].as_str()).collect();
+        assert_eq!(
+            ops,
+            [
+                "set",
+                "get",
+                "get_range",
+                "delete",
+                "set_batch",
+                "iter",
+                "transaction"
+            ]
+        );
+        let outcomes: Vec<&str> = events.iter().map(|e| e["outcome"].as_str()).collect();
+        assert_eq!(outcomes, ["ok", "ok", "ok", "ok", "ok", "ok", "error"]);
+        assert_eq!(events[4]["entries"], "2");
+        assert_eq!(events[5]["entries"], "2");
+        let (set, get) = (&events[0], &events[1]);
+        assert_eq!(set["key_len"], key.to_key().0.len().to_string());
+        assert_eq!(set["value_size"], get["value_size"]);
+        assert!(set.contains_key("elapsed_us"));
+        assert_eq!(events[2]["entries"], "1");
+        assert!(!events[3].contains_key("value_size"));
+        Ok(())
+    }
+
+    #[test]
+    fn keys_as_decodes_every_key() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        for (id, name) in [(3u64, "carol"), (1, "alice"), (2, "bob")] {
+            kv.set((id, name.to_string()), KvValue::Binary(vec![0; 1024]))?;
+        }
+        let keys: Vec<(u64, String)> = kv.keys_as()?;
+        assert_eq!(
+            keys,
+            vec![
+                (1, "alice".to_string()),
+                (2, "bob".to_string()),
+                (3, "carol".to_string())
+            ]
+        );
+
+        // A key of another shape stops the scan with a decode error.
+        kv.set((4u64,), KvValue::Null)?;
+        assert!(matches!(
+            kv.keys_as::<(u64, String)>(),
+            Err(KvError::WrongSegmentCount { .. })
+        ));
+
+        // Keys are decoded with the store's own codec.
+        let mut collated =
+            Kv::new(Box::new(MemoryBackend::new())).with_string_collation(|s| s.to_lowercase());
+        collated.set(("Bob",), KvValue::Null)?;
+        collated.set(("alice",), KvValue::Null)?;
+        assert_eq!(
+            collated.keys_as::<(String,)>()?,
+            [("alice".to_string(),), ("Bob".to_string(),)]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dump_dir_round_trips_through_files() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        kv.set(("config", "theme"), KvValue::String("dark".into()))?;
+        kv.set(("config", "Theme"), KvValue::String("light".into()))?;
+        kv.set(("../escape", "a/b\0c"), KvValue::I64(1))?;
+        kv.set(("blob",), KvValue::Binary(vec![0, 255]))?;
+        kv.set(("long", "x".repeat(300)), KvValue::Bool(true))?;
+        kv.set(KvKey::from_bytes(vec![]), KvValue::Null)?;
+        kv.set(KvKey::from_bytes(vec![0xff, 0x00, 0x2f]), KvValue::Null)?;
+
+        let dir = tempfile::tempdir().unwrap();
+        // Files of the user's that only look like entries must survive and be skipped.
+        let extras = [
+            "README.md",
+            "cafe.json",
+            "deadbeef.json",
+            "key-abc.json",
+            "key-AB.json",
+        ];
+        for name in extras {
+            std::fs::write(dir.path().join(name), "{").unwrap();
+        }
+        std::fs::create_dir(dir.path().join(".git")).unwrap();
+        kv.dump_dir(dir.path())?;
+        assert!(dir.path().join("key-.json").exists());
+        let files = std::fs::read_dir(dir.path()).unwrap().count();
+        // One file per short key, one directory for the long one, plus the extras.
+        assert_eq!(files, 6 + 1 + extras.len() + 1);
+
+        let loaded = Kv::from_dir(Box::new(MemoryBackend::new()), dir.path())?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+
+        // Dumping again after deletes removes the stale files, and nothing else.
+        kv.remove(("long", "x".repeat(300)))?;
+        kv.remove(("blob",))?;
+        kv.dump_dir(dir.path())?;
+        let loaded = Kv::from_dir(Box::new(MemoryBackend::new()), dir.path())?;
+        assert_eq!(loaded.entries()?, kv.entries()?);
+        assert_eq!(
+            std::fs::read_dir(dir.path()).unwrap().count(),
+            5 + extras.len() + 1
+        );
+        for name in extras {
+            assert_eq!(std::fs::read_to_string(dir.path().join(name)).unwrap(), "{");
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn diff_reports_added_removed_and_changed() -> KvResult<()> {
+        let mut old = Kv::new(Box::new(MemoryBackend::new()));
+        let mut new = Kv::with_codec(Box::new(MemoryBackend::new()), JsonCodec);
+        for i in 0..600u64 {
+            old.set((1u64, i), KvValue::I64(i as i64))?;
+            new.set((1u64, i), KvValue::I64(i as i64))?;
+        }
+        old.set(("gone",), KvValue::Null)?;
+        old.set((0u64,), KvValue::Bool(true))?;
+        new.set((1u64, 4u64), KvValue::String("four".into()))?;
+        new.set((1u64, 4u64, "child"), KvValue::Null)?;
+        new.set(("zz",), KvValue::Binary(vec![1]))?;
+        new.remove((1u64, 9u64))?;
+
+        let diffs = old.diff(&new)?;
+        assert_eq!(
+            diffs,
+            vec![
+                KvDiff::Removed((0u64,).to_key(), KvValue::Bool(true)),
+                KvDiff::Changed(
+                    (1u64, 4u64).to_key(),
+                    KvValue::I64(4),
+                    KvValue::String("four".into())
+                ),
+                KvDiff::Added((1u64, 4u64, "child").to_key(), KvValue::Null),
+                KvDiff::Removed((1u64, 9u64).to_key(), KvValue::I64(9)),
+                KvDiff::Added(("zz",).to_key(), KvValue::Binary(vec![1])),
+                KvDiff::Removed(("gone",).to_key(), KvValue::Null),
+            ]
+        );
+        assert!(old.diff(&old)?.is_empty());
+        assert_eq!(new.diff(&old)?.len(), diffs.len());
+
+        // Values that are `==` across variants still count as changed.
+        let mut a = Kv::new(Box::new(MemoryBackend::new()));
+        let mut b = Kv::new(Box::new(MemoryBackend::new()));
+        a.set(("n",), KvValue::I64(5))?;
+        b.set(("n",), KvValue::F64(5.0))?;
+        assert_eq!(
+            a.diff(&b)?,
+            vec![KvDiff::Changed(
+                ("n",).to_key(),
+                KvValue::I64(5),
+                KvValue::F64(5.0)
+            )]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn delete_if_only_removes_matching_values() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let key = ("job", 7u64);
+        kv.set(key, KvValue::String("running".into()))?;
+
+        // Mismatch: nothing happens.
+        assert!(!kv.delete_if(&key, &KvValue::String("done".into()))?);
+        assert!(!kv.delete_if(&key, &KvValue::Null)?);
+        assert_eq!(kv.get(key)?, Some(KvValue::String("running".into())));
+
+        // Match: the key goes away.
+        assert!(kv.delete_if(&key, &KvValue::String("running".into()))?);
+        assert_eq!(kv.get(&key)?, None);
+
+        // Absent: nothing to delete, even for a value that would have matched.
+        assert!(!kv.delete_if(&key, &KvValue::String("running".into()))?);
+        assert!(kv.is_empty()?);
+        Ok(())
+    }
+
+    #[test]
+    fn cloned_handles_update_atomically() -> KvResult<()> {
+        let kv = Kv::new(Box::new(MemoryBackend::new()));
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut kv = kv.clone();
+                std::thread::spawn(move || {
+                    for _ in 0..250 {
+                        kv.update(&("hits",), |v| {
+                            Some(KvValue::I64(v.and_then(|v| v.as_i64()).unwrap_or(0) + 1))
+                        })
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for worker in workers {
+            worker.join().unwrap();
+        }
+        assert_eq!(kv.get(("hits",))?, Some(KvValue::I64(1000)));
+        Ok(())
+    }
+
+    #[test]
+    fn collated_store_dumps_and_restores() -> KvResult<()> {
+        let collated =
+            || Kv::new(Box::new(MemoryBackend::new())).with_string_collation(str::to_lowercase);
+        let mut kv = collated();
+        kv.set(&("users", "Bob"), KvValue::I64(1))?;
+        kv.set(&("users", "alice", 7u64), KvValue::Null)?;
+        kv.set(&(Some("x"), 'c'), KvValue::from("y"))?;
+
+        // Display strings are the same as for a default store.
+        let json = kv.dump_json()?;
+        assert!(json.contains(r#""\"users\":\"Bob\"":1"#), "{json}");
+        let plain = Kv::from_json_string(Box::new(MemoryBackend::new()), json.clone())?;
+        assert_eq!(plain.get(&("users", "Bob"))?, Some(KvValue::I64(1)));
+
+        let mut restored = collated();
+        restored.import_json_str(&json, ImportMode::Replace)?;
+        assert_eq!(restored.entries()?, kv.entries()?);
+
+        let mut ndjson = Vec::new();
+        kv.dump_ndjson(&mut ndjson)?;
+        let mut restored = collated();
+        restored.load_ndjson(ndjson.as_slice())?;
+        assert_eq!(restored.entries()?, kv.entries()?);
+
+        let dir = tempfile::tempdir().unwrap();
+        kv.dump_dir(dir.path())?;
+        let restored = Kv::from_dir(Box::new(MemoryBackend::new()), dir.path())?
+            .with_string_collation(str::to_lowercase);
+        assert_eq!(restored.entries()?, kv.entries()?);
+        assert_eq!(restored.get(&("users", "Bob"))?, Some(KvValue::I64(1)));
+
+        // A key that doesn't decode with the store's codec is a plain error.
+        kv.backend
+            .write()
+            .set(KvKey::from_bytes(vec![0xEE]), Some(KvValue::Null.encode()?))?;
+        assert!(matches!(kv.dump_json(), Err(KvError::KeyDecodeError(_))));
+        Ok(())
+    }
+
+    #[test]
+    fn panicking_closure_leaves_store_usable() -> KvResult<()> {
+        use std::panic::{AssertUnwindSafe, catch_unwind};
+
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        let mut clone = kv.clone();
+        kv.set(&("a",), KvValue::I64(1))?;
+
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            kv.update(&("a",), |_| panic!("boom")).ok();
+        }));
+        assert!(result.is_err());
+        let result = catch_unwind(AssertUnwindSafe(|| {
+            kv.transaction::<()>(|txn| {
+                txn.set(("b",), KvValue::I64(2))?;
+                panic!("boom")
+            })
+            .ok();
+        }));
+        assert!(result.is_err());
+
+        // Neither closure wrote anything, and both handles still work.
+        assert_eq!(kv.get(&("a",))?, Some(KvValue::I64(1)));
+        assert_eq!(clone.get(&("b",))?, None);
+        clone.set(&("b",), KvValue::I64(3))?;
+        assert_eq!(kv.get(&("b",))?, Some(KvValue::I64(3)));
+        Ok(())
+    }
+
+    #[test]
+    fn iterate_without_collecting() -> KvResult<()> {
+        let backend = Box::new(MemoryBackend::new());
+        let mut kv = Kv::new(backend);
+
+        for i in 0..1000i64 {
+            kv.set(&(5u64, i), KvValue::I64(i))?;
         }
-        kv.backend.try_borrow_mut()?.clear()?;
-        let items = kv.entries()?;
-        assert_eq!(items.len(), 0);
+        kv.set(&(6u64, 0i64), KvValue::I64(-1))?;
+
+        let mut sum = 0;
+        for item in kv.list().prefix(&(5u64,)).iter()? {
+            let (_, v) = item?;
+            sum += i64::try_from(v)?;
+        }
+        assert_eq!(sum, (0..1000).sum::<i64>());
+
+        // Stop early without touching the rest of the range.
+        let first_three: Vec<_> = kv
+            .list()
+            .prefix(&(5u64,))
+            .iter()?
+            .take(3)
+            .collect::<KvResult<_>>()?;
+        assert_eq!(first_three.len(), 3);
+        assert_eq!(first_three[2].1, KvValue::I64(2));
+        Ok(())
+    }
+
+    #[test]
+    fn iteration_reads_each_row_once() -> KvResult<()> {
+        use std::sync::Arc;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        /// Counts the rows handed out by range reads; leaves `get_range_iter` at its
+        /// default, which reads the whole range.
+        struct RowCounter {
+            inner: MemoryBackend,
+            rows: Arc<AtomicUsize>,
+        }
+
+        impl KvBackend for RowCounter {
+            fn get_range(
+                &self,
+                start: Option<KvKey>,
+                end: Option<KvKey>,
+            ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+                let rows = self.inner.get_range(start, end)?;
+                self.rows.fetch_add(rows.len(), Ordering::SeqCst);
+                Ok(rows)
+            }
+
+            fn get_range_page(
+                &self,
+                start: Option<KvKey>,
+                end: Option<KvKey>,
+                offset: usize,
+                limit: Option<usize>,
+            ) -> KvResult<Vec<(KvKey, Vec<u8>)>> {
+                let rows = self.inner.get_range_page(start, end, offset, limit)?;
+                self.rows.fetch_add(rows.len(), Ordering::SeqCst);
+                Ok(rows)
+            }
+
+            fn set(&mut self, key: KvKey, value: Option<Vec<u8>>) -> KvResult<()> {
+                self.inner.set(key, value)
+            }
+
+            fn clear(&mut self) -> KvResult<()> {
+                self.inner.clear()
+            }
+        }
+
+        let rows = Arc::new(AtomicUsize::new(0));
+        let mut kv = Kv::new(Box::new(RowCounter {
+            inner: MemoryBackend::new(),
+            rows: rows.clone(),
+        }));
+        for i in 0..2000u64 {
+            kv.set((i,), KvValue::Null)?;
+        }
+
+        assert_eq!(kv.list().iter()?.count(), 2000);
+        assert_eq!(rows.swap(0, Ordering::SeqCst), 2000);
+        assert_eq!(kv.entries_iter().count(), 2000);
+        assert_eq!(rows.swap(0, Ordering::SeqCst), 2000);
         Ok(())
     }
 
@@ -128,22 +2660,46 @@ mod kv_integration_tests {
         let backend = Box::new(MemoryBackend::new());
         let mut kv = Kv::new(backend);
 
-        kv.set(&(1u64,), KvValue::String("foo".to_string()))
-            .unwrap();
-        kv.set(&(2u64,), KvValue::Bool(true)).unwrap();
-        kv.set(&(3u64,), KvValue::I64(999)).unwrap();
+        kv.set((1u64,), KvValue::String("foo".to_string())).unwrap();
+        kv.set((2u64,), KvValue::Bool(true)).unwrap();
+        kv.set((3u64,), KvValue::I64(999)).unwrap();
 
         let orig_entries = { kv.entries().unwrap() };
         // Dump and reload
         let json = kv.dump_json().unwrap();
         println!("Serialized: {json}");
         let backend2 = Box::new(MemoryBackend::new());
-        let mut kv2 = Kv::from_json_string(backend2, json).unwrap();
+        let kv2 = Kv::from_json_string(backend2, json).unwrap();
 
         let new_entries = kv2.entries().unwrap();
         assert_eq!(orig_entries, new_entries);
     }
 
+    #[cfg(feature = "sqlite")]
+    #[test]
+    fn migrate_memory_to_sqlite() -> KvResult<()> {
+        let mut kv = Kv::new(Box::new(MemoryBackend::new()));
+        // Enough entries to span several batches.
+        for i in 0..600i64 {
+            kv.set(&(7u64, i), KvValue::I64(i))?;
+        }
+        kv.set(&("meta", "name"), KvValue::String("migrated".to_owned()))?;
+        let ns = kv.namespace(&("meta",));
+        let before = kv.entries()?;
+
+        kv.migrate_to(Box::new(SqliteBackend::in_memory()?))?;
+        assert_eq!(kv.entries()?, before);
+        assert_eq!(
+            ns.get(&("name",))?,
+            Some(KvValue::String("migrated".to_owned()))
+        );
+
+        // Writes now land in the new backend.
+        kv.set(&(7u64, 600i64), KvValue::I64(600))?;
+        assert_eq!(kv.len()?, 602);
+        Ok(())
+    }
+
     #[cfg(feature = "sqlite")]
     #[test]
     fn json_roundtrip_sqlite() -> KvResult<()> {
@@ -158,7 +2714,7 @@ mod kv_integration_tests {
 
         let json = kv.dump_json().unwrap();
         let backend2 = Box::new(SqliteBackend::in_memory()?);
-        let mut kv2 = Kv::from_json_string(backend2, json).unwrap();
+        let kv2 = Kv::from_json_string(backend2, json).unwrap();
         let new_entries = kv2.entries().unwrap();
         assert_eq!(orig_entries, new_entries);
         Ok(())
@@ -0,0 +1,111 @@
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+/// Times one operation and reports it as a `DEBUG` event with target `stupid_simple_kv`.
+///
+/// The event is sent when the timer is dropped, so an operation cut short by `?` is still
+/// reported, with `outcome = "error"`; calling one of the `finish` methods marks it `"ok"`.
+/// Point operations record `op`, `key_len`, `value_size` (when there is a value) and
+/// `elapsed_us`; range reads and batch writes record `op`, `entries`, `value_size` (the
+/// total) and `elapsed_us`.
+#[cfg(feature = "tracing")]
+pub(crate) struct OpTimer {
+    op: &'static str,
+    key_len: Option<usize>,
+    entries: Option<usize>,
+    value_size: Option<usize>,
+    ok: bool,
+    start: Instant,
+}
+
+#[cfg(feature = "tracing")]
+impl OpTimer {
+    pub(crate) fn start(op: &'static str, key_len: usize) -> Self {
+        let mut timer = Self::start_range(op);
+        timer.key_len = Some(key_len);
+        timer
+    }
+
+    pub(crate) fn start_range(op: &'static str) -> Self {
+        Self {
+            op,
+            key_len: None,
+            entries: None,
+            value_size: None,
+            ok: false,
+            start: Instant::now(),
+        }
+    }
+
+    /// Record the sizes of the values a range or batch operation touches, before it runs.
+    pub(crate) fn values(mut self, sizes: impl IntoIterator<Item = usize>) -> Self {
+        let (mut entries, mut total) = (0, 0);
+        for size in sizes {
+            entries += 1;
+            total += size;
+        }
+        self.entries = Some(entries);
+        self.value_size = Some(total);
+        self
+    }
+
+    pub(crate) fn finish(mut self, value_size: Option<usize>) {
+        self.value_size = value_size;
+        self.ok = true;
+    }
+
+    pub(crate) fn finish_range(self, sizes: impl IntoIterator<Item = usize>) {
+        self.values(sizes).succeed();
+    }
+
+    /// Mark the operation successful, keeping the sizes recorded so far.
+    pub(crate) fn succeed(mut self) {
+        self.ok = true;
+    }
+}
+
+#[cfg(feature = "tracing")]
+impl Drop for OpTimer {
+    fn drop(&mut self) {
+        tracing::debug!(
+            target: "stupid_simple_kv",
+            op = self.op,
+            outcome = if self.ok { "ok" } else { "error" },
+            key_len = self.key_len.map(|n| n as u64),
+            entries = self.entries.map(|n| n as u64),
+            value_size = self.value_size.map(|n| n as u64),
+            elapsed_us = self.start.elapsed().as_micros() as u64,
+        );
+    }
+}
+
+/// Without the `tracing` feature timers are zero-sized and do nothing, so they compile away.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct OpTimer;
+
+#[cfg(not(feature = "tracing"))]
+impl OpTimer {
+    #[inline(always)]
+    pub(crate) fn start(_op: &'static str, _key_len: usize) -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    pub(crate) fn start_range(_op: &'static str) -> Self {
+        Self
+    }
+
+    #[inline(always)]
+    pub(crate) fn values(self, _sizes: impl IntoIterator<Item = usize>) -> Self {
+        self
+    }
+
+    #[inline(always)]
+    pub(crate) fn finish(self, _value_size: Option<usize>) {}
+
+    #[inline(always)]
+    pub(crate) fn finish_range(self, _sizes: impl IntoIterator<Item = usize>) {}
+
+    #[inline(always)]
+    pub(crate) fn succeed(self) {}
+}
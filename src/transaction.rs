@@ -0,0 +1,73 @@
+use std::collections::BTreeMap;
+
+use crate::value_codec::decode_stored;
+use crate::{IntoKey, Kv, KvBackend, KvKey, KvResult, KvValue};
+
+/// A group of reads and writes applied atomically, handed out by [`Kv::transaction`].
+///
+/// Writes are buffered until the transaction commits; reads see the buffered writes first
+/// and fall back to the backend. The backend stays write-locked for the whole transaction,
+/// so no clone of the [`Kv`] can read or write in between.
+pub struct Txn<'a> {
+    kv: &'a Kv,
+    backend: &'a mut dyn KvBackend,
+    writes: BTreeMap<KvKey, Option<Vec<u8>>>,
+}
+
+impl<'a> Txn<'a> {
+    pub(crate) fn new(kv: &'a Kv, backend: &'a mut dyn KvBackend) -> Self {
+        Self {
+            kv,
+            backend,
+            writes: BTreeMap::new(),
+        }
+    }
+
+    fn get_raw(&self, key: &KvKey) -> KvResult<Option<Vec<u8>>> {
+        match self.writes.get(key) {
+            Some(pending) => Ok(pending.clone()),
+            None => self.backend.get(key),
+        }
+    }
+
+    /// Retrieve the value for a key, including writes made earlier in this transaction.
+    pub fn get(&self, key: impl IntoKey) -> KvResult<Option<KvValue>> {
+        self.get_raw(&self.kv.checked_key(&key)?)?
+            .map(|bytes| decode_stored(&*self.kv.codec, &bytes))
+            .transpose()
+    }
+
+    /// Whether a value is stored at `key`, including writes made earlier in this transaction.
+    pub fn contains_key(&self, key: impl IntoKey) -> KvResult<bool> {
+        let key = self.kv.checked_key(&key)?;
+        match self.writes.get(&key) {
+            Some(pending) => Ok(pending.is_some()),
+            None => self.backend.contains_key(&key),
+        }
+    }
+
+    /// Set the value for a key when the transaction commits.
+    ///
+    /// The value is encoded (and checked against the store's size limit) right away.
+    pub fn set(&mut self, key: impl IntoKey, value: KvValue) -> KvResult<()> {
+        let key = self.kv.checked_key(&key)?;
+        let encoded = self.kv.encode_value(&value)?;
+        self.writes.insert(key, Some(encoded));
+        Ok(())
+    }
+
+    /// Delete a key when the transaction commits, returning the value it holds now.
+    pub fn delete(&mut self, key: impl IntoKey) -> KvResult<Option<KvValue>> {
+        let key = self.kv.checked_key(&key)?;
+        let previous = self
+            .get_raw(&key)?
+            .map(|bytes| decode_stored(&*self.kv.codec, &bytes))
+            .transpose()?;
+        self.writes.insert(key, None);
+        Ok(previous)
+    }
+
+    pub(crate) fn into_writes(self) -> Vec<(KvKey, Option<Vec<u8>>)> {
+        self.writes.into_iter().collect()
+    }
+}
@@ -0,0 +1,79 @@
+use std::marker::PhantomData;
+
+use crate::{FromKvKey, IntoKey, KeyDecoder, Kv, KvBackend, KvError, KvKey, KvResult, KvValue};
+
+/// A [`Kv`] whose keys and values all have fixed Rust types.
+///
+/// Keys are any `K` that can be both encoded ([`IntoKey`]) and decoded ([`FromKvKey`]), such
+/// as a tuple. Values are any `V` convertible to and from [`KvValue`]; conversion failures
+/// are reported as [`KvError`]s.
+///
+/// # Example
+/// ```rust
+/// use stupid_simple_kv::{MemoryBackend, TypedKv};
+/// let mut names: TypedKv<(u64, String), String> = TypedKv::new(Box::new(MemoryBackend::new()));
+/// let key = (1, "first".to_string());
+/// names.set(&key, "alice".to_string()).unwrap();
+/// assert_eq!(names.get(&key).unwrap(), Some("alice".to_string()));
+/// ```
+pub struct TypedKv<K, V> {
+    pub(crate) kv: Kv,
+    _types: PhantomData<fn() -> (K, V)>,
+}
+
+impl<K, V> TypedKv<K, V>
+where
+    K: IntoKey + for<'a> FromKvKey<'a>,
+    V: Into<KvValue> + TryFrom<KvValue>,
+    <V as TryFrom<KvValue>>::Error: Into<KvError>,
+{
+    /// Create a new [`TypedKv`] with the given backend.
+    pub fn new(backend: Box<dyn KvBackend>) -> Self {
+        Self {
+            kv: Kv::new(backend),
+            _types: PhantomData,
+        }
+    }
+
+    /// Retrieve the value for a key.
+    pub fn get(&self, key: &K) -> KvResult<Option<V>> {
+        self.kv.get(key)?.map(Self::value).transpose()
+    }
+
+    /// Set the value for a key, overwriting it if present.
+    pub fn set(&mut self, key: &K, value: V) -> KvResult<()> {
+        self.kv.set(key, value.into())
+    }
+
+    /// Delete a key, returning its previous value if present.
+    pub fn delete(&mut self, key: &K) -> KvResult<Option<V>> {
+        self.kv
+            .delete(key)?
+            .map(|(_, value)| Self::value(value))
+            .transpose()
+    }
+
+    /// List every entry in the store.
+    ///
+    /// # Errors
+    /// Fails if any stored key or value does not have the expected type.
+    pub fn entries(&self) -> KvResult<Vec<(K, V)>> {
+        self.kv
+            .list()
+            .map(|(key, value)| Ok((Self::key(&key)?, Self::value(value)?)))
+    }
+
+    fn key(key: &KvKey) -> KvResult<K> {
+        let mut decoder = KeyDecoder::new(&key.0);
+        match K::from_kv_key(&mut decoder) {
+            Some(out) if decoder.is_empty() => Ok(out),
+            _ => Err(KvError::KeyDecodeError(format!(
+                "Key {key:?} does not match the store's key type"
+            ))),
+        }
+    }
+
+    fn value(value: KvValue) -> KvResult<V> {
+        V::try_from(value).map_err(Into::into)
+    }
+}
@@ -0,0 +1,95 @@
+use crate::{KvError, KvResult, KvValue};
+
+/// A byte encoding for stored values.
+///
+/// A [`crate::Kv`] hands every value it writes to its codec's [`ValueCodec::encode`] and
+/// every stored blob it reads back to [`ValueCodec::decode`]. The default is
+/// [`BincodeCodec`]; pick another with [`crate::Kv::with_codec`]. The store adds its own
+/// one-byte format header in front of the encoded bytes, so codecs never see it.
+///
+/// Values written with one codec generally can't be read with another, so a store should
+/// stick to one.
+pub trait ValueCodec: Send + Sync {
+    /// A stable identifier for this encoding, suitable for recording alongside persisted data.
+    fn id(&self) -> &'static str;
+
+    fn encode(&self, value: &KvValue) -> KvResult<Vec<u8>>;
+    fn decode(&self, bytes: &[u8]) -> KvResult<KvValue>;
+}
+
+/// Format version byte written in front of every stored value.
+///
+/// Values stored before the header existed start with a `bincode` variant tag (or, for
+/// other codecs, ASCII text), which is always below `0x80`; any other high byte is a
+/// version this build doesn't know.
+pub(crate) const VALUE_VERSION: u8 = 0x81;
+
+/// Encode `value` with `codec`, prefixed with [`VALUE_VERSION`].
+pub(crate) fn encode_stored(codec: &dyn ValueCodec, value: &KvValue) -> KvResult<Vec<u8>> {
+    let encoded = codec.encode(value)?;
+    let mut bytes = Vec::with_capacity(encoded.len() + 1);
+    bytes.push(VALUE_VERSION);
+    bytes.extend_from_slice(&encoded);
+    Ok(bytes)
+}
+
+/// Decode a stored value, accepting both headered and headerless (pre-header) values.
+pub(crate) fn decode_stored(codec: &dyn ValueCodec, bytes: &[u8]) -> KvResult<KvValue> {
+    match bytes.first() {
+        Some(&VALUE_VERSION) => codec.decode(&bytes[1..]),
+        Some(&version) if is_versioned(version) => Err(KvError::UnsupportedValueVersion(version)),
+        _ => codec.decode(bytes),
+    }
+}
+
+/// Whether a stored value starting with `first` carries a version header.
+pub(crate) fn is_versioned(first: u8) -> bool {
+    first >= 0x80
+}
+
+/// The default value codec: `bincode` with its standard configuration.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BincodeCodec;
+
+impl ValueCodec for BincodeCodec {
+    fn id(&self) -> &'static str {
+        "bincode-standard-v1"
+    }
+
+    fn encode(&self, value: &KvValue) -> KvResult<Vec<u8>> {
+        value.encode()
+    }
+
+    fn decode(&self, bytes: &[u8]) -> KvResult<KvValue> {
+        KvValue::decode(bytes)
+    }
+}
+
+/// Stores values as UTF-8 JSON text, in the format [`crate::Kv::dump_json`] writes values
+/// in, except that binary blobs are always [`JsonBinary::ByteArray`] whatever
+/// [`crate::Kv::with_json_binary`] is set to, so stored bytes never depend on it.
+///
+/// [`JsonBinary::ByteArray`]: crate::JsonBinary::ByteArray
+///
+/// Handy when other tools need to read the backend directly. It is larger and slower than
+/// [`BincodeCodec`]. NaN and the infinities, which JSON can't represent, are stored as
+/// `{"__sskv_f64_value": "NaN"}` (or `"inf"`, `"-inf"`) and read back as `F64`.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JsonCodec;
+
+impl ValueCodec for JsonCodec {
+    fn id(&self) -> &'static str {
+        "json-v1"
+    }
+
+    fn encode(&self, value: &KvValue) -> KvResult<Vec<u8>> {
+        serde_json::to_vec(&serde_json::Value::from(value))
+            .map_err(|e| KvError::Other(format!("serde error encoding json value: {e}")))
+    }
+
+    fn decode(&self, bytes: &[u8]) -> KvResult<KvValue> {
+        let json: serde_json::Value = serde_json::from_slice(bytes)
+            .map_err(|e| KvError::Other(format!("serde error parsing json value: {e}")))?;
+        Ok(KvValue::from(&json))
+    }
+}
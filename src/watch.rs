@@ -0,0 +1,47 @@
+use std::sync::Mutex;
+use std::sync::mpsc::{Receiver, Sender, channel};
+
+use crate::KvKey;
+
+/// What happened to a key, as reported by [`KvEvent`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KvEventKind {
+    Set,
+    Delete,
+}
+
+/// A change notification delivered to receivers created by [`crate::Kv::subscribe`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KvEvent {
+    pub key: KvKey,
+    pub kind: KvEventKind,
+}
+
+/// Broadcasts write events to every live subscriber.
+#[derive(Default)]
+pub(crate) struct Subscribers {
+    senders: Mutex<Vec<(Option<KvKey>, Sender<KvEvent>)>>,
+}
+
+impl Subscribers {
+    pub(crate) fn subscribe(&self, prefix: Option<KvKey>) -> Receiver<KvEvent> {
+        let (tx, rx) = channel();
+        self.senders.lock().unwrap().push((prefix, tx));
+        rx
+    }
+
+    /// Send an event to every subscriber whose prefix matches `key`, dropping subscribers
+    /// whose receivers have gone away.
+    pub(crate) fn publish(&self, key: &KvKey, kind: KvEventKind) {
+        self.senders.lock().unwrap().retain(|(prefix, tx)| {
+            if prefix.as_ref().is_some_and(|p| !key.starts_with(p)) {
+                return true;
+            }
+            let event = KvEvent {
+                key: key.clone(),
+                kind,
+            };
+            tx.send(event).is_ok()
+        });
+    }
+}